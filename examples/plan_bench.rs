@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+const HELP: &str = "\
+Compares shaping with planning on every run against shaping with a prebuilt plan.
+
+USAGE:
+    plan_bench FONT-FILE TEXT [ITERATIONS]
+
+ARGS:
+    FONT-FILE                           A path to font file
+    TEXT                                A text to shape
+    ITERATIONS                          Number of runs [default: 10000]
+";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 2 || args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{}", HELP);
+        return;
+    }
+
+    let iterations: u32 = match args.get(2) {
+        Some(s) => s.parse().expect("invalid number of iterations"),
+        None => 10000,
+    };
+
+    let font_data = std::fs::read(&args[0]).expect("failed to read the font file");
+    let font = rustybuzz::Font::from_slice(&font_data, 0).expect("failed to parse the font");
+
+    let make_buffer = || {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&args[1]);
+        buffer.guess_segment_properties();
+        buffer
+    };
+
+    let shape_time = measure(iterations, || {
+        rustybuzz::shape(&font, &[], make_buffer());
+    });
+
+    let plan = rustybuzz::ShapePlan::new(&font, &make_buffer(), &[]);
+    let plan_time = measure(iterations, || {
+        rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &[]).unwrap();
+    });
+
+    println!("shape:           {:?} per run", shape_time / iterations);
+    println!("shape_with_plan: {:?} per run", plan_time / iterations);
+}
+
+fn measure<F: FnMut()>(iterations: u32, mut f: F) -> Duration {
+    // Warm up caches.
+    f();
+
+    let now = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+
+    now.elapsed()
+}
@@ -198,6 +198,7 @@ void hb_buffer_t::reset()
     flags = HB_BUFFER_FLAG_DEFAULT;
     replacement = HB_BUFFER_REPLACEMENT_CODEPOINT_DEFAULT;
     invisible = 0;
+    not_found = 0;
 
     clear();
 }
@@ -587,6 +588,7 @@ DEFINE_NULL_INSTANCE(hb_buffer_t) = {
     HB_BUFFER_CLUSTER_LEVEL_DEFAULT,
     HB_BUFFER_REPLACEMENT_CODEPOINT_DEFAULT,
     0, /* invisible */
+    0, /* not_found */
     HB_BUFFER_SCRATCH_FLAG_DEFAULT,
     HB_BUFFER_MAX_LEN_DEFAULT,
     HB_BUFFER_MAX_OPS_DEFAULT,
@@ -677,6 +679,8 @@ void hb_buffer_destroy(hb_buffer_t *buffer)
 
     free(buffer->info);
     free(buffer->pos);
+    if (buffer->message_destroy)
+        buffer->message_destroy(buffer->message_data);
     free(buffer);
 }
 
@@ -1003,6 +1007,36 @@ hb_codepoint_t hb_buffer_get_invisible_glyph(hb_buffer_t *buffer)
     return buffer->invisible;
 }
 
+/**
+ * hb_buffer_set_not_found_glyph:
+ * @buffer: an #hb_buffer_t.
+ * @not_found: the not-found #hb_codepoint_t
+ *
+ * Sets the #hb_codepoint_t that replaces characters not found in
+ * the font during shaping.  The default is zero, the `.notdef` glyph.
+ **/
+void hb_buffer_set_not_found_glyph(hb_buffer_t *buffer, hb_codepoint_t not_found)
+{
+    if (unlikely(hb_object_is_immutable(buffer)))
+        return;
+
+    buffer->not_found = not_found;
+}
+
+/**
+ * hb_buffer_get_not_found_glyph:
+ * @buffer: an #hb_buffer_t.
+ *
+ * See hb_buffer_set_not_found_glyph().
+ *
+ * Return value:
+ * The @buffer not-found #hb_codepoint_t.
+ **/
+hb_codepoint_t hb_buffer_get_not_found_glyph(hb_buffer_t *buffer)
+{
+    return buffer->not_found;
+}
+
 /**
  * hb_buffer_reset:
  * @buffer: an #hb_buffer_t.
@@ -1615,6 +1649,16 @@ void hb_buffer_unsafe_to_break_from_outbuffer(hb_buffer_t *buffer, unsigned int
     buffer->unsafe_to_break(start, end);
 }
 
+void hb_buffer_unsafe_to_concat(hb_buffer_t *buffer, unsigned int start, unsigned int end)
+{
+    buffer->unsafe_to_concat(start, end);
+}
+
+void hb_buffer_safe_to_insert_tatweel(hb_buffer_t *buffer, unsigned int start, unsigned int end)
+{
+    buffer->safe_to_insert_tatweel(start, end);
+}
+
 void hb_buffer_merge_clusters(hb_buffer_t *buffer, unsigned int start, unsigned int end)
 {
     buffer->merge_clusters(start, end);
@@ -1639,3 +1683,59 @@ void hb_buffer_sort(hb_buffer_t *buffer, unsigned int start, unsigned int end, i
 {
     buffer->sort(start, end, compar);
 }
+
+/*
+ * Debugging.
+ */
+
+/**
+ * hb_buffer_set_message_func:
+ * @buffer: an #hb_buffer_t.
+ * @func: (closure user_data) (destroy destroy) (scope notified):
+ * @user_data:
+ * @destroy:
+ *
+ * Sets a callback that is called with a short description
+ * of each shaping step. Returning false from the callback
+ * skips the step, when possible.
+ *
+ * Since: 1.1.3
+ **/
+void hb_buffer_set_message_func(hb_buffer_t *buffer,
+                                hb_buffer_message_func_t func,
+                                void *user_data,
+                                hb_destroy_func_t destroy)
+{
+    if (buffer->message_destroy)
+        buffer->message_destroy(buffer->message_data);
+
+    if (func) {
+        buffer->message_func = func;
+        buffer->message_data = user_data;
+        buffer->message_destroy = destroy;
+    } else {
+        buffer->message_func = nullptr;
+        buffer->message_data = nullptr;
+        buffer->message_destroy = nullptr;
+    }
+}
+
+hb_bool_t hb_buffer_messaging(hb_buffer_t *buffer)
+{
+    return buffer->messaging();
+}
+
+/* Unlike hb_buffer_t::message(), the message is not formatted and not truncated. */
+hb_bool_t hb_buffer_message(hb_buffer_t *buffer, hb_font_t *font, const char *message)
+{
+    if (!buffer->messaging())
+        return true;
+    return buffer->message_func(buffer, font, message, buffer->message_data);
+}
+
+bool hb_buffer_t::message_impl(hb_font_t *font, const char *fmt, va_list ap)
+{
+    char buf[100];
+    vsnprintf(buf, sizeof(buf), fmt, ap);
+    return (bool)this->message_func(this, font, buf, this->message_data);
+}
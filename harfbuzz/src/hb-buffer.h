@@ -89,14 +89,28 @@ typedef struct hb_glyph_info_t
  * 				   of each line after line-breaking, or limiting
  * 				   the reshaping to a small piece around the
  * 				   breaking point only.
+ * 				   Implies @HB_GLYPH_FLAG_UNSAFE_TO_CONCAT.
+ * @HB_GLYPH_FLAG_UNSAFE_TO_CONCAT: Indicates that if input text is changed on one
+ * 				   side of the beginning of the cluster this glyph is
+ * 				   part of, then the shaping results for the other
+ * 				   side might change.  Only produced when the buffer
+ * 				   has @HB_BUFFER_FLAG_PRODUCE_UNSAFE_TO_CONCAT set,
+ * 				   except that it's always implied by
+ * 				   @HB_GLYPH_FLAG_UNSAFE_TO_BREAK.
+ * @HB_GLYPH_FLAG_SAFE_TO_INSERT_TATWEEL: Indicates that it's safe to insert a U+0640
+ * 				   TATWEEL character before this cluster for
+ * 				   elongation.  Only produced when the buffer has
+ * 				   @HB_BUFFER_FLAG_PRODUCE_SAFE_TO_INSERT_TATWEEL set.
  * @HB_GLYPH_FLAG_DEFINED: All the currently defined flags.
  *
  * Since: 1.5.0
  */
 typedef enum { /*< flags >*/
                HB_GLYPH_FLAG_UNSAFE_TO_BREAK = 0x00000001,
+               HB_GLYPH_FLAG_UNSAFE_TO_CONCAT = 0x00000002,
+               HB_GLYPH_FLAG_SAFE_TO_INSERT_TATWEEL = 0x00000004,
 
-               HB_GLYPH_FLAG_DEFINED = 0x00000001 /* OR of all defined flags */
+               HB_GLYPH_FLAG_DEFINED = 0x00000007 /* OR of all defined flags */
 } hb_glyph_flags_t;
 
 HB_EXTERN hb_glyph_flags_t hb_glyph_info_get_glyph_flags(const hb_glyph_info_t *info);
@@ -238,6 +252,19 @@ HB_EXTERN void hb_buffer_guess_segment_properties(hb_buffer_t *buffer);
  *                      flag indicating that a dotted circle should
  *                      not be inserted in the rendering of incorrect
  *                      character sequences (such at <0905 093E>). Since: 2.4
+ * @HB_BUFFER_FLAG_DECOMPOSE_PRESENTATION_FORMS:
+ *                      flag indicating that Arabic presentation forms
+ *                      should be decomposed into plain letters before joining.
+ * @HB_BUFFER_FLAG_PRODUCE_UNSAFE_TO_CONCAT:
+ *                      flag indicating that the @HB_GLYPH_FLAG_UNSAFE_TO_CONCAT
+ *                      glyph-flag should be produced by the shaper.
+ * @HB_BUFFER_FLAG_PRODUCE_SAFE_TO_INSERT_TATWEEL:
+ *                      flag indicating that the @HB_GLYPH_FLAG_SAFE_TO_INSERT_TATWEEL
+ *                      glyph-flag should be produced by the shaper.
+ * @HB_BUFFER_FLAG_MOVE_MARKS_WITH_STCH_TILES:
+ *                      flag indicating that marks of a glyph stretched
+ *                      by the Arabic stch feature should be moved
+ *                      together with its tiles.
  *
  * Since: 0.9.20
  */
@@ -247,7 +274,11 @@ typedef enum { /*< flags >*/
                HB_BUFFER_FLAG_EOT = 0x00000002u, /* End-of-text */
                HB_BUFFER_FLAG_PRESERVE_DEFAULT_IGNORABLES = 0x00000004u,
                HB_BUFFER_FLAG_REMOVE_DEFAULT_IGNORABLES = 0x00000008u,
-               HB_BUFFER_FLAG_DO_NOT_INSERT_DOTTED_CIRCLE = 0x00000010u
+               HB_BUFFER_FLAG_DO_NOT_INSERT_DOTTED_CIRCLE = 0x00000010u,
+               HB_BUFFER_FLAG_DECOMPOSE_PRESENTATION_FORMS = 0x00000020u,
+               HB_BUFFER_FLAG_PRODUCE_UNSAFE_TO_CONCAT = 0x00000040u,
+               HB_BUFFER_FLAG_PRODUCE_SAFE_TO_INSERT_TATWEEL = 0x00000080u,
+               HB_BUFFER_FLAG_MOVE_MARKS_WITH_STCH_TILES = 0x00000100u
 } hb_buffer_flags_t;
 
 HB_EXTERN void hb_buffer_set_flags(hb_buffer_t *buffer, hb_buffer_flags_t flags);
@@ -294,6 +325,10 @@ HB_EXTERN void hb_buffer_set_invisible_glyph(hb_buffer_t *buffer, hb_codepoint_t
 
 HB_EXTERN hb_codepoint_t hb_buffer_get_invisible_glyph(hb_buffer_t *buffer);
 
+HB_EXTERN void hb_buffer_set_not_found_glyph(hb_buffer_t *buffer, hb_codepoint_t not_found);
+
+HB_EXTERN hb_codepoint_t hb_buffer_get_not_found_glyph(hb_buffer_t *buffer);
+
 HB_EXTERN void hb_buffer_reset(hb_buffer_t *buffer);
 
 HB_EXTERN void hb_buffer_clear_contents(hb_buffer_t *buffer);
@@ -376,6 +411,10 @@ HB_EXTERN void hb_buffer_unsafe_to_break(hb_buffer_t *buffer, unsigned int start
 
 HB_EXTERN void hb_buffer_unsafe_to_break_from_outbuffer(hb_buffer_t *buffer, unsigned int start, unsigned int end);
 
+HB_EXTERN void hb_buffer_unsafe_to_concat(hb_buffer_t *buffer, unsigned int start, unsigned int end);
+
+HB_EXTERN void hb_buffer_safe_to_insert_tatweel(hb_buffer_t *buffer, unsigned int start, unsigned int end);
+
 HB_EXTERN void hb_buffer_merge_clusters(hb_buffer_t *buffer, unsigned int start, unsigned int end);
 
 HB_EXTERN void hb_buffer_merge_out_clusters(hb_buffer_t *buffer, unsigned int start, unsigned int end);
@@ -384,6 +423,24 @@ HB_EXTERN void hb_buffer_swap_buffers(hb_buffer_t *buffer);
 
 HB_EXTERN void hb_buffer_sort(hb_buffer_t *buffer, unsigned int start, unsigned int end, int (*compar)(const hb_glyph_info_t *, const hb_glyph_info_t *));
 
+/*
+ * Debugging.
+ */
+
+typedef hb_bool_t (*hb_buffer_message_func_t)(hb_buffer_t *buffer,
+                                              hb_font_t *font,
+                                              const char *message,
+                                              void *user_data);
+
+HB_EXTERN void hb_buffer_set_message_func(hb_buffer_t *buffer,
+                                          hb_buffer_message_func_t func,
+                                          void *user_data,
+                                          hb_destroy_func_t destroy);
+
+HB_EXTERN hb_bool_t hb_buffer_messaging(hb_buffer_t *buffer);
+
+HB_EXTERN hb_bool_t hb_buffer_message(hb_buffer_t *buffer, hb_font_t *font, const char *message);
+
 HB_END_DECLS
 
 #endif /* HB_BUFFER_H */
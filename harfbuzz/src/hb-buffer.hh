@@ -63,8 +63,9 @@ enum hb_buffer_scratch_flags_t {
     HB_BUFFER_SCRATCH_FLAG_HAS_DEFAULT_IGNORABLES = 0x00000002u,
     HB_BUFFER_SCRATCH_FLAG_HAS_SPACE_FALLBACK = 0x00000004u,
     HB_BUFFER_SCRATCH_FLAG_HAS_GPOS_ATTACHMENT = 0x00000008u,
-    HB_BUFFER_SCRATCH_FLAG_HAS_UNSAFE_TO_BREAK = 0x00000010u,
+    HB_BUFFER_SCRATCH_FLAG_HAS_GLYPH_FLAGS = 0x00000010u,
     HB_BUFFER_SCRATCH_FLAG_HAS_CGJ = 0x00000020u,
+    HB_BUFFER_SCRATCH_FLAG_HAS_MISSING_VARIATION = 0x00000040u,
 
     /* Reserved for complex shapers' internal use. */
     HB_BUFFER_SCRATCH_FLAG_COMPLEX0 = 0x01000000u,
@@ -87,6 +88,7 @@ struct hb_buffer_t
     hb_buffer_cluster_level_t cluster_level;
     hb_codepoint_t replacement;              /* U+FFFD or something else. */
     hb_codepoint_t invisible;                /* 0 or something else. */
+    hb_codepoint_t not_found;                /* 0 or something else. */
     hb_buffer_scratch_flags_t scratch_flags; /* Have space-fallback, etc. */
     unsigned int max_len;                    /* Maximum allowed len. */
     int max_ops;                             /* Maximum allowed operations. */
@@ -117,6 +119,11 @@ struct hb_buffer_t
     hb_codepoint_t context[2][CONTEXT_LENGTH];
     unsigned int context_len[2];
 
+    /* Debugging API */
+    hb_buffer_message_func_t message_func;
+    void *message_data;
+    hb_destroy_func_t message_destroy;
+
     /* Methods */
 
     bool in_error() const
@@ -124,6 +131,22 @@ struct hb_buffer_t
         return !successful;
     }
 
+    bool messaging()
+    {
+        return unlikely(message_func);
+    }
+    bool message(hb_font_t *font, const char *fmt, ...) HB_PRINTF_FUNC(3, 4)
+    {
+        if (!messaging())
+            return true;
+        va_list ap;
+        va_start(ap, fmt);
+        bool ret = message_impl(font, fmt, ap);
+        va_end(ap);
+        return ret;
+    }
+    HB_INTERNAL bool message_impl(hb_font_t *font, const char *fmt, va_list ap) HB_PRINTF_FUNC(3, 0);
+
     hb_glyph_info_t &cur(unsigned int i = 0)
     {
         return info[idx + i];
@@ -301,6 +324,21 @@ struct hb_buffer_t
     HB_INTERNAL void unsafe_to_break_impl(unsigned int start, unsigned int end);
     HB_INTERNAL void unsafe_to_break_from_outbuffer(unsigned int start, unsigned int end);
 
+    void unsafe_to_concat(unsigned int start, unsigned int end)
+    {
+        if (likely((flags & HB_BUFFER_FLAG_PRODUCE_UNSAFE_TO_CONCAT) == 0))
+            return;
+        _set_glyph_flags(HB_GLYPH_FLAG_UNSAFE_TO_CONCAT, start, end);
+    }
+    void safe_to_insert_tatweel(unsigned int start, unsigned int end)
+    {
+        if ((flags & HB_BUFFER_FLAG_PRODUCE_SAFE_TO_INSERT_TATWEEL) == 0) {
+            unsafe_to_break(start, end);
+            return;
+        }
+        _set_glyph_flags(HB_GLYPH_FLAG_SAFE_TO_INSERT_TATWEEL, start, end, true);
+    }
+
     /* Internal methods */
     HB_INTERNAL bool move_to(unsigned int i); /* i is output-buffer index. */
 
@@ -332,12 +370,8 @@ struct hb_buffer_t
 
     static void set_cluster(hb_glyph_info_t &inf, unsigned int cluster, unsigned int mask = 0)
     {
-        if (inf.cluster != cluster) {
-            if (mask & HB_GLYPH_FLAG_UNSAFE_TO_BREAK)
-                inf.mask |= HB_GLYPH_FLAG_UNSAFE_TO_BREAK;
-            else
-                inf.mask &= ~HB_GLYPH_FLAG_UNSAFE_TO_BREAK;
-        }
+        if (inf.cluster != cluster)
+            inf.mask = (inf.mask & ~HB_GLYPH_FLAG_DEFINED) | (mask & HB_GLYPH_FLAG_DEFINED);
         inf.cluster = cluster;
     }
 
@@ -354,10 +388,23 @@ struct hb_buffer_t
     {
         for (unsigned int i = start; i < end; i++)
             if (cluster != infos[i].cluster) {
-                scratch_flags |= HB_BUFFER_SCRATCH_FLAG_HAS_UNSAFE_TO_BREAK;
-                infos[i].mask |= HB_GLYPH_FLAG_UNSAFE_TO_BREAK;
+                scratch_flags |= HB_BUFFER_SCRATCH_FLAG_HAS_GLYPH_FLAGS;
+                infos[i].mask |= HB_GLYPH_FLAG_UNSAFE_TO_BREAK | HB_GLYPH_FLAG_UNSAFE_TO_CONCAT;
             }
     }
+    /* With `interior`, the first cluster of the range is left as is. */
+    void _set_glyph_flags(hb_mask_t mask, unsigned int start, unsigned int end, bool interior = false)
+    {
+        end = hb_min(end, len);
+        if (end <= start || (interior && end - start < 2))
+            return;
+
+        scratch_flags |= HB_BUFFER_SCRATCH_FLAG_HAS_GLYPH_FLAGS;
+        unsigned int cluster = interior ? _unsafe_to_break_find_min_cluster(info, start, end, UINT_MAX) : UINT_MAX;
+        for (unsigned int i = start; i < end; i++)
+            if (!interior || cluster != info[i].cluster)
+                info[i].mask |= mask;
+    }
 
     void unsafe_to_break_all()
     {
@@ -366,7 +413,7 @@ struct hb_buffer_t
     void safe_to_break_all()
     {
         for (unsigned int i = 0; i < len; i++)
-            info[i].mask &= ~HB_GLYPH_FLAG_UNSAFE_TO_BREAK;
+            info[i].mask &= ~(HB_GLYPH_FLAG_UNSAFE_TO_BREAK | HB_GLYPH_FLAG_UNSAFE_TO_CONCAT);
     }
 };
 DECLARE_NULL_INSTANCE(hb_buffer_t);
@@ -1613,12 +1613,17 @@ hb_ot_map_t::apply(const Proxy &proxy, const hb_ot_shape_plan_t *plan, hb_font_t
                 c.set_random(true);
                 buffer->unsafe_to_break_all();
             }
+            if (!buffer->message(font, "start lookup %d", lookup_index))
+                continue;
             apply_string<Proxy>(&c, proxy.table.get_lookup(lookup_index), proxy.accels[lookup_index]);
+            (void)buffer->message(font, "end lookup %d", lookup_index);
         }
 
         if (stage->pause_func) {
             buffer->clear_output();
+            (void)buffer->message(font, "start pause %d", stage_index);
             stage->pause_func(plan, font, buffer);
+            (void)buffer->message(font, "end pause %d", stage_index);
         }
     }
 }
@@ -67,12 +67,66 @@ void hb_ot_map_get_stage_lookups(const hb_ot_map_t *map,
     map->get_stage_lookups(table_index, stage, plookups, lookup_count);
 }
 
+unsigned int hb_ot_map_get_feature_count(const hb_ot_map_t *map)
+{
+    return map->get_feature_count();
+}
+
+bool hb_ot_map_get_feature(const hb_ot_map_t *map,
+                           unsigned int table_index,
+                           unsigned int index,
+                           hb_tag_t *tag,
+                           unsigned int *stage)
+{
+    const hb_ot_map_t::feature_map_t &feature = map->get_feature(index);
+    if (feature.index[table_index] == HB_OT_LAYOUT_NO_FEATURE_INDEX)
+        return false;
+
+    *tag = feature.tag;
+    *stage = feature.stage[table_index];
+    return true;
+}
+
+unsigned int hb_ot_map_get_stage_count(const hb_ot_map_t *map, unsigned int table_index)
+{
+    return map->get_stage_count(table_index);
+}
+
+bool hb_ot_map_stage_has_pause(const hb_ot_map_t *map, unsigned int table_index, unsigned int stage)
+{
+    return map->stage_has_pause(table_index, stage);
+}
+
+bool hb_ot_map_needs_fallback(const hb_ot_map_t *map, hb_tag_t tag)
+{
+    return map->needs_fallback(tag);
+}
+
+bool hb_ot_map_disable_lookup(hb_ot_map_t *map, unsigned int table_index, unsigned int lookup_index)
+{
+    return map->disable_lookup(table_index, lookup_index);
+}
+
 void hb_ot_map_t::collect_lookups(unsigned int table_index, hb_set_t *lookups_out) const
 {
     for (unsigned int i = 0; i < lookups[table_index].length; i++)
         lookups_out->add(lookups[table_index][i].index);
 }
 
+/* A lookup with a zero mask doesn't match any glyph, so it's skipped,
+ * while stages and pauses stay the same. */
+bool hb_ot_map_t::disable_lookup(unsigned int table_index, unsigned int lookup_index)
+{
+    bool found = false;
+    for (unsigned int i = 0; i < lookups[table_index].length; i++) {
+        if (lookups[table_index][i].index == lookup_index) {
+            lookups[table_index][i].mask = 0;
+            found = true;
+        }
+    }
+    return found;
+}
+
 hb_ot_map_builder_t::hb_ot_map_builder_t(hb_face_t *face_, const hb_segment_properties_t *props_)
 {
     memset(this, 0, sizeof(*this));
@@ -160,7 +160,28 @@ public:
         *lookup_count = end - start;
     }
 
+    unsigned int get_feature_count() const
+    {
+        return features.length;
+    }
+
+    const feature_map_t &get_feature(unsigned int index) const
+    {
+        return features[index];
+    }
+
+    unsigned int get_stage_count(unsigned int table_index) const
+    {
+        return stages[table_index].length;
+    }
+
+    bool stage_has_pause(unsigned int table_index, unsigned int stage) const
+    {
+        return stage < stages[table_index].length && stages[table_index][stage].pause_func;
+    }
+
     HB_INTERNAL void collect_lookups(unsigned int table_index, hb_set_t *lookups) const;
+    HB_INTERNAL bool disable_lookup(unsigned int table_index, unsigned int lookup_index);
     template <typename Proxy>
     HB_INTERNAL void
     apply(const Proxy &proxy, const struct hb_ot_shape_plan_t *plan, hb_font_t *font, hb_buffer_t *buffer) const;
@@ -296,6 +317,17 @@ HB_EXTERN void hb_ot_map_get_stage_lookups(const hb_ot_map_t *map,
                                            const struct hb_ot_map_lookup_map_t **plookups,
                                            unsigned int *lookup_count);
 
+HB_EXTERN unsigned int hb_ot_map_get_feature_count(const hb_ot_map_t *map);
+HB_EXTERN bool hb_ot_map_get_feature(const hb_ot_map_t *map,
+                                     unsigned int table_index,
+                                     unsigned int index,
+                                     hb_tag_t *tag,
+                                     unsigned int *stage);
+HB_EXTERN unsigned int hb_ot_map_get_stage_count(const hb_ot_map_t *map, unsigned int table_index);
+HB_EXTERN bool hb_ot_map_stage_has_pause(const hb_ot_map_t *map, unsigned int table_index, unsigned int stage);
+HB_EXTERN bool hb_ot_map_needs_fallback(const hb_ot_map_t *map, hb_tag_t tag);
+HB_EXTERN bool hb_ot_map_disable_lookup(hb_ot_map_t *map, unsigned int table_index, unsigned int lookup_index);
+
 HB_EXTERN void hb_ot_map_builder_add_feature(hb_ot_map_builder_t *builder,
                                              hb_tag_t tag,
                                              hb_ot_map_feature_flags_t flags,
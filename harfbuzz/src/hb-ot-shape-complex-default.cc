@@ -41,7 +41,7 @@ const hb_ot_complex_shaper_t _hb_ot_complex_shaper_arabic = {
     nullptr, /* override_features */
     hb_ot_complex_data_create_arabic,
     hb_ot_complex_data_destroy_arabic,
-    nullptr, /* preprocess_text */
+    hb_ot_complex_preprocess_text_arabic,
     hb_ot_complex_postprocess_glyphs_arabic,
     HB_OT_SHAPE_NORMALIZATION_MODE_DEFAULT,
     nullptr, /* decompose */
@@ -380,6 +380,8 @@ HB_EXTERN void hb_ot_complex_collect_features_arabic(hb_ot_shape_planner_t *plan
 HB_EXTERN void
 hb_ot_complex_postprocess_glyphs_arabic(const hb_ot_shape_plan_t *plan, hb_buffer_t *buffer, hb_font_t *font);
 HB_EXTERN void hb_ot_complex_setup_masks_arabic(const hb_ot_shape_plan_t *plan, hb_buffer_t *buffer, hb_font_t *font);
+HB_EXTERN void
+hb_ot_complex_preprocess_text_arabic(const hb_ot_shape_plan_t *plan, hb_buffer_t *buffer, hb_font_t *font);
 HB_EXTERN void hb_ot_complex_reorder_marks_arabic(const hb_ot_shape_plan_t *plan,
                                                   hb_buffer_t *buffer,
                                                   unsigned int start,
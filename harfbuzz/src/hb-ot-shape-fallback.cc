@@ -29,129 +29,6 @@
 #include "hb-ot-shape-fallback.hh"
 #include "hb-kern.hh"
 
-static unsigned int recategorize_combining_class(hb_codepoint_t u, unsigned int klass)
-{
-    if (klass >= 200)
-        return klass;
-
-    /* Thai / Lao need some per-character work. */
-    if ((u & ~0xFF) == 0x0E00u) {
-        if (unlikely(klass == 0)) {
-            switch (u) {
-            case 0x0E31u:
-            case 0x0E34u:
-            case 0x0E35u:
-            case 0x0E36u:
-            case 0x0E37u:
-            case 0x0E47u:
-            case 0x0E4Cu:
-            case 0x0E4Du:
-            case 0x0E4Eu:
-                klass = HB_UNICODE_COMBINING_CLASS_ABOVE_RIGHT;
-                break;
-
-            case 0x0EB1u:
-            case 0x0EB4u:
-            case 0x0EB5u:
-            case 0x0EB6u:
-            case 0x0EB7u:
-            case 0x0EBBu:
-            case 0x0ECCu:
-            case 0x0ECDu:
-                klass = HB_UNICODE_COMBINING_CLASS_ABOVE;
-                break;
-
-            case 0x0EBCu:
-                klass = HB_UNICODE_COMBINING_CLASS_BELOW;
-                break;
-            }
-        } else {
-            /* Thai virama is below-right */
-            if (u == 0x0E3Au)
-                klass = HB_UNICODE_COMBINING_CLASS_BELOW_RIGHT;
-        }
-    }
-
-    switch (klass) {
-
-        /* Hebrew */
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC10: /* sheva */
-    case HB_MODIFIED_COMBINING_CLASS_CCC11: /* hataf segol */
-    case HB_MODIFIED_COMBINING_CLASS_CCC12: /* hataf patah */
-    case HB_MODIFIED_COMBINING_CLASS_CCC13: /* hataf qamats */
-    case HB_MODIFIED_COMBINING_CLASS_CCC14: /* hiriq */
-    case HB_MODIFIED_COMBINING_CLASS_CCC15: /* tsere */
-    case HB_MODIFIED_COMBINING_CLASS_CCC16: /* segol */
-    case HB_MODIFIED_COMBINING_CLASS_CCC17: /* patah */
-    case HB_MODIFIED_COMBINING_CLASS_CCC18: /* qamats */
-    case HB_MODIFIED_COMBINING_CLASS_CCC20: /* qubuts */
-    case HB_MODIFIED_COMBINING_CLASS_CCC22: /* meteg */
-        return HB_UNICODE_COMBINING_CLASS_BELOW;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC23: /* rafe */
-        return HB_UNICODE_COMBINING_CLASS_ATTACHED_ABOVE;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC24: /* shin dot */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE_RIGHT;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC25: /* sin dot */
-    case HB_MODIFIED_COMBINING_CLASS_CCC19: /* holam */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE_LEFT;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC26: /* point varika */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC21: /* dagesh */
-        break;
-
-        /* Arabic and Syriac */
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC27: /* fathatan */
-    case HB_MODIFIED_COMBINING_CLASS_CCC28: /* dammatan */
-    case HB_MODIFIED_COMBINING_CLASS_CCC30: /* fatha */
-    case HB_MODIFIED_COMBINING_CLASS_CCC31: /* damma */
-    case HB_MODIFIED_COMBINING_CLASS_CCC33: /* shadda */
-    case HB_MODIFIED_COMBINING_CLASS_CCC34: /* sukun */
-    case HB_MODIFIED_COMBINING_CLASS_CCC35: /* superscript alef */
-    case HB_MODIFIED_COMBINING_CLASS_CCC36: /* superscript alaph */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC29: /* kasratan */
-    case HB_MODIFIED_COMBINING_CLASS_CCC32: /* kasra */
-        return HB_UNICODE_COMBINING_CLASS_BELOW;
-
-        /* Thai */
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC103: /* sara u / sara uu */
-        return HB_UNICODE_COMBINING_CLASS_BELOW_RIGHT;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC107: /* mai */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE_RIGHT;
-
-        /* Lao */
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC118: /* sign u / sign uu */
-        return HB_UNICODE_COMBINING_CLASS_BELOW;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC122: /* mai */
-        return HB_UNICODE_COMBINING_CLASS_ABOVE;
-
-        /* Tibetan */
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC129: /* sign aa */
-        return HB_UNICODE_COMBINING_CLASS_BELOW;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC130: /* sign i*/
-        return HB_UNICODE_COMBINING_CLASS_ABOVE;
-
-    case HB_MODIFIED_COMBINING_CLASS_CCC132: /* sign u */
-        return HB_UNICODE_COMBINING_CLASS_BELOW;
-    }
-
-    return klass;
-}
-
 void _hb_ot_shape_fallback_mark_position_recategorize_marks(const hb_ot_shape_plan_t *plan HB_UNUSED,
                                                             hb_font_t *font HB_UNUSED,
                                                             hb_buffer_t *buffer)
@@ -161,7 +38,7 @@ void _hb_ot_shape_fallback_mark_position_recategorize_marks(const hb_ot_shape_pl
     for (unsigned int i = 0; i < count; i++)
         if (_hb_glyph_info_get_general_category(&info[i]) == HB_UNICODE_GENERAL_CATEGORY_NON_SPACING_MARK) {
             unsigned int combining_class = _hb_glyph_info_get_modified_combining_class(&info[i]);
-            combining_class = recategorize_combining_class(info[i].codepoint, combining_class);
+            combining_class = hb_ucd_recategorize_combining_class(info[i].codepoint, combining_class);
             _hb_glyph_info_set_modified_combining_class(&info[i], combining_class);
         }
 }
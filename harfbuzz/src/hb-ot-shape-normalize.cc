@@ -83,9 +83,10 @@ compose_unicode(const hb_ot_shape_normalize_context_t *c, hb_codepoint_t a, hb_c
     return (bool)hb_ucd_compose(a, b, ab);
 }
 
-static inline void set_glyph(hb_glyph_info_t &info, hb_font_t *font)
+static inline void set_glyph(hb_glyph_info_t &info, hb_font_t *font, hb_codepoint_t not_found)
 {
-    hb_font_get_nominal_glyph(font, info.codepoint, &info.glyph_index());
+    if (!hb_font_get_nominal_glyph(font, info.codepoint, &info.glyph_index()))
+        info.glyph_index() = not_found;
 }
 
 static inline void output_char(hb_buffer_t *buffer, hb_codepoint_t unichar, hb_codepoint_t glyph)
@@ -190,7 +191,8 @@ static inline void decompose_current_character(const hb_ot_shape_normalize_conte
         }
     }
 
-    next_char(buffer, glyph); /* glyph is initialized in earlier branches. */
+    /* The character is not in the font. */
+    next_char(buffer, buffer->not_found);
 }
 
 static inline void handle_variation_selector_cluster(const hb_ot_shape_normalize_context_t *c,
@@ -208,23 +210,24 @@ static inline void handle_variation_selector_cluster(const hb_ot_shape_normalize
                 buffer->replace_glyphs(2, 1, &unicode);
             } else {
                 /* Just pass on the two characters separately, let GSUB do its magic. */
-                set_glyph(buffer->cur(), font);
+                buffer->scratch_flags |= HB_BUFFER_SCRATCH_FLAG_HAS_MISSING_VARIATION;
+                set_glyph(buffer->cur(), font, buffer->not_found);
                 buffer->next_glyph();
-                set_glyph(buffer->cur(), font);
+                set_glyph(buffer->cur(), font, buffer->not_found);
                 buffer->next_glyph();
             }
             /* Skip any further variation selectors. */
             while (buffer->idx < end && unlikely(hb_ucd_is_variation_selector(buffer->cur().codepoint))) {
-                set_glyph(buffer->cur(), font);
+                set_glyph(buffer->cur(), font, buffer->not_found);
                 buffer->next_glyph();
             }
         } else {
-            set_glyph(buffer->cur(), font);
+            set_glyph(buffer->cur(), font, buffer->not_found);
             buffer->next_glyph();
         }
     }
     if (likely(buffer->idx < end)) {
-        set_glyph(buffer->cur(), font);
+        set_glyph(buffer->cur(), font, buffer->not_found);
         buffer->next_glyph();
     }
 }
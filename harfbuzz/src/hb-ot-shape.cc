@@ -59,6 +59,11 @@ bool hb_ot_shape_plan_has_gpos_mark(const hb_ot_shape_plan_t *plan)
     return plan->has_gpos_mark;
 }
 
+bool hb_ot_shape_plan_fallback_mark_positioning(const hb_ot_shape_plan_t *plan)
+{
+    return plan->fallback_mark_positioning;
+}
+
 hb_ot_map_builder_t* hb_ot_shape_planner_get_ot_map(hb_ot_shape_planner_t *planner)
 {
     return &planner->map;
@@ -738,7 +743,9 @@ static inline void hb_ot_substitute_default(const hb_ot_shape_context_t *c)
 
     hb_ot_rotate_chars(c);
 
+    (void)buffer->message(c->font, "start normalize");
     _hb_ot_shape_normalize(c->plan, buffer, c->font);
+    (void)buffer->message(c->font, "end normalize");
 
     hb_ot_shape_setup_masks(c);
 
@@ -758,7 +765,9 @@ static inline void hb_ot_substitute_complex(const hb_ot_shape_context_t *c)
     if (c->plan->fallback_glyph_classes)
         hb_synthesize_glyph_classes(c->buffer);
 
-    c->plan->substitute(c->font, buffer);
+    if (buffer->message(c->font, "start table GSUB"))
+        c->plan->substitute(c->font, buffer);
+    (void)buffer->message(c->font, "end table GSUB");
 }
 
 static inline void hb_ot_substitute_pre(const hb_ot_shape_context_t *c)
@@ -773,8 +782,11 @@ static inline void hb_ot_substitute_post(const hb_ot_shape_context_t *c)
     if (c->plan->apply_morx)
         hb_aat_layout_remove_deleted_glyphs(c->buffer);
 
-    if (c->plan->shaper->postprocess_glyphs)
+    if (c->plan->shaper->postprocess_glyphs) {
+        (void)c->buffer->message(c->font, "start postprocess-glyphs");
         c->plan->shaper->postprocess_glyphs(c->plan, c->buffer, c->font);
+        (void)c->buffer->message(c->font, "end postprocess-glyphs");
+    }
 }
 
 /*
@@ -856,7 +868,9 @@ static inline void hb_ot_position_complex(const hb_ot_shape_context_t *c)
             break;
         }
 
-    c->plan->position(c->font, c->buffer);
+    if (c->buffer->message(c->font, "start table GPOS"))
+        c->plan->position(c->font, c->buffer);
+    (void)c->buffer->message(c->font, "end table GPOS");
 
     if (c->plan->zero_marks)
         switch (c->plan->shaper->zero_width_marks) {
@@ -898,7 +912,7 @@ static inline void hb_propagate_flags(hb_buffer_t *buffer)
     /* Propagate cluster-level glyph flags to be the same on all cluster glyphs.
      * Simplifies using them. */
 
-    if (!(buffer->scratch_flags & HB_BUFFER_SCRATCH_FLAG_HAS_UNSAFE_TO_BREAK))
+    if (!(buffer->scratch_flags & HB_BUFFER_SCRATCH_FLAG_HAS_GLYPH_FLAGS))
         return;
 
     hb_glyph_info_t *info = buffer->info;
@@ -907,10 +921,9 @@ static inline void hb_propagate_flags(hb_buffer_t *buffer)
     {
         unsigned int mask = 0;
         for (unsigned int i = start; i < end; i++)
-            if (info[i].mask & HB_GLYPH_FLAG_UNSAFE_TO_BREAK) {
-                mask = HB_GLYPH_FLAG_UNSAFE_TO_BREAK;
-                break;
-            }
+            mask |= info[i].mask & HB_GLYPH_FLAG_DEFINED;
+        if (mask & HB_GLYPH_FLAG_UNSAFE_TO_BREAK)
+            mask |= HB_GLYPH_FLAG_UNSAFE_TO_CONCAT;
         if (mask)
             for (unsigned int i = start; i < end; i++)
                 info[i].mask |= mask;
@@ -942,8 +955,11 @@ static void hb_ot_shape_internal(hb_ot_shape_context_t *c)
 
     hb_ensure_native_direction(c->buffer);
 
-    if (c->plan->shaper->preprocess_text)
+    if (c->plan->shaper->preprocess_text) {
+        (void)c->buffer->message(c->font, "start preprocess-text");
         c->plan->shaper->preprocess_text(c->plan, c->buffer, c->font);
+        (void)c->buffer->message(c->font, "end preprocess-text");
+    }
 
     hb_ot_substitute_pre(c);
     hb_ot_position(c);
@@ -165,3 +165,13 @@ hb_bool_t hb_shape_plan_execute(hb_shape_plan_t *shape_plan,
     _hb_ot_shape(shape_plan, font, buffer, features, num_features);
     return true;
 }
+
+const hb_ot_shape_plan_t *hb_shape_plan_get_ot_shape_plan(const hb_shape_plan_t *shape_plan)
+{
+    return &shape_plan->ot;
+}
+
+hb_ot_map_t *hb_shape_plan_get_ot_map_mut(hb_shape_plan_t *shape_plan)
+{
+    return &shape_plan->ot.map;
+}
@@ -58,6 +58,10 @@ HB_EXTERN hb_bool_t hb_shape_plan_execute(hb_shape_plan_t *shape_plan,
                                           const hb_feature_t *features,
                                           unsigned int num_features);
 
+HB_EXTERN const struct hb_ot_shape_plan_t *hb_shape_plan_get_ot_shape_plan(const hb_shape_plan_t *shape_plan);
+
+HB_EXTERN struct hb_ot_map_t *hb_shape_plan_get_ot_map_mut(hb_shape_plan_t *shape_plan);
+
 HB_END_DECLS
 
 #endif /* HB_SHAPE_PLAN_H */
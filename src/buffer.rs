@@ -1,36 +1,82 @@
 use std::convert::TryFrom;
 use std::fmt;
+use std::os::raw::{c_char, c_void};
 use std::ptr::NonNull;
 
-use ttf_parser::Tag;
-
 use crate::Font;
-use crate::common::{Direction, Language, Script};
+use crate::common::{script, Direction, Language, Script};
+use crate::complex::ArabicForm;
 use crate::ffi;
-use crate::unicode::{GeneralCategory, GeneralCategoryExt};
+use crate::unicode::{GeneralCategory, GeneralCategoryExt, GeneralCategoryGroups};
+
 
+bitflags::bitflags! {
+    /// Glyph flags.
+    ///
+    /// Mirrors harfbuzz's `hb_glyph_flags_t`, with the same values.
+    #[derive(Default)]
+    pub struct GlyphFlags: u32 {
+        /// Indicates that if input text is broken at the
+        /// beginning of the cluster this glyph is part of,
+        /// then both sides need to be re-shaped, as the
+        /// result might be different.  On the flip side,
+        /// it means that when this flag is not present,
+        /// then it's safe to break the glyph-run at the
+        /// beginning of this cluster, and the two sides
+        /// represent the exact same result one would get
+        /// if breaking input text at the beginning of
+        /// this cluster and shaping the two sides
+        /// separately.  This can be used to optimize
+        /// paragraph layout, by avoiding re-shaping
+        /// of each line after line-breaking, or limiting
+        /// the reshaping to a small piece around the
+        /// breaking point only.
+        ///
+        /// Shapers that work on syllables, like Indic, Khmer, Myanmar and USE,
+        /// mark every glyph inside a syllable as unsafe to break, so syllable
+        /// boundaries are exactly the cluster starts without this flag.
+        /// There is no separate syllable accessor, because syllables are
+        /// internal to shaping and are cleared after `GSUB`.
+        ///
+        /// Implies `UNSAFE_TO_CONCAT`.
+        const UNSAFE_TO_BREAK = 0x00000001;
+
+        /// Indicates that if input text is changed on one
+        /// side of the beginning of the cluster this glyph
+        /// is part of, then the shaping results for the
+        /// other side might change.
+        ///
+        /// Only produced when the buffer has
+        /// `BufferFlags::PRODUCE_UNSAFE_TO_CONCAT` set,
+        /// except that it's always set along with `UNSAFE_TO_BREAK`.
+        const UNSAFE_TO_CONCAT = 0x00000002;
+
+        /// Indicates that it's safe to insert a U+0640 TATWEEL
+        /// character before this cluster for elongation.
+        ///
+        /// Only produced when the buffer has
+        /// `BufferFlags::PRODUCE_SAFE_TO_INSERT_TATWEEL` set.
+        const SAFE_TO_INSERT_TATWEEL = 0x00000004;
+
+        /// All the currently defined flags.
+        ///
+        /// Bits outside of this mask are used internally and must be ignored.
+        const DEFINED = 0x00000007; // OR of all defined flags
+    }
+}
 
-pub(crate) mod glyph_flag {
-    /// Indicates that if input text is broken at the
-    /// beginning of the cluster this glyph is part of,
-    /// then both sides need to be re-shaped, as the
-    /// result might be different.  On the flip side,
-    /// it means that when this flag is not present,
-    /// then it's safe to break the glyph-run at the
-    /// beginning of this cluster, and the two sides
-    /// represent the exact same result one would get
-    /// if breaking input text at the beginning of
-    /// this cluster and shaping the two sides
-    /// separately.  This can be used to optimize
-    /// paragraph layout, by avoiding re-shaping
-    /// of each line after line-breaking, or limiting
-    /// the reshaping to a small piece around the
-    /// breaking point only.
-    #[allow(dead_code)]
-    pub const UNSAFE_TO_BREAK: u32 = 0x00000001;
 
-    /// All the currently defined flags.
-    pub const DEFINED: u32 = 0x00000001; // OR of all defined flags
+/// A glyph positions rounding mode.
+///
+/// See `GlyphBuffer::round_positions`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoundMode {
+    /// Positions are left as is.
+    None,
+    /// Only advances are rounded.
+    Advances,
+    /// Advances and offsets are rounded.
+    All,
 }
 
 
@@ -55,12 +101,23 @@ pub struct GlyphPosition {
     var: u32,
 }
 
+impl GlyphPosition {
+    /// Creates a new glyph position.
+    #[inline]
+    pub fn new(x_advance: i32, y_advance: i32, x_offset: i32, y_offset: i32) -> Self {
+        GlyphPosition { x_advance, y_advance, x_offset, y_offset, var: 0 }
+    }
+}
+
 
 /// A glyph info.
 #[derive(Clone, Copy, Default, Debug)]
 #[repr(C)]
 pub struct GlyphInfo {
     /// A selected glyph.
+    ///
+    /// Holds a Unicode code point before glyphs are mapped
+    /// and a glyph ID afterwards.
     pub codepoint: u32,
     pub(crate) mask: ffi::hb_mask_t,
     /// An original cluster index.
@@ -70,11 +127,61 @@ pub struct GlyphInfo {
 }
 
 impl GlyphInfo {
+    /// Creates a new glyph info with a Unicode code point and a cluster.
+    ///
+    /// Meant for Unicode content, i.e. before shaping.
+    /// The mask and all the internal shaping properties are zeroed.
+    #[inline]
+    pub fn new(codepoint: u32, cluster: u32) -> Self {
+        GlyphInfo {
+            codepoint,
+            cluster,
+            ..GlyphInfo::default()
+        }
+    }
+
+    /// Returns glyph flags.
+    ///
+    /// Only the flags from the `GlyphFlags::DEFINED` set are returned.
     #[inline]
-    pub(crate) fn as_char(&self) -> char {
+    pub fn flags(&self) -> GlyphFlags {
+        GlyphFlags::from_bits_truncate(self.mask)
+    }
+
+    /// Returns the positional form selected by cursive joining.
+    ///
+    /// Set by the Arabic shaper, which also handles Syriac, Mongolian and other joining scripts.
+    /// The form is recorded before the `stch` feature, so stretched glyphs keep it.
+    /// A ligature has the form of its first component.
+    ///
+    /// `None` for non-joining and transparent characters, like spaces and marks,
+    /// and for glyphs shaped by other shapers.
+    #[inline]
+    pub fn positional_form(&self) -> Option<ArabicForm> {
+        self.arabic_positional_form()
+    }
+
+    /// Returns `codepoint` as a character.
+    ///
+    /// Valid only before shaping, while `codepoint` is a character.
+    /// Glyph infos of a `GlyphBuffer` contain glyph IDs, use `as_glyph` for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `codepoint` is not a valid character.
+    #[inline]
+    pub fn as_char(&self) -> char {
         char::try_from(self.codepoint).unwrap()
     }
 
+    /// Returns `codepoint` as a glyph ID.
+    ///
+    /// Valid only after shaping, like for glyph infos of a `GlyphBuffer`.
+    #[inline]
+    pub fn as_glyph(&self) -> u32 {
+        self.codepoint
+    }
+
     #[inline]
     pub(crate) fn glyph_props(&self) -> u16 {
         unsafe {
@@ -200,6 +307,13 @@ impl GlyphInfo {
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn set_default_ignorable(&mut self) {
+        let mut n = self.unicode_props();
+        n |= UnicodeProps::IGNORABLE.bits;
+        self.set_unicode_props(n);
+    }
+
     #[inline]
     pub(crate) fn set_continuation(&mut self) {
         let mut n = self.unicode_props();
@@ -238,6 +352,12 @@ impl GlyphInfo {
 pub enum BufferClusterLevel {
     MonotoneGraphemes,
     MonotoneCharacters,
+    /// Clusters are never merged, only ligatures take the smallest cluster of their components.
+    ///
+    /// Reordered glyphs, like marks sorted by normalization or by the Arabic shaper,
+    /// keep their own clusters, so clusters are not monotone anymore.
+    /// Combined with `UnicodeBuffer::reset_clusters`, every glyph cluster is
+    /// the index of the input character that produced it.
     Characters,
 }
 
@@ -267,6 +387,26 @@ impl Default for BufferClusterLevel {
 }
 
 
+/// A buffer content type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum BufferContentType {
+    Invalid,
+    Unicode,
+    Glyphs,
+}
+
+impl BufferContentType {
+    fn from_raw(raw: ffi::hb_buffer_content_type_t) -> Self {
+        match raw {
+            ffi::HB_BUFFER_CONTENT_TYPE_INVALID => BufferContentType::Invalid,
+            ffi::HB_BUFFER_CONTENT_TYPE_UNICODE => BufferContentType::Unicode,
+            ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS => BufferContentType::Glyphs,
+            _ => panic!("received unrecognized HB_BUFFER_CONTENT_TYPE"),
+        }
+    }
+}
+
+
 pub(crate) struct Buffer {
     ptr: NonNull<ffi::hb_buffer_t>,
     language: Option<Language>,
@@ -301,7 +441,7 @@ impl Buffer {
     #[inline]
     pub fn script(&self) -> Script {
         unsafe {
-            Script(Tag(ffi::hb_buffer_get_script(self.as_ptr())))
+            Script::from_hb(ffi::hb_buffer_get_script(self.as_ptr()))
         }
     }
 
@@ -315,6 +455,11 @@ impl Buffer {
         BufferClusterLevel::from_raw(unsafe { ffi::hb_buffer_get_cluster_level(self.as_ptr()) })
     }
 
+    #[inline]
+    pub(crate) fn content_type(&self) -> BufferContentType {
+        BufferContentType::from_raw(unsafe { ffi::hb_buffer_get_content_type(self.as_ptr()) })
+    }
+
     // buffer.info 0..allocated slice.
     #[inline]
     pub(crate) fn info(&self) -> &[GlyphInfo] {
@@ -416,16 +561,36 @@ impl Buffer {
         unsafe { ffi::hb_buffer_get_length(self.as_ptr()) as usize }
     }
 
+    // Sets the length without touching the content, therefore new items keep
+    // whatever is in the allocated space. `info` and `pos` are allocated together
+    // and always have the same length, so only the capacity must be checked.
     #[inline]
     pub(crate) fn set_len(&self, len: usize) {
+        debug_assert!(len <= self.allocated(), "length must not exceed capacity");
         unsafe { ffi::hb_buffer_set_length_force(self.as_ptr(), len as u32) };
     }
 
+    // Like `set_len`, but new items are zeroed.
+    //
+    // harfbuzz resets the content type of an empty buffer,
+    // so it must be set again by the caller.
+    #[inline]
+    pub(crate) fn resize(&mut self, len: usize, content_type: ffi::hb_buffer_content_type_t) -> bool {
+        unsafe {
+            let ok = ffi::hb_buffer_set_length(self.as_ptr(), len as u32) != 0;
+            ffi::hb_buffer_set_content_type(self.as_ptr(), content_type);
+            ok
+        }
+    }
+
     #[inline]
     pub(crate) fn out_len(&self) -> usize {
         unsafe { ffi::hb_buffer_get_out_length(self.as_ptr()) as usize }
     }
 
+    // Context 0 is before the buffer content and is stored in reverse order,
+    // starting from the character closest to the content.
+    // Context 1 is after the buffer content.
     #[inline]
     pub(crate) fn context_len(&self, index: u32) -> u32 {
         unsafe { ffi::hb_buffer_context_len(self.as_ptr(), index) }
@@ -437,9 +602,15 @@ impl Buffer {
         char::try_from(c).unwrap()
     }
 
+    // Returns `false` when allocation failed.
+    #[inline]
+    pub(crate) fn ensure(&self, len: usize) -> bool {
+        unsafe { ffi::hb_buffer_ensure(self.as_ptr(), len as u32) != 0 }
+    }
+
     #[inline]
-    pub(crate) fn ensure(&self, len: usize) {
-        unsafe { ffi::hb_buffer_ensure(self.as_ptr(), len as u32) };
+    pub(crate) fn allocation_successful(&self) -> bool {
+        unsafe { ffi::hb_buffer_allocation_successful(self.as_ptr()) != 0 }
     }
 
     #[inline]
@@ -449,6 +620,11 @@ impl Buffer {
         }
     }
 
+    #[inline]
+    pub(crate) fn set_flags(&mut self, flags: BufferFlags) {
+        unsafe { ffi::hb_buffer_set_flags(self.as_ptr(), flags.bits) }
+    }
+
     #[inline]
     pub(crate) fn scratch_flags(&self) -> BufferScratchFlags {
         unsafe {
@@ -518,6 +694,16 @@ impl Buffer {
         unsafe { ffi::hb_buffer_unsafe_to_break_from_outbuffer(self.as_ptr(), start as u32, end as u32) };
     }
 
+    #[inline]
+    pub(crate) fn unsafe_to_concat(&mut self, start: usize, end: usize) {
+        unsafe { ffi::hb_buffer_unsafe_to_concat(self.as_ptr(), start as u32, end as u32) };
+    }
+
+    #[inline]
+    pub(crate) fn safe_to_insert_tatweel(&mut self, start: usize, end: usize) {
+        unsafe { ffi::hb_buffer_safe_to_insert_tatweel(self.as_ptr(), start as u32, end as u32) };
+    }
+
     #[inline]
     pub(crate) fn swap_buffers(&mut self) {
         unsafe { ffi::hb_buffer_swap_buffers(self.as_ptr()) };
@@ -528,6 +714,20 @@ impl Buffer {
         unsafe { ffi::hb_buffer_sort(self.as_ptr(), start as u32, end as u32, p) };
     }
 
+    #[inline]
+    pub(crate) fn messaging(&self) -> bool {
+        unsafe { ffi::hb_buffer_messaging(self.as_ptr()) != 0 }
+    }
+
+    // Returns `true` when there is no message callback.
+    pub(crate) fn message(&mut self, font: &Font, message: &str) -> bool {
+        let message = std::ffi::CString::new(message).unwrap_or_default();
+        unsafe {
+            ffi::hb_buffer_message(self.as_ptr(), font.as_ptr() as *mut _, message.as_ptr()) != 0
+        }
+    }
+
+    // Also resets segment properties, scratch flags, context and content type.
     #[inline]
     fn clear(&mut self) {
         unsafe { ffi::hb_buffer_clear_contents(self.as_ptr()) };
@@ -583,13 +783,45 @@ bitflags::bitflags! {
 
 
 bitflags::bitflags! {
+    /// Flags that control the buffer shaping behavior.
     #[derive(Default)]
     pub struct BufferFlags: u32 {
-        const BEGINNING_OF_TEXT             = 1 << 1;
-        const END_OF_TEXT                   = 1 << 2;
-        const PRESERVE_DEFAULT_IGNORABLES   = 1 << 3;
-        const REMOVE_DEFAULT_IGNORABLES     = 1 << 4;
-        const DO_NOT_INSERT_DOTTED_CIRCLE   = 1 << 5;
+        /// Indicates that the buffer starts at the beginning of the text paragraph.
+        const BEGINNING_OF_TEXT             = 1 << 0;
+        /// Indicates that the buffer ends at the end of the text paragraph.
+        const END_OF_TEXT                   = 1 << 1;
+        /// Keeps default ignorable characters visible.
+        const PRESERVE_DEFAULT_IGNORABLES   = 1 << 2;
+        /// Removes default ignorable characters from the output
+        /// instead of replacing them with an invisible glyph.
+        /// They are removed even without this flag, when the font has no space glyph.
+        ///
+        /// A removed character is merged into the cluster of the glyph before it
+        /// in the output, which is visual order, or the glyph after it, when it's the first one.
+        /// So for left-to-right text it joins the preceding cluster
+        /// and for right-to-left text the following one.
+        /// The glyph receiving it is assigned the smaller cluster value,
+        /// so every input character still belongs to some cluster.
+        ///
+        /// `PRESERVE_DEFAULT_IGNORABLES` takes precedence over this flag.
+        const REMOVE_DEFAULT_IGNORABLES     = 1 << 3;
+        /// Disables the dotted circle insertion for broken character sequences.
+        const DO_NOT_INSERT_DOTTED_CIRCLE   = 1 << 4;
+        /// Decomposes Arabic presentation forms into plain letters before joining.
+        ///
+        /// Presentation forms are already positional, so they do not join
+        /// and are usually missing from modern fonts.
+        /// With this flag, legacy text, like ALEF FINAL FORM, is shaped as a plain text.
+        const DECOMPOSE_PRESENTATION_FORMS  = 1 << 5;
+        /// Produces `GlyphFlags::UNSAFE_TO_CONCAT`.
+        const PRODUCE_UNSAFE_TO_CONCAT      = 1 << 6;
+        /// Produces `GlyphFlags::SAFE_TO_INSERT_TATWEEL`.
+        const PRODUCE_SAFE_TO_INSERT_TATWEEL = 1 << 7;
+        /// Moves marks of a glyph stretched by the Arabic `stch` feature,
+        /// like a vowel on a stretched letter, together with its tiles.
+        ///
+        /// Without this flag, such marks keep their positions, like in harfbuzz.
+        const MOVE_MARKS_WITH_STCH_TILES = 1 << 8;
     }
 }
 
@@ -601,8 +833,9 @@ bitflags::bitflags! {
         const HAS_DEFAULT_IGNORABLES    = 0x00000002;
         const HAS_SPACE_FALLBACK        = 0x00000004;
         const HAS_GPOS_ATTACHMENT       = 0x00000008;
-        const HAS_UNSAFE_TO_BREAK       = 0x00000010;
+        const HAS_GLYPH_FLAGS           = 0x00000010;
         const HAS_CGJ                   = 0x00000020;
+        const HAS_MISSING_VARIATION     = 0x00000040;
 
         // Reserved for complex shapers' internal use.
         const COMPLEX0                  = 0x01000000;
@@ -659,8 +892,82 @@ impl UnicodeBuffer {
         self.0.is_empty()
     }
 
+    /// Returns the number of elements the buffer can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.allocated()
+    }
+
+    /// Makes sure the buffer can hold at least `capacity` characters without reallocating.
+    ///
+    /// Returns `false` when memory allocation failed.
+    #[inline]
+    pub fn ensure(&mut self, capacity: usize) -> bool {
+        self.0.ensure(capacity)
+    }
+
+    /// Makes sure `chars` more characters, multiplied by `headroom`, fit without reallocating.
+    ///
+    /// `push_str` already reserves space for the pushed characters, so this is needed
+    /// only when shaping is expected to add glyphs, like the `stch` feature in Arabic
+    /// and Syriac text, which repeats a glyph to fill the space.
+    /// For example, a `headroom` of `1.5` reserves half as much space again.
+    /// Values below `1.0` are treated as `1.0`.
+    ///
+    /// Returns `false` when memory allocation failed.
+    pub fn pre_allocate(&mut self, chars: usize, headroom: f32) -> bool {
+        let extra = (chars as f32 * headroom.max(1.0)).ceil() as usize;
+        self.0.ensure(self.len() + extra)
+    }
+
+    /// Shortens or extends the buffer to `len` characters.
+    ///
+    /// New characters are U+0000 with a zero cluster.
+    /// Glyph infos and positions are stored together, so they are always resized in sync.
+    ///
+    /// `len` must not exceed `capacity`, which is checked in debug builds.
+    /// Use `ensure` first to grow the buffer.
+    #[inline]
+    pub fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= self.capacity(), "length must not exceed capacity");
+        self.0.resize(len, ffi::HB_BUFFER_CONTENT_TYPE_UNICODE);
+    }
+
+    /// Returns a raw pointer to the underlying harfbuzz `hb_buffer_t`.
+    ///
+    /// Can be passed to the `hb_buffer_*` functions of the harfbuzz copy bundled
+    /// with rustybuzz, which is linked statically. It's not compatible with other
+    /// harfbuzz builds, because the bundled one is modified.
+    ///
+    /// The pointer is valid as long as the buffer is alive.
+    /// The caller must not destroy the buffer or keep the pointer after it was dropped.
+    #[inline]
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0.as_ptr() as *const c_void
+    }
+
+    /// Returns a mutable raw pointer to the underlying harfbuzz `hb_buffer_t`.
+    ///
+    /// See `as_ptr` for details.
+    /// In addition, the caller must not change the buffer content type.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        self.0.as_ptr() as *mut c_void
+    }
+
+    // Checks that the content type was changed to glyphs, e.g. via a raw pointer.
+    // Code points are glyph IDs in this case, so the buffer cannot be shaped.
+    pub(crate) fn contains_glyphs(&self) -> bool {
+        !self.is_empty() && self.0.content_type() == BufferContentType::Glyphs
+    }
+
     /// Pushes a string to a buffer.
+    ///
+    /// Space for all the characters is reserved up front. See `pre_allocate`.
     pub fn push_str(&mut self, str: &str) {
+        // harfbuzz reserves a character per 4 bytes, which is too little for most texts.
+        self.pre_allocate(str.chars().count(), 1.0);
+
         unsafe {
             ffi::hb_buffer_add_utf8(
                 self.0.as_ptr(),
@@ -672,17 +979,154 @@ impl UnicodeBuffer {
         }
     }
 
+    /// Pushes a UTF-16 string to a buffer.
+    ///
+    /// Clusters are code unit offsets into `text`, like in `hb_buffer_add_utf16`.
+    /// Unpaired surrogates are replaced with U+FFFD REPLACEMENT CHARACTER.
+    pub fn push_utf16(&mut self, text: &[u16]) {
+        self.0.ensure(self.len() + text.len());
+
+        let mut offset = 0;
+        for c in std::char::decode_utf16(text.iter().cloned()) {
+            let (c, len) = match c {
+                Ok(c) => (c, c.len_utf16()),
+                Err(_) => (std::char::REPLACEMENT_CHARACTER, 1),
+            };
+
+            self.push_char_with_cluster(c, offset);
+            offset += len as u32;
+        }
+    }
+
+    /// Pushes a UTF-32 string to a buffer.
+    ///
+    /// Clusters are code unit offsets into `text`, like in `hb_buffer_add_utf32`.
+    /// Surrogates and values above U+10FFFF are replaced with U+FFFD REPLACEMENT CHARACTER.
+    pub fn push_utf32(&mut self, text: &[u32]) {
+        self.0.ensure(self.len() + text.len());
+
+        for (i, &u) in text.iter().enumerate() {
+            let c = char::try_from(u).unwrap_or(std::char::REPLACEMENT_CHARACTER);
+            self.push_char_with_cluster(c, i as u32);
+        }
+    }
+
+    /// Pushes a character with a custom cluster.
+    ///
+    /// Useful when clusters must be something else than UTF-8 byte offsets,
+    /// like UTF-16 code unit offsets for JavaScript or JVM hosts.
+    /// Clusters must not decrease in logical order, otherwise merged clusters
+    /// of ligatures and reordered marks will be wrong.
+    ///
+    /// Resets the post-context, like `push_str`.
+    pub fn push_char_with_cluster(&mut self, c: char, cluster: u32) {
+        unsafe {
+            ffi::hb_buffer_add(self.0.as_ptr(), c as u32, cluster);
+            // Unlike `hb_buffer_add_utf8`, `hb_buffer_add` doesn't set the content type.
+            ffi::hb_buffer_set_content_type(self.0.as_ptr(), ffi::HB_BUFFER_CONTENT_TYPE_UNICODE);
+        }
+    }
+
+    /// Sets the cluster of the character at `index`.
+    ///
+    /// See `push_char_with_cluster` for requirements.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn set_cluster(&mut self, index: usize, cluster: u32) {
+        let len = self.len();
+        assert!(index < len, "character index {} is out of bounds for {} characters", index, len);
+        self.0.info_mut()[index].cluster = cluster;
+    }
+
+    /// Pushes a `range` of `text` to a buffer.
+    ///
+    /// Text around the range is used as a pre- and post-context.
+    /// Clusters are byte offsets into `text`.
+    pub(crate) fn push_str_with_context(&mut self, text: &str, range: std::ops::Range<usize>) {
+        assert!(text.is_char_boundary(range.start) && text.is_char_boundary(range.end));
+
+        unsafe {
+            ffi::hb_buffer_add_utf8(
+                self.0.as_ptr(),
+                text.as_ptr() as *const _,
+                text.len() as i32,
+                range.start as u32,
+                range.len() as i32,
+            );
+        }
+    }
+
+    /// Sets the text before the buffer content.
+    ///
+    /// Up to 5 last characters are used by shapers, like for Arabic joining.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the buffer is not empty, since the context must be set before the text.
+    pub fn set_pre_context(&mut self, text: &str) {
+        assert!(self.is_empty(), "pre-context must be set before pushing text");
+
+        unsafe {
+            ffi::hb_buffer_add_utf8(
+                self.0.as_ptr(),
+                text.as_ptr() as *const _,
+                text.len() as i32,
+                text.len() as u32,
+                0,
+            );
+        }
+    }
+
+    /// Sets the text after the buffer content.
+    ///
+    /// Up to 5 first characters are used by shapers, like for Arabic joining.
+    ///
+    /// Pushing text resets the post-context, so it must be set last.
+    pub fn set_post_context(&mut self, text: &str) {
+        unsafe {
+            ffi::hb_buffer_add_utf8(
+                self.0.as_ptr(),
+                text.as_ptr() as *const _,
+                text.len() as i32,
+                0,
+                0,
+            );
+        }
+    }
+
+    /// Returns the characters before the buffer content, in logical order.
+    pub fn pre_context(&self) -> Vec<char> {
+        let mut context: Vec<_> = (0..self.0.context_len(0)).map(|i| self.0.context(0, i)).collect();
+        context.reverse();
+        context
+    }
+
+    /// Returns the characters after the buffer content, in logical order.
+    pub fn post_context(&self) -> Vec<char> {
+        (0..self.0.context_len(1)).map(|i| self.0.context(1, i)).collect()
+    }
+
     /// Set the text direction of the `Buffer`'s contents.
+    ///
+    /// When not set, the direction is guessed from the script during shaping.
+    /// See `guess_segment_properties`.
     pub fn set_direction(&mut self, direction: Direction) {
         unsafe { ffi::hb_buffer_set_direction(self.0.as_ptr(), direction.to_raw()) };
     }
 
     /// Returns the `Buffer`'s text direction.
+    ///
+    /// `Direction::Invalid` when not set.
     pub fn direction(&self) -> Direction {
         Direction::from_raw(unsafe { ffi::hb_buffer_get_direction(self.0.as_ptr()) })
     }
 
     /// Set the script from an ISO15924 tag.
+    ///
+    /// When not set, the script is guessed from the buffer content during shaping.
+    /// See `guess_segment_properties`.
     pub fn set_script(&mut self, script: Script) {
         unsafe {
             ffi::hb_buffer_set_script(self.0.as_ptr(), script.0.as_u32())
@@ -690,11 +1134,15 @@ impl UnicodeBuffer {
     }
 
     /// Get the ISO15924 script tag.
+    ///
+    /// Has a null tag when not set.
     pub fn script(&self) -> Script {
         self.0.script()
     }
 
     /// Set the buffer language.
+    ///
+    /// When not set, the language of the current locale is used during shaping.
     pub fn set_language(&mut self, lang: Language) {
         let lang_ptr = lang.0.as_ptr();
         self.0.language = Some(lang); // Language must outlive Buffer.
@@ -702,6 +1150,8 @@ impl UnicodeBuffer {
     }
 
     /// Get the buffer language.
+    ///
+    /// `None` when not set.
     pub fn language(&self) -> Option<Language> {
         let raw_lang = unsafe { ffi::hb_buffer_get_language(self.0.as_ptr()) };
         if raw_lang.is_null() {
@@ -711,8 +1161,37 @@ impl UnicodeBuffer {
         }
     }
 
+    /// Guesses the text direction from the buffer content.
+    ///
+    /// Returns the direction of the first character with a directional script,
+    /// regardless of the buffer script. `Common`, `Inherited` and `Unknown` characters,
+    /// like digits, punctuation and marks, are skipped.
+    /// This is an approximation of the first strong character rule of the bidi algorithm,
+    /// since the script is used instead of the bidi class.
+    ///
+    /// Returns `Direction::Invalid` when there is no such character.
+    /// The buffer direction is not changed.
+    pub fn guess_direction(&self) -> Direction {
+        let len = self.len();
+        self.0.info()[..len].iter()
+            .map(|info| crate::unicode::char_script(info.as_char()))
+            .filter(|sc| ![script::COMMON, script::INHERITED, script::UNKNOWN].contains(sc))
+            .find_map(Direction::from_script)
+            .unwrap_or_default()
+    }
+
     /// Guess the segment properties (direction, language, script) for the
     /// current buffer.
+    ///
+    /// Only unset properties are guessed:
+    ///
+    /// - the script is taken from the first character that is not
+    ///   `Common`, `Inherited` or `Unknown`
+    /// - the direction is the horizontal direction of the script,
+    ///   or `LeftToRight` when the script has none
+    /// - the language is taken from the current locale
+    ///
+    /// `shape` calls it automatically.
     pub fn guess_segment_properties(&mut self) {
         unsafe { ffi::hb_buffer_guess_segment_properties(self.0.as_ptr()) };
     }
@@ -728,14 +1207,98 @@ impl UnicodeBuffer {
     }
 
     /// Resets clusters.
+    ///
+    /// Sets each character cluster to its index in the buffer,
+    /// instead of the byte offset set by `push_str`.
     pub fn reset_clusters(&mut self) {
         unsafe { ffi::hb_buffer_reset_clusters(self.0.as_ptr()) }
     }
 
+    /// Sets buffer flags.
+    ///
+    /// Flags are not reset by `clear`.
+    pub fn set_flags(&mut self, flags: BufferFlags) {
+        self.0.set_flags(flags)
+    }
+
+    /// Returns buffer flags.
+    pub fn flags(&self) -> BufferFlags {
+        self.0.flags()
+    }
+
+    /// Sets the glyph ID used for characters that are not in the font.
+    ///
+    /// The default is 0, the `.notdef` glyph. A font-specific tofu glyph
+    /// can be used instead to make missing characters visible.
+    ///
+    /// Clusters of missing characters can no longer be detected by a zero glyph ID
+    /// in the shaping result, therefore compare with this glyph instead.
+    ///
+    /// The glyph is not reset by `clear`.
+    pub fn set_not_found_glyph(&mut self, glyph_id: u32) {
+        unsafe { ffi::hb_buffer_set_not_found_glyph(self.0.as_ptr(), glyph_id) }
+    }
+
+    /// Returns the glyph ID used for characters that are not in the font.
+    pub fn not_found_glyph(&self) -> u32 {
+        unsafe { ffi::hb_buffer_get_not_found_glyph(self.0.as_ptr()) }
+    }
+
     /// Clear the contents of the buffer.
     pub fn clear(&mut self) {
         self.0.clear()
     }
+
+    /// Sets a shaping trace callback.
+    ///
+    /// The callback is called before and after each shaping step with a short description
+    /// of it, like `start table GSUB`, `start lookup 3` or `start pause 2`,
+    /// and the current glyphs.
+    ///
+    /// Returning `false` on `start table ...` and `start lookup ...` messages will skip
+    /// the table or the lookup. Returning `false` on other messages has no effect.
+    ///
+    /// In debug builds, the Arabic shaper also reports each stretched `stch` sequence
+    /// with a `stch w_total=... w_fixed=... w_repeating=... n_repeating=... n_copies=...
+    /// extra_repeat_overlap=...` message. Its result is ignored.
+    ///
+    /// There is no overhead when the callback is not set.
+    pub fn set_message_func<F>(&mut self, func: F)
+        where F: FnMut(&str, &[GlyphInfo]) -> bool + 'static
+    {
+        let func: MessageFunc = Box::new(func);
+        let user_data = Box::into_raw(Box::new(func)) as *mut c_void;
+        unsafe {
+            ffi::hb_buffer_set_message_func(
+                self.0.as_ptr(),
+                Some(message_func_trampoline),
+                user_data,
+                Some(message_func_destroy),
+            );
+        }
+    }
+}
+
+type MessageFunc = Box<dyn FnMut(&str, &[GlyphInfo]) -> bool>;
+
+unsafe extern "C" fn message_func_trampoline(
+    buffer: *mut ffi::hb_buffer_t,
+    _: *mut ffi::hb_font_t,
+    message: *const c_char,
+    user_data: *mut c_void,
+) -> ffi::hb_bool_t {
+    let func = &mut *(user_data as *mut MessageFunc);
+    let message = std::ffi::CStr::from_ptr(message).to_str().unwrap_or_default();
+
+    let mut len: u32 = 0;
+    let infos = ffi::hb_buffer_get_glyph_infos(buffer, &mut len as *mut u32);
+    let infos = std::slice::from_raw_parts(infos as *const GlyphInfo, len as usize);
+
+    func(message, infos) as ffi::hb_bool_t
+}
+
+unsafe extern "C" fn message_func_destroy(user_data: *mut c_void) {
+    drop(Box::from_raw(user_data as *mut MessageFunc));
 }
 
 impl std::fmt::Debug for UnicodeBuffer {
@@ -756,15 +1319,30 @@ impl Default for UnicodeBuffer {
 }
 
 
+/// A glyph with an absolute drawing position.
+///
+/// See `GlyphBuffer::positioned_glyphs`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PositionedGlyph {
+    /// A glyph ID.
+    pub glyph_id: u32,
+    /// An original cluster index.
+    pub cluster: u32,
+    /// A horizontal drawing position.
+    pub x: i32,
+    /// A vertical drawing position.
+    pub y: i32,
+}
+
+
 /// A buffer that contains the results of the shaping process.
 pub struct GlyphBuffer(pub(crate) Buffer);
 
 impl GlyphBuffer {
-    /// Returns the length of the data of the buffer.
+    /// Returns the number of glyphs in the buffer.
     ///
-    /// When called before shaping this is the number of unicode codepoints
-    /// contained in the buffer. When called after shaping it returns the number
-    /// of glyphs stored.
+    /// It can differ from the number of input characters in both directions:
+    /// ligatures reduce it, while decompositions or Arabic `stch` repeats increase it.
     pub fn len(&self) -> usize {
         self.0.len()
     }
@@ -774,6 +1352,52 @@ impl GlyphBuffer {
         self.0.is_empty()
     }
 
+    /// Returns the number of glyphs the buffer can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.0.allocated()
+    }
+
+    /// Makes sure the buffer can hold at least `capacity` glyphs without reallocating.
+    ///
+    /// Returns `false` when memory allocation failed.
+    pub fn ensure(&mut self, capacity: usize) -> bool {
+        self.0.ensure(capacity)
+    }
+
+    /// Shortens or extends the buffer to `len` glyphs.
+    ///
+    /// New glyphs have zeroed infos and positions.
+    /// Glyph infos and positions are stored together, so they are always resized in sync.
+    ///
+    /// `len` must not exceed `capacity`, which is checked in debug builds.
+    /// Use `ensure` first to grow the buffer.
+    pub fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= self.capacity(), "length must not exceed capacity");
+        self.0.resize(len, ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS);
+    }
+
+    /// Returns a raw pointer to the underlying harfbuzz `hb_buffer_t`.
+    ///
+    /// Can be passed to the `hb_buffer_*` functions of the harfbuzz copy bundled
+    /// with rustybuzz, which is linked statically. It's not compatible with other
+    /// harfbuzz builds, because the bundled one is modified.
+    ///
+    /// The pointer is valid as long as the buffer is alive.
+    /// The caller must not destroy the buffer or keep the pointer after it was dropped.
+    #[inline]
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0.as_ptr() as *const c_void
+    }
+
+    /// Returns a mutable raw pointer to the underlying harfbuzz `hb_buffer_t`.
+    ///
+    /// See `as_ptr` for details.
+    /// In addition, the caller must not change the buffer content type.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        self.0.as_ptr() as *mut c_void
+    }
+
     /// Get the glyph positions.
     pub fn glyph_positions(&self) -> &[GlyphPosition] {
         unsafe {
@@ -793,10 +1417,393 @@ impl GlyphBuffer {
         }
     }
 
+    /// Returns a mutable reference to the position of the glyph at `index`.
+    ///
+    /// Can be used for custom adjustments on top of the shaping result, like optical kerning.
+    /// Nothing is cached, so methods like `total_advance` and `positioned_glyphs`
+    /// take the changes into account.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn position_mut(&mut self, index: usize) -> &mut GlyphPosition {
+        let len = self.len();
+        assert!(index < len, "glyph index {} is out of bounds for {} glyphs", index, len);
+        &mut self.0.pos_mut()[index]
+    }
+
+    /// Inserts a glyph at `index`, shifting all glyphs after it.
+    ///
+    /// The glyph cluster is clamped between the clusters of its neighbors,
+    /// so clusters stay monotone. Use `GlyphInfo::new` to create a glyph info.
+    ///
+    /// Returns `false` when memory allocation failed.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is greater than the number of glyphs.
+    pub fn insert_glyph(&mut self, index: usize, mut info: GlyphInfo, pos: GlyphPosition) -> bool {
+        let len = self.len();
+        assert!(index <= len, "glyph index {} is out of bounds for {} glyphs", index, len);
+
+        if !self.0.resize(len + 1, ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS) {
+            return false;
+        }
+
+        let right = if index < len { Some(index) } else { None };
+        let (prev, next) = self.logical_order(index.checked_sub(1), right);
+        if let Some(prev) = prev {
+            info.cluster = info.cluster.max(self.0.info()[prev].cluster);
+        }
+        if let Some(next) = next {
+            info.cluster = info.cluster.min(self.0.info()[next].cluster);
+        }
+
+        self.0.info_mut().copy_within(index..len, index + 1);
+        self.0.pos_mut().copy_within(index..len, index + 1);
+        self.0.info_mut()[index] = info;
+        self.0.pos_mut()[index] = pos;
+        true
+    }
+
+    /// Removes the glyph at `index`, shifting all glyphs after it.
+    ///
+    /// When the glyph was the only one in its cluster, the cluster is merged
+    /// into the logically previous one, the same way as harfbuzz deletes glyphs.
+    /// When there is no previous cluster, the next one is merged into it instead,
+    /// so the text is still fully covered by clusters.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn remove_glyph(&mut self, index: usize) {
+        let len = self.len();
+        assert!(index < len, "glyph index {} is out of bounds for {} glyphs", index, len);
+
+        let cluster = self.0.info()[index].cluster;
+        let right = if index + 1 < len { Some(index + 1) } else { None };
+        let (prev, next) = self.logical_order(index.checked_sub(1), right);
+
+        let survives = [prev, next].iter().flatten().any(|&i| self.0.info()[i].cluster == cluster);
+        if !survives {
+            if let Some(neighbor) = prev.or(next) {
+                let old_cluster = self.0.info()[neighbor].cluster;
+                if cluster < old_cluster {
+                    for info in &mut self.0.info_mut()[..len] {
+                        if info.cluster == old_cluster {
+                            info.cluster = cluster;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.0.info_mut().copy_within(index + 1..len, index);
+        self.0.pos_mut().copy_within(index + 1..len, index);
+        self.0.resize(len - 1, ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS);
+    }
+
+    // Maps glyphs to the left and to the right onto the logically previous and next ones.
+    fn logical_order(&self, left: Option<usize>, right: Option<usize>) -> (Option<usize>, Option<usize>) {
+        if self.direction().is_backward() {
+            (right, left)
+        } else {
+            (left, right)
+        }
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::from_raw(unsafe { ffi::hb_buffer_get_direction(self.0.as_ptr()) })
+    }
+
+    /// Splits glyphs at the start of `cluster`.
+    ///
+    /// Returns glyphs of the text before `cluster` and glyphs of the text starting from it,
+    /// in this order, as if the text was shaped in two parts. Both keep the visual order,
+    /// so for the right-to-left text, the first part comes from the end of the buffer.
+    /// Segment properties, the cluster level and flags are copied into both parts.
+    /// So are `used_gpos_attachments` and `has_missing_variation`,
+    /// which still describe the whole buffer.
+    ///
+    /// Splitting is allowed only where no glyph of `cluster` has the
+    /// `GlyphFlags::UNSAFE_TO_BREAK` flag, like between Arabic words.
+    /// Otherwise, shaping the parts separately would produce different glyphs,
+    /// like different joining forms, so the text must be reshaped instead.
+    pub fn split_at_cluster(&self, cluster: u32) -> Result<(GlyphBuffer, GlyphBuffer), SplitError> {
+        let infos = self.glyph_infos();
+        let mut glyphs = infos.iter().filter(|info| info.cluster == cluster).peekable();
+        if glyphs.peek().is_none() {
+            return Err(SplitError::NotClusterStart);
+        }
+
+        if glyphs.any(|info| info.flags().contains(GlyphFlags::UNSAFE_TO_BREAK)) {
+            return Err(SplitError::UnsafeToBreak);
+        }
+
+        let len = self.len();
+        if self.direction().is_backward() {
+            let split = infos.iter().position(|info| info.cluster < cluster).unwrap_or(len);
+            Ok((self.copy_range(split..len), self.copy_range(0..split)))
+        } else {
+            let split = infos.iter().position(|info| info.cluster >= cluster).unwrap_or(len);
+            Ok((self.copy_range(0..split), self.copy_range(split..len)))
+        }
+    }
+
+    fn copy_range(&self, range: std::ops::Range<usize>) -> GlyphBuffer {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_direction(self.direction());
+        buffer.set_script(self.0.script());
+        if let Some(ref language) = self.0.language {
+            buffer.set_language(language.clone());
+        }
+        buffer.set_cluster_level(self.0.cluster_level());
+        buffer.set_flags(self.0.flags());
+
+        let len = range.len();
+        let mut buffer = buffer.0;
+        buffer.resize(len, ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS);
+        buffer.info_mut()[..len].copy_from_slice(&self.glyph_infos()[range.clone()]);
+        buffer.pos_mut()[..len].copy_from_slice(&self.glyph_positions()[range]);
+
+        let results = BufferScratchFlags::HAS_GPOS_ATTACHMENT | BufferScratchFlags::HAS_MISSING_VARIATION;
+        buffer.set_scratch_flags(self.0.scratch_flags() & results);
+        GlyphBuffer(buffer)
+    }
+
+    /// Resets all glyph positions to zero.
+    ///
+    /// Glyphs and clusters are kept, so the run can be positioned again
+    /// without redoing the substitution, e.g. when justifying it to different widths.
+    /// Use `set_nominal_advances` to get the advances from the font back.
+    pub fn clear_positions(&mut self) {
+        let len = self.len();
+        for pos in &mut self.0.pos_mut()[..len] {
+            *pos = GlyphPosition { x_advance: 0, y_advance: 0, x_offset: 0, y_offset: 0, var: 0 };
+        }
+    }
+
+    /// Resets glyph positions to the nominal advances from the font.
+    ///
+    /// Same as the default positioning that runs before `GPOS`:
+    /// offsets are zeroed and advances are fetched from the font again,
+    /// so the current variation coordinates are taken into account.
+    /// `GPOS` and `kern` adjustments, mark positioning and `stch` are not reapplied.
+    ///
+    /// For vertical text, the vertical advances are negative and the vertical origins
+    /// are subtracted from the offsets, like during shaping.
+    pub fn set_nominal_advances(&mut self, font: &Font) {
+        let direction = Direction::from_raw(unsafe { ffi::hb_buffer_get_direction(self.0.as_ptr()) });
+        self.clear_positions();
+
+        let len = self.len();
+        for i in 0..len {
+            let glyph = self.0.info()[i].codepoint;
+            let pos = &mut self.0.pos_mut()[i];
+            if direction.is_vertical() {
+                let (x, y) = font.glyph_v_origin(glyph);
+                pos.y_advance = -(crate::font::hb_font_get_advance(font.as_ptr(), glyph, 1) as i32);
+                pos.x_offset = -x;
+                pos.y_offset = -y;
+            } else {
+                pos.x_advance = font.glyph_h_advance(glyph) as i32;
+            }
+        }
+    }
+
+    /// Returns the range of glyphs that belong to the cluster containing `cluster`.
+    ///
+    /// `cluster` can be any value from the original text, not only a cluster start.
+    /// In this case, a cluster with the largest value not greater than `cluster` is used.
+    /// This way, an offset in the middle of a ligature maps onto the ligature glyph.
+    ///
+    /// Glyphs are stored in visual order, therefore for the right-to-left text,
+    /// glyphs of the first cluster are at the end of the buffer.
+    /// Glyphs of a single cluster are always adjacent, so the range is still continuous.
+    ///
+    /// Returns an empty range when there is no such cluster.
+    pub fn cluster_to_glyph_range(&self, cluster: u32) -> std::ops::Range<usize> {
+        let infos = self.glyph_infos();
+        let target = match infos.iter().map(|info| info.cluster).filter(|c| *c <= cluster).max() {
+            Some(c) => c,
+            None => return 0..0,
+        };
+
+        let start = infos.iter().position(|info| info.cluster == target).unwrap();
+        let end = infos.iter().rposition(|info| info.cluster == target).unwrap() + 1;
+        start..end
+    }
+
+    /// Returns every cluster with the range of glyphs it produced, in visual order.
+    ///
+    /// A ligature of multiple characters is a single cluster with a single glyph,
+    /// while a character decomposed into multiple glyphs, like by `stch`,
+    /// is a single cluster with many glyphs.
+    /// Ranges are adjacent and cover all glyphs.
+    pub fn cluster_map(&self) -> Vec<(u32, std::ops::Range<usize>)> {
+        let infos = self.glyph_infos();
+        let mut map = Vec::new();
+        let mut start = 0;
+        while start < infos.len() {
+            let cluster = infos[start].cluster;
+            let mut end = start + 1;
+            while end < infos.len() && infos[end].cluster == cluster {
+                end += 1;
+            }
+
+            map.push((cluster, start..end));
+            start = end;
+        }
+
+        map
+    }
+
+    /// Returns the cluster of the glyph at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `index` is out of bounds.
+    pub fn glyph_to_cluster(&self, index: usize) -> u32 {
+        self.glyph_infos()[index].cluster
+    }
+
+    /// Returns the sum of all glyph advances.
+    ///
+    /// This is the width and height of the run, in font units.
+    /// For vertical text, the vertical advances are negative.
+    pub fn total_advance(&self) -> (i32, i32) {
+        self.glyph_positions().iter().fold((0, 0), |(x, y), pos| (x + pos.x_advance, y + pos.y_advance))
+    }
+
+    /// Returns the sum of all glyph advances scaled to `size`, like a font size in pixels.
+    ///
+    /// Same as `total_advance`, but one EM of `font` is `size` units long.
+    /// `font` must be the one the buffer was shaped with.
+    pub fn scaled_total_advance(&self, font: &Font, size: f32) -> (f32, f32) {
+        let (x, y) = self.total_advance();
+        let scale = size / font.units_per_em() as f32;
+        (x as f32 * scale, y as f32 * scale)
+    }
+
+    /// Rounds glyph positions to a pixel grid.
+    ///
+    /// `units_per_pixel` is the size of a pixel in font units,
+    /// e.g. `units_per_em / ppem`. A fraction of it can be used to snap to a sub-pixel grid.
+    ///
+    /// Advances are rounded so that the pen position after each glyph is snapped,
+    /// instead of snapping each advance independently. This way rounding errors
+    /// do not accumulate and the total advance stays within a pixel of the unrounded one.
+    /// Offsets are relative to the pen position and are snapped independently.
+    ///
+    /// Positions are still in font units afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `units_per_pixel` is not positive.
+    pub fn round_positions(&mut self, units_per_pixel: f32, mode: RoundMode) {
+        assert!(units_per_pixel > 0.0, "units per pixel must be positive");
+
+        if mode == RoundMode::None {
+            return;
+        }
+
+        let units_per_pixel = f64::from(units_per_pixel);
+        let snap = |v: i64| ((v as f64 / units_per_pixel).round() * units_per_pixel).round() as i64;
+
+        let len = self.len();
+        let mut pen = (0i64, 0i64);
+        let mut snapped_pen = (0i64, 0i64);
+        for pos in &mut self.0.pos_mut()[..len] {
+            pen.0 += i64::from(pos.x_advance);
+            pen.1 += i64::from(pos.y_advance);
+
+            let next = (snap(pen.0), snap(pen.1));
+            pos.x_advance = (next.0 - snapped_pen.0) as i32;
+            pos.y_advance = (next.1 - snapped_pen.1) as i32;
+            snapped_pen = next;
+
+            if mode == RoundMode::All {
+                pos.x_offset = snap(i64::from(pos.x_offset)) as i32;
+                pos.y_offset = snap(i64::from(pos.y_offset)) as i32;
+            }
+        }
+    }
+
+    /// Returns glyphs with their absolute drawing positions.
+    ///
+    /// Positions are calculated by accumulating advances, starting from `origin`,
+    /// and applying offsets for each glyph.
+    /// Since glyphs are stored in visual order, this works for both text directions.
+    /// Marks usually have a zero advance, so they are drawn relative to their base.
+    pub fn positioned_glyphs(&self, origin: (i32, i32)) -> impl Iterator<Item = PositionedGlyph> + '_ {
+        let mut pen = origin;
+        self.glyph_infos().iter().zip(self.glyph_positions()).map(move |(info, pos)| {
+            let glyph = PositionedGlyph {
+                glyph_id: info.codepoint,
+                cluster: info.cluster,
+                x: pen.0 + pos.x_offset,
+                y: pen.1 + pos.y_offset,
+            };
+
+            pen.0 += pos.x_advance;
+            pen.1 += pos.y_advance;
+            glyph
+        })
+    }
+
+    /// Checks that the buffer contains a glyph with the specified name.
+    ///
+    /// Makes shaping assertions readable and independent of glyph IDs,
+    /// which change between font versions.
+    /// Returns `false` when the font has no such glyph. See `Font::glyph_from_name`.
+    pub fn contains_glyph_named(&self, font: &Font, name: &str) -> bool {
+        match font.glyph_from_name(name) {
+            Some(glyph) => self.glyph_infos().iter().any(|info| info.codepoint == glyph),
+            None => false,
+        }
+    }
+
+    /// Checks that at least one glyph was attached to another one via `GPOS`,
+    /// i.e. a mark to its base or a glyph via cursive attachment.
+    ///
+    /// `false` when marks were positioned by the fallback positioning
+    /// or were not positioned at all.
+    pub fn used_gpos_attachments(&self) -> bool {
+        self.0.scratch_flags().contains(BufferScratchFlags::HAS_GPOS_ATTACHMENT)
+    }
+
+    /// Checks that at least one character was followed by a variation selector,
+    /// but the font doesn't have a glyph for this variation sequence.
+    ///
+    /// The base glyph is used instead and the selector is hidden
+    /// like any other default ignorable. The font can still handle the sequence via `GSUB`.
+    pub fn has_missing_variation(&self) -> bool {
+        self.0.scratch_flags().contains(BufferScratchFlags::HAS_MISSING_VARIATION)
+    }
+
     /// Clears the content of the glyph buffer and returns an empty
     /// `UnicodeBuffer` reusing the existing allocation.
-    pub fn clear(mut self) -> UnicodeBuffer {
+    ///
+    /// Same as `into_buffer`.
+    pub fn clear(self) -> UnicodeBuffer {
+        self.into_buffer()
+    }
+
+    /// Converts the glyph buffer back into an empty `UnicodeBuffer`.
+    ///
+    /// The glyph buffer is consumed and its allocation is moved into the returned buffer,
+    /// so the next shaping call doesn't have to allocate as long as the text fits.
+    ///
+    /// Glyphs, segment properties, context and internal shaping state are reset,
+    /// so the result is indistinguishable from a new buffer.
+    /// The cluster level and the message callback are preserved.
+    pub fn into_buffer(mut self) -> UnicodeBuffer {
         self.0.clear();
+        unsafe {
+            ffi::hb_buffer_set_content_type(self.0.as_ptr(), ffi::HB_BUFFER_CONTENT_TYPE_UNICODE);
+        }
+
+        debug_assert!(self.0.scratch_flags().is_empty());
         UnicodeBuffer(self.0)
     }
 
@@ -838,8 +1845,8 @@ impl GlyphBuffer {
             }
 
             if flags.contains(SerializeFlags::GLYPH_FLAGS) {
-                if info.mask & glyph_flag::DEFINED != 0 {
-                    write!(&mut s, "#{:X}", info.mask & glyph_flag::DEFINED).unwrap();
+                if !info.flags().is_empty() {
+                    write!(&mut s, "#{:X}", info.flags().bits()).unwrap();
                 }
             }
 
@@ -865,11 +1872,94 @@ impl GlyphBuffer {
     }
 }
 
+impl Clone for GlyphBuffer {
+    /// Copies glyphs, segment properties, the cluster level, flags
+    /// and shaping results, like `used_gpos_attachments` and `has_missing_variation`.
+    ///
+    /// The message callback and the internal shaping state are not copied.
+    fn clone(&self) -> Self {
+        self.copy_range(0..self.len())
+    }
+}
+
+/// An error returned by `GlyphBuffer::split_at_cluster`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SplitError {
+    /// No glyph starts at the requested cluster.
+    NotClusterStart,
+    /// The glyphs depend on the text on both sides of the cluster,
+    /// so the text must be split and reshaped instead.
+    UnsafeToBreak,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitError::NotClusterStart => f.write_str("no glyph starts at the cluster"),
+            SplitError::UnsafeToBreak => f.write_str("unsafe to break at the cluster, reshape instead"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// Prints glyphs as a table.
+///
+/// Each row contains: glyph id, cluster, x/y offsets, x/y advances and glyph flags.
 impl fmt::Debug for GlyphBuffer {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.debug_struct("GlyphBuffer")
-            .field("glyph_positions", &self.glyph_positions())
-            .field("glyph_infos", &self.glyph_infos())
-            .finish()
+        writeln!(fmt, "GlyphBuffer [")?;
+        writeln!(fmt, "  {:>5} {:>7} {:>6} {:>6} {:>6} {:>6} {:>5}",
+                 "gid", "cluster", "x_off", "y_off", "x_adv", "y_adv", "flags")?;
+        for (info, pos) in self.glyph_infos().iter().zip(self.glyph_positions()) {
+            writeln!(fmt, "  {:>5} {:>7} {:>6} {:>6} {:>6} {:>6} {:>5}",
+                     info.codepoint, info.cluster,
+                     pos.x_offset, pos.y_offset, pos.x_advance, pos.y_advance,
+                     format!("{:#X}", info.flags().bits()))?;
+        }
+        write!(fmt, "]")
+    }
+}
+
+
+/// A shaping result with diagnostics.
+///
+/// Returned by `shape_with_result`. Dereferences to `GlyphBuffer`.
+#[derive(Debug)]
+pub struct ShapingResult {
+    /// Shaped glyphs.
+    pub glyphs: GlyphBuffer,
+    /// Whether marks were positioned using the fallback implementation,
+    /// because the font has no `GPOS` table.
+    ///
+    /// See `ShapePlan::uses_fallback_mark_positioning`.
+    pub used_fallback: bool,
+    /// Whether a memory allocation failed or the buffer size limit was reached during shaping.
+    ///
+    /// The glyphs are incomplete in this case.
+    ///
+    /// Also set when the buffer already contained glyphs, which are returned unshaped.
+    pub had_error: bool,
+    /// A text direction the buffer was shaped with.
+    ///
+    /// Guessed from the text, when it wasn't set.
+    pub direction: Direction,
+    /// A script the buffer was shaped with.
+    ///
+    /// Guessed from the text, when it wasn't set.
+    pub script: Script,
+}
+
+impl std::ops::Deref for ShapingResult {
+    type Target = GlyphBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.glyphs
+    }
+}
+
+impl std::ops::DerefMut for ShapingResult {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.glyphs
     }
 }
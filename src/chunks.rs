@@ -0,0 +1,151 @@
+use std::ops::Range;
+
+use crate::{Direction, Feature, Font, GlyphBuffer, GlyphFlags, Language, Script, UnicodeBuffer};
+
+/// Shapes text in chunks of about `chunk_len` bytes.
+///
+/// Useful for very long paragraphs, because chunks are shaped lazily.
+///
+/// A chunk ends only at a cluster boundary which is safe to break,
+/// so shaping chunks separately produces the same glyphs as shaping the whole text.
+/// When there is no such boundary, the chunk length is doubled until there is one,
+/// so a long unbreakable run, like a long Arabic word, is shaped only a few times.
+/// Text around a chunk is used as a context, therefore Arabic joining is preserved
+/// across chunk boundaries.
+///
+/// Segment properties are guessed once, from the whole text.
+///
+/// Chunks are returned in logical order, while glyphs of each chunk are in visual order.
+/// Glyph clusters are byte offsets into `text`.
+///
+/// # Panics
+///
+/// Panics when `chunk_len` is zero.
+pub fn shape_chunks<'a, 'b>(
+    font: &'a Font<'b>,
+    features: &'a [Feature],
+    text: &'a str,
+    chunk_len: usize,
+) -> ShapeChunks<'a, 'b> {
+    assert_ne!(chunk_len, 0, "chunk length must be positive");
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+
+    ShapeChunks {
+        font,
+        features,
+        text,
+        chunk_len,
+        offset: 0,
+        direction: buffer.direction(),
+        script: buffer.script(),
+        language: buffer.language(),
+        buffer: Some(buffer),
+    }
+}
+
+
+/// An iterator over chunks of a shaped text.
+///
+/// See `shape_chunks`.
+pub struct ShapeChunks<'a, 'b> {
+    font: &'a Font<'b>,
+    features: &'a [Feature],
+    text: &'a str,
+    chunk_len: usize,
+    offset: usize,
+    direction: Direction,
+    script: Script,
+    language: Option<Language>,
+    buffer: Option<UnicodeBuffer>,
+}
+
+impl ShapeChunks<'_, '_> {
+    fn shape(&mut self, range: Range<usize>) -> GlyphBuffer {
+        let mut buffer = self.buffer.take().unwrap_or_default();
+        buffer.clear();
+        buffer.push_str_with_context(self.text, range);
+        buffer.set_direction(self.direction);
+        buffer.set_script(self.script);
+        if let Some(ref language) = self.language {
+            buffer.set_language(language.clone());
+        }
+
+        crate::shape(self.font, self.features, buffer)
+    }
+}
+
+impl Iterator for ShapeChunks<'_, '_> {
+    type Item = GlyphBuffer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.text.len() {
+            return None;
+        }
+
+        let mut len = self.chunk_len;
+        loop {
+            let end = ceil_char_boundary(self.text, self.offset.saturating_add(len));
+            let glyphs = self.shape(self.offset..end);
+            if end == self.text.len() {
+                self.offset = end;
+                return Some(glyphs);
+            }
+
+            // Glyphs before the cut are the same as in the whole text shaping.
+            // The rest is reshaped as a part of the next chunk.
+            let chunk = last_safe_break(&glyphs, self.offset)
+                .and_then(|cut| glyphs.split_at_cluster(cut as u32).ok().map(|(chunk, _)| (cut, chunk)));
+            self.buffer = Some(glyphs.clear());
+
+            if let Some((cut, chunk)) = chunk {
+                self.offset = cut;
+                return Some(chunk);
+            }
+
+            len = len.saturating_mul(2);
+        }
+    }
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+
+    index
+}
+
+/// Returns the largest cluster after `start` at which the glyphs can be split.
+fn last_safe_break(glyphs: &GlyphBuffer, start: usize) -> Option<usize> {
+    let mut clusters: Vec<_> = glyphs.glyph_infos().iter()
+        .map(|info| (info.cluster as usize, info.flags().contains(GlyphFlags::UNSAFE_TO_BREAK)))
+        .collect();
+    clusters.sort_unstable();
+
+    let mut i = clusters.len();
+    while i > 0 {
+        let cluster = clusters[i - 1].0;
+        if cluster <= start {
+            break;
+        }
+
+        let mut is_safe = true;
+        while i > 0 && clusters[i - 1].0 == cluster {
+            is_safe &= !clusters[i - 1].1;
+            i -= 1;
+        }
+
+        if is_safe {
+            return Some(cluster);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,825 @@
+use crate::stream::Stream;
+
+// Paint graphs are DAGs, but a malformed font can have cycles or be arbitrary deep.
+const MAX_NESTING_DEPTH: usize = 64;
+
+// ColorStop and VarColorStop, which adds a u32 `varIndexBase`.
+const COLOR_STOP_SIZE: usize = 6;
+const VAR_COLOR_STOP_SIZE: usize = 10;
+
+/// An RGBA color.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    /// An opaque black color.
+    pub const BLACK: Color = Color { red: 0, green: 0, blue: 0, alpha: 255 };
+
+    fn multiply_alpha(self, alpha: f32) -> Self {
+        let alpha = (f32::from(self.alpha) * alpha).round().max(0.0).min(255.0) as u8;
+        Color { alpha, ..self }
+    }
+}
+
+
+/// A gradient color stop.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    /// A position on the color line.
+    pub offset: f32,
+    /// A stop color.
+    pub color: Color,
+}
+
+
+/// A gradient extend mode.
+///
+/// Defines how a gradient is painted outside of its color line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum GradientExtend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+
+/// A 2x3 affine transform.
+///
+/// Maps `(x, y)` to `(xx * x + xy * y + dx, yx * x + yy * y + dy)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(missing_docs)]
+pub struct Transform {
+    pub xx: f32,
+    pub yx: f32,
+    pub xy: f32,
+    pub yy: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Transform {
+    fn new(xx: f32, yx: f32, xy: f32, yy: f32, dx: f32, dy: f32) -> Self {
+        Transform { xx, yx, xy, yy, dx, dy }
+    }
+
+    fn translate(dx: f32, dy: f32) -> Self {
+        Transform::new(1.0, 0.0, 0.0, 1.0, dx, dy)
+    }
+
+    fn scale(sx: f32, sy: f32) -> Self {
+        Transform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    // Counter-clockwise, in radians.
+    fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    // Counter-clockwise, in radians.
+    fn skew(x_angle: f32, y_angle: f32) -> Self {
+        Transform::new(1.0, y_angle.tan(), -x_angle.tan(), 1.0, 0.0, 0.0)
+    }
+
+    // Applies a transform without a translation around a center point.
+    fn around_center(self, cx: f32, cy: f32) -> Self {
+        Transform {
+            dx: cx - (self.xx * cx + self.xy * cy),
+            dy: cy - (self.yx * cx + self.yy * cy),
+            ..self
+        }
+    }
+}
+
+
+/// A layers composition mode.
+///
+/// See [COLR](https://docs.microsoft.com/en-us/typography/opentype/spec/colr#compositemode-enumeration)
+/// for details.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum CompositeMode {
+    Clear,
+    Source,
+    Destination,
+    SourceOver,
+    DestinationOver,
+    SourceIn,
+    DestinationIn,
+    SourceOut,
+    DestinationOut,
+    SourceAtop,
+    DestinationAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeMode {
+    fn from_u8(n: u8) -> Option<Self> {
+        use CompositeMode::*;
+        const MODES: &[CompositeMode] = &[
+            Clear, Source, Destination, SourceOver, DestinationOver, SourceIn, DestinationIn,
+            SourceOut, DestinationOut, SourceAtop, DestinationAtop, Xor, Plus, Screen, Overlay,
+            Darken, Lighten, ColorDodge, ColorBurn, HardLight, SoftLight, Difference, Exclusion,
+            Multiply, Hue, Saturation, Color, Luminosity,
+        ];
+
+        MODES.get(usize::from(n)).cloned()
+    }
+}
+
+
+/// A fill of the current clip area.
+///
+/// Coordinates are in font units, in the current transform space.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(missing_docs)]
+pub enum Paint<'a> {
+    Solid(Color),
+    /// A gradient from `(x0, y0)` to `(x1, y1)`, rotated by `(x2, y2)`.
+    LinearGradient {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        extend: GradientExtend,
+        stops: &'a [ColorStop],
+    },
+    /// A gradient between two circles.
+    RadialGradient {
+        x0: f32,
+        y0: f32,
+        r0: f32,
+        x1: f32,
+        y1: f32,
+        r1: f32,
+        extend: GradientExtend,
+        stops: &'a [ColorStop],
+    },
+}
+
+
+/// A receiver of a color glyph paint graph.
+///
+/// See `Font::paint_glyph`.
+///
+/// Push and pop calls are always balanced.
+pub trait PaintSink {
+    /// Applies a transform to all nested paints, on top of the current one.
+    fn push_transform(&mut self, transform: Transform);
+
+    /// Restores the transform before the matching `push_transform`.
+    fn pop_transform(&mut self);
+
+    /// Restricts all nested paints to a glyph outline, in the current transform space.
+    fn push_clip_glyph(&mut self, glyph: u32);
+
+    /// Restores the clip before the matching `push_clip_glyph`.
+    fn pop_clip(&mut self);
+
+    /// Fills the current clip area.
+    fn paint(&mut self, paint: Paint);
+
+    /// Starts a new layer, which will be composed with the current one on `pop_layer`.
+    fn push_layer(&mut self, mode: CompositeMode);
+
+    /// Composes the current layer with the one below it.
+    fn pop_layer(&mut self);
+
+    /// Returns a color used by paints that reference the foreground (text) color.
+    ///
+    /// Black by default.
+    fn foreground_color(&self) -> Color {
+        Color::BLACK
+    }
+}
+
+
+pub(crate) fn paint_glyph(colr: &[u8], cpal: Option<&[u8]>, glyph: u16, sink: &mut dyn PaintSink) -> bool {
+    let foreground = sink.foreground_color();
+    let mut painter = Painter {
+        colr: Stream(colr),
+        cpal: cpal.map(Stream),
+        sink,
+        foreground,
+        stack: Vec::new(),
+    };
+
+    if let Some(offset) = painter.find_base_paint(glyph) {
+        painter.paint(offset);
+        true
+    } else {
+        painter.paint_layers_v0(glyph)
+    }
+}
+
+
+struct Painter<'a, 'b> {
+    colr: Stream<'a>,
+    cpal: Option<Stream<'a>>,
+    sink: &'b mut dyn PaintSink,
+    foreground: Color,
+    // Offsets of the paints being painted, to detect cycles.
+    stack: Vec<usize>,
+}
+
+impl Painter<'_, '_> {
+    fn find_base_paint(&self, glyph: u16) -> Option<usize> {
+        if self.colr.u16(0)? < 1 {
+            return None;
+        }
+
+        let list = self.colr.offset32(0, 14)?;
+        let count = self.colr.u32(list)? as usize;
+
+        // Records are sorted by glyph id.
+        let (mut start, mut end) = (0, count);
+        while start < end {
+            let mid = (start + end) / 2;
+            let record = list + 4 + mid * 6;
+            let id = self.colr.u16(record)?;
+            if id == glyph {
+                return self.colr.offset32(list, record - list + 2);
+            } else if id < glyph {
+                start = mid + 1;
+            } else {
+                end = mid;
+            }
+        }
+
+        None
+    }
+
+    fn paint_layers_v0(&mut self, glyph: u16) -> bool {
+        let colr = self.colr;
+        let base_count = colr.u16(2).unwrap_or(0) as usize;
+        let base_records = colr.u32(4).unwrap_or(0) as usize;
+        let layer_records = colr.u32(8).unwrap_or(0) as usize;
+        let layer_count = colr.u16(12).unwrap_or(0) as usize;
+
+        // Records are sorted by glyph id, but there are usually only a few of them.
+        let record = (0..base_count)
+            .map(|i| base_records + i * 6)
+            .find(|&record| colr.u16(record) == Some(glyph));
+
+        let record = match record {
+            Some(v) => v,
+            None => return false,
+        };
+
+        let first = colr.u16(record + 2).unwrap_or(0) as usize;
+        let count = colr.u16(record + 4).unwrap_or(0) as usize;
+        for i in first..(first + count).min(layer_count) {
+            let layer = layer_records + i * 4;
+            if let (Some(id), Some(palette_index)) = (colr.u16(layer), colr.u16(layer + 2)) {
+                let color = self.color(palette_index, 1.0);
+                self.sink.push_clip_glyph(u32::from(id));
+                self.sink.paint(Paint::Solid(color));
+                self.sink.pop_clip();
+            }
+        }
+
+        true
+    }
+
+    fn paint(&mut self, offset: usize) {
+        if self.stack.len() >= MAX_NESTING_DEPTH || self.stack.contains(&offset) {
+            return;
+        }
+
+        self.stack.push(offset);
+        self.paint_impl(offset);
+        self.stack.pop();
+    }
+
+    // Variable formats are painted using default values.
+    // Sweep gradients and unknown formats are skipped.
+    fn paint_impl(&mut self, offset: usize) -> Option<()> {
+        let s = self.colr;
+        let format = s.u8(offset)?;
+        match format {
+            1 => {
+                // PaintColrLayers
+                let count = usize::from(s.u8(offset + 1)?);
+                let first = s.u32(offset + 2)? as usize;
+                let list = s.offset32(0, 18)?;
+                for i in first..first.checked_add(count)? {
+                    let layer = s.offset32(list, 4 + i * 4)?;
+                    self.paint(layer);
+                }
+            }
+            2 | 3 => {
+                // PaintSolid, PaintVarSolid
+                let color = self.color(s.u16(offset + 1)?, s.f2dot14(offset + 3)?);
+                self.sink.paint(Paint::Solid(color));
+            }
+            4 | 5 => {
+                // PaintLinearGradient, PaintVarLinearGradient
+                let stop_size = if format == 5 { VAR_COLOR_STOP_SIZE } else { COLOR_STOP_SIZE };
+                let (extend, stops) = self.color_line(s.offset24(offset, 1)?, stop_size)?;
+                self.sink.paint(Paint::LinearGradient {
+                    x0: s.fword(offset + 4)?,
+                    y0: s.fword(offset + 6)?,
+                    x1: s.fword(offset + 8)?,
+                    y1: s.fword(offset + 10)?,
+                    x2: s.fword(offset + 12)?,
+                    y2: s.fword(offset + 14)?,
+                    extend,
+                    stops: &stops,
+                });
+            }
+            6 | 7 => {
+                // PaintRadialGradient, PaintVarRadialGradient
+                let stop_size = if format == 7 { VAR_COLOR_STOP_SIZE } else { COLOR_STOP_SIZE };
+                let (extend, stops) = self.color_line(s.offset24(offset, 1)?, stop_size)?;
+                self.sink.paint(Paint::RadialGradient {
+                    x0: s.fword(offset + 4)?,
+                    y0: s.fword(offset + 6)?,
+                    r0: s.ufword(offset + 8)?,
+                    x1: s.fword(offset + 10)?,
+                    y1: s.fword(offset + 12)?,
+                    r1: s.ufword(offset + 14)?,
+                    extend,
+                    stops: &stops,
+                });
+            }
+            10 => {
+                // PaintGlyph
+                let paint = s.offset24(offset, 1)?;
+                let glyph = s.u16(offset + 4)?;
+                self.sink.push_clip_glyph(u32::from(glyph));
+                self.paint(paint);
+                self.sink.pop_clip();
+            }
+            11 => {
+                // PaintColrGlyph
+                let paint = self.find_base_paint(s.u16(offset + 1)?)?;
+                self.paint(paint);
+            }
+            12 | 13 => {
+                // PaintTransform, PaintVarTransform
+                let paint = s.offset24(offset, 1)?;
+                let t = s.offset24(offset, 4)?;
+                let transform = Transform::new(
+                    s.fixed(t)?, s.fixed(t + 4)?, s.fixed(t + 8)?,
+                    s.fixed(t + 12)?, s.fixed(t + 16)?, s.fixed(t + 20)?,
+                );
+                self.paint_transformed(paint, transform);
+            }
+            14 | 15 => {
+                // PaintTranslate, PaintVarTranslate
+                let transform = Transform::translate(s.fword(offset + 4)?, s.fword(offset + 6)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            16 | 17 => {
+                // PaintScale, PaintVarScale
+                let transform = Transform::scale(s.f2dot14(offset + 4)?, s.f2dot14(offset + 6)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            18 | 19 => {
+                // PaintScaleAroundCenter, PaintVarScaleAroundCenter
+                let transform = Transform::scale(s.f2dot14(offset + 4)?, s.f2dot14(offset + 6)?)
+                    .around_center(s.fword(offset + 8)?, s.fword(offset + 10)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            20 | 21 => {
+                // PaintScaleUniform, PaintVarScaleUniform
+                let scale = s.f2dot14(offset + 4)?;
+                self.paint_transformed(s.offset24(offset, 1)?, Transform::scale(scale, scale));
+            }
+            22 | 23 => {
+                // PaintScaleUniformAroundCenter, PaintVarScaleUniformAroundCenter
+                let scale = s.f2dot14(offset + 4)?;
+                let transform = Transform::scale(scale, scale)
+                    .around_center(s.fword(offset + 6)?, s.fword(offset + 8)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            24 | 25 => {
+                // PaintRotate, PaintVarRotate
+                let transform = Transform::rotate(angle(s.f2dot14(offset + 4)?));
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            26 | 27 => {
+                // PaintRotateAroundCenter, PaintVarRotateAroundCenter
+                let transform = Transform::rotate(angle(s.f2dot14(offset + 4)?))
+                    .around_center(s.fword(offset + 6)?, s.fword(offset + 8)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            28 | 29 => {
+                // PaintSkew, PaintVarSkew
+                let transform = Transform::skew(
+                    angle(s.f2dot14(offset + 4)?),
+                    angle(s.f2dot14(offset + 6)?),
+                );
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            30 | 31 => {
+                // PaintSkewAroundCenter, PaintVarSkewAroundCenter
+                let transform = Transform::skew(
+                    angle(s.f2dot14(offset + 4)?),
+                    angle(s.f2dot14(offset + 6)?),
+                ).around_center(s.fword(offset + 8)?, s.fword(offset + 10)?);
+                self.paint_transformed(s.offset24(offset, 1)?, transform);
+            }
+            32 => {
+                // PaintComposite
+                let source = s.offset24(offset, 1)?;
+                let mode = CompositeMode::from_u8(s.u8(offset + 4)?)?;
+                let backdrop = s.offset24(offset, 5)?;
+                self.sink.push_layer(CompositeMode::SourceOver);
+                self.paint(backdrop);
+                self.sink.push_layer(mode);
+                self.paint(source);
+                self.sink.pop_layer();
+                self.sink.pop_layer();
+            }
+            _ => {}
+        }
+
+        Some(())
+    }
+
+    fn paint_transformed(&mut self, offset: usize, transform: Transform) {
+        self.sink.push_transform(transform);
+        self.paint(offset);
+        self.sink.pop_transform();
+    }
+
+    // Variable gradients point to a VarColorLine, which has bigger stops.
+    fn color_line(&self, offset: usize, stop_size: usize) -> Option<(GradientExtend, Vec<ColorStop>)> {
+        let s = self.colr;
+        let extend = match s.u8(offset)? {
+            1 => GradientExtend::Repeat,
+            2 => GradientExtend::Reflect,
+            _ => GradientExtend::Pad,
+        };
+
+        let count = usize::from(s.u16(offset + 1)?);
+        let mut stops = Vec::with_capacity(count);
+        for i in 0..count {
+            let stop = offset + 3 + i * stop_size;
+            stops.push(ColorStop {
+                offset: s.f2dot14(stop)?,
+                color: self.color(s.u16(stop + 2)?, s.f2dot14(stop + 4)?),
+            });
+        }
+
+        Some((extend, stops))
+    }
+
+    // Colors are always taken from the first palette.
+    // 0xFFFF and invalid indices are resolved to the foreground color.
+    fn color(&self, palette_index: u16, alpha: f32) -> Color {
+        let color = if palette_index == 0xFFFF {
+            None
+        } else {
+            self.cpal.and_then(|cpal| palette_color(cpal, palette_index))
+        };
+
+        color.unwrap_or(self.foreground).multiply_alpha(alpha)
+    }
+}
+
+fn palette_color(cpal: Stream, index: u16) -> Option<Color> {
+    if index >= cpal.u16(2)? || cpal.u16(4)? == 0 {
+        return None;
+    }
+
+    let records = cpal.u32(8)? as usize;
+    let record = records + (usize::from(cpal.u16(12)?) + usize::from(index)) * 4;
+    Some(Color {
+        blue: cpal.u8(record)?,
+        green: cpal.u8(record + 1)?,
+        red: cpal.u8(record + 2)?,
+        alpha: cpal.u8(record + 3)?,
+    })
+}
+
+// Angles are stored in half turns.
+fn angle(n: f32) -> f32 {
+    n * std::f32::consts::PI
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(PartialEq, Debug)]
+    enum Event {
+        PushTransform(Transform),
+        PopTransform,
+        PushClip(u32),
+        PopClip,
+        Solid(Color),
+        Linear(f32, f32, f32, f32, GradientExtend, Vec<ColorStop>),
+        Radial(f32, f32, GradientExtend, Vec<ColorStop>),
+        PushLayer(CompositeMode),
+        PopLayer,
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    impl PaintSink for Recorder {
+        fn push_transform(&mut self, transform: Transform) {
+            self.0.push(Event::PushTransform(transform));
+        }
+
+        fn pop_transform(&mut self) {
+            self.0.push(Event::PopTransform);
+        }
+
+        fn push_clip_glyph(&mut self, glyph: u32) {
+            self.0.push(Event::PushClip(glyph));
+        }
+
+        fn pop_clip(&mut self) {
+            self.0.push(Event::PopClip);
+        }
+
+        fn paint(&mut self, paint: Paint) {
+            self.0.push(match paint {
+                Paint::Solid(color) => Event::Solid(color),
+                Paint::LinearGradient { x0, y0, x1, y1, extend, stops, .. } => {
+                    Event::Linear(x0, y0, x1, y1, extend, stops.to_vec())
+                }
+                Paint::RadialGradient { r0, r1, extend, stops, .. } => {
+                    Event::Radial(r0, r1, extend, stops.to_vec())
+                }
+            });
+        }
+
+        fn push_layer(&mut self, mode: CompositeMode) {
+            self.0.push(Event::PushLayer(mode));
+        }
+
+        fn pop_layer(&mut self) {
+            self.0.push(Event::PopLayer);
+        }
+    }
+
+    const RED: Color = Color { red: 255, green: 0, blue: 0, alpha: 255 };
+    const BLUE: Color = Color { red: 0, green: 0, blue: 255, alpha: 255 };
+
+    // One palette with red and blue.
+    const CPAL: &[u8] = &[
+        0, 0, // version
+        0, 2, // numPaletteEntries
+        0, 1, // numPalettes
+        0, 2, // numColorRecords
+        0, 0, 0, 14, // colorRecordsArrayOffset
+        0, 0, // colorRecordIndices[0]
+        0, 0, 255, 255, // red, as BGRA
+        255, 0, 0, 255, // blue
+    ];
+
+    // Builds a COLRv1 table with the provided base glyph paints.
+    // `paints` are appended right after the base glyph list and are referenced
+    // by offsets relative to the start of `paints`.
+    fn colr_v1(base_glyphs: &[(u16, u32)], layers: &[u32], paints: &[u8]) -> Vec<u8> {
+        let header_len = 34;
+        let base_list_len = 4 + base_glyphs.len() * 6;
+        let layer_list = header_len + base_list_len;
+        let paints_start = (layer_list + 4 + layers.len() * 4) as u32;
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0, 1, 0, 0]); // version, numBaseGlyphRecords
+        data.extend_from_slice(&[0; 10]); // baseGlyphRecordsOffset, layerRecordsOffset, numLayerRecords
+        data.extend_from_slice(&(header_len as u32).to_be_bytes());
+        data.extend_from_slice(&(layer_list as u32).to_be_bytes());
+        data.extend_from_slice(&[0; 12]); // clipList, varIndexMap, itemVariationStore
+
+        data.extend_from_slice(&(base_glyphs.len() as u32).to_be_bytes());
+        for &(glyph, paint) in base_glyphs {
+            data.extend_from_slice(&glyph.to_be_bytes());
+            data.extend_from_slice(&(paints_start - header_len as u32 + paint).to_be_bytes());
+        }
+
+        data.extend_from_slice(&(layers.len() as u32).to_be_bytes());
+        for &paint in layers {
+            data.extend_from_slice(&(paints_start - layer_list as u32 + paint).to_be_bytes());
+        }
+
+        data.extend_from_slice(paints);
+        data
+    }
+
+    fn paint(colr: &[u8], glyph: u16) -> Option<Vec<Event>> {
+        let mut sink = Recorder::default();
+        if paint_glyph(colr, Some(CPAL), glyph, &mut sink) {
+            Some(sink.0)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn solid_in_glyph() {
+        let paints = &[
+            10, 0, 0, 6, 0, 5, // PaintGlyph: paint +6, glyph 5
+            2, 0, 1, 0x20, 0, // PaintSolid: blue, alpha 0.5
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[], paints);
+        assert_eq!(paint(&colr, 1), Some(vec![
+            Event::PushClip(5),
+            Event::Solid(Color { alpha: 128, ..BLUE }),
+            Event::PopClip,
+        ]));
+
+        assert_eq!(paint(&colr, 2), None);
+    }
+
+    #[test]
+    fn foreground_color() {
+        let paints = &[
+            2, 0xFF, 0xFF, 0x40, 0, // PaintSolid: foreground, alpha 1
+            2, 0, 9, 0x40, 0, // PaintSolid: out of palette
+        ];
+
+        let colr = colr_v1(&[(1, 0), (2, 5)], &[], paints);
+        assert_eq!(paint(&colr, 1), Some(vec![Event::Solid(Color::BLACK)]));
+        assert_eq!(paint(&colr, 2), Some(vec![Event::Solid(Color::BLACK)]));
+    }
+
+    #[test]
+    fn gradients() {
+        let paints = &[
+            // PaintColrLayers: 2 layers, starting at 0
+            1, 2, 0, 0, 0, 0,
+            // PaintLinearGradient: color line +16, (0, 0), (100, -100), (0, 100)
+            4, 0, 0, 16, 0, 0, 0, 0, 0, 100, 0xFF, 0x9C, 0, 0, 0, 100,
+            // ColorLine: reflect, 2 stops: red at 0, blue at 1
+            2, 0, 2, 0, 0, 0, 0, 0x40, 0, 0x40, 0, 0, 1, 0x40, 0,
+            // PaintRadialGradient: color line +16, (0, 0, 10), (0, 0, 50)
+            6, 0, 0, 16, 0, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0, 50,
+            // ColorLine: pad, 1 stop: blue at 0.5
+            0, 0, 1, 0x20, 0, 0, 1, 0x40, 0,
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[6, 37], paints);
+        let stops = vec![
+            ColorStop { offset: 0.0, color: RED },
+            ColorStop { offset: 1.0, color: BLUE },
+        ];
+
+        assert_eq!(paint(&colr, 1), Some(vec![
+            Event::Linear(0.0, 0.0, 100.0, -100.0, GradientExtend::Reflect, stops),
+            Event::Radial(10.0, 50.0, GradientExtend::Pad, vec![ColorStop { offset: 0.5, color: BLUE }]),
+        ]));
+    }
+
+    #[test]
+    fn variable_gradient() {
+        let paints = &[
+            // PaintVarLinearGradient: color line +20, (0, 0), (100, -100), (0, 100), no variations
+            5, 0, 0, 20, 0, 0, 0, 0, 0, 100, 0xFF, 0x9C, 0, 0, 0, 100, 0xFF, 0xFF, 0xFF, 0xFF,
+            // VarColorLine: pad, 2 stops: red at 0, blue with alpha 0.5 at 1
+            0, 0, 2,
+            0, 0, 0, 0, 0x40, 0, 0xFF, 0xFF, 0xFF, 0xFF,
+            0x40, 0, 0, 1, 0x20, 0, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[], paints);
+        let stops = vec![
+            ColorStop { offset: 0.0, color: RED },
+            ColorStop { offset: 1.0, color: Color { alpha: 128, ..BLUE } },
+        ];
+
+        assert_eq!(paint(&colr, 1), Some(vec![
+            Event::Linear(0.0, 0.0, 100.0, -100.0, GradientExtend::Pad, stops),
+        ]));
+    }
+
+    #[test]
+    fn transforms() {
+        let paints = &[
+            // PaintTranslate: paint +8, (10, -20)
+            14, 0, 0, 8, 0, 10, 0xFF, 0xEC,
+            // PaintRotateAroundCenter: paint +10, 0.5 (90 degrees), (100, 0)
+            26, 0, 0, 10, 0x20, 0, 0, 100, 0, 0,
+            // PaintSolid: red
+            2, 0, 0, 0x40, 0,
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[], paints);
+        let events = paint(&colr, 1).unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0], Event::PushTransform(Transform::translate(10.0, -20.0)));
+        assert_eq!(events[2], Event::Solid(RED));
+        assert_eq!(events[3], Event::PopTransform);
+        assert_eq!(events[4], Event::PopTransform);
+
+        // (100, 0) stays in place, while (0, 0) is moved to (100, -100).
+        match events[1] {
+            Event::PushTransform(ts) => {
+                assert!((ts.xx * 100.0 + ts.dx - 100.0).abs() < 0.001);
+                assert!((ts.yx * 100.0 + ts.dy).abs() < 0.001);
+                assert!((ts.dx - 100.0).abs() < 0.001);
+                assert!((ts.dy + 100.0).abs() < 0.001);
+            }
+            ref e => panic!("unexpected event: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn composite() {
+        let paints = &[
+            // PaintComposite: source +8, Multiply, backdrop +13
+            32, 0, 0, 8, 23, 0, 0, 13,
+            // PaintSolid: red
+            2, 0, 0, 0x40, 0,
+            // PaintSolid: blue
+            2, 0, 1, 0x40, 0,
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[], paints);
+        assert_eq!(paint(&colr, 1), Some(vec![
+            Event::PushLayer(CompositeMode::SourceOver),
+            Event::Solid(BLUE),
+            Event::PushLayer(CompositeMode::Multiply),
+            Event::Solid(RED),
+            Event::PopLayer,
+            Event::PopLayer,
+        ]));
+    }
+
+    #[test]
+    fn cycles() {
+        let paints = &[
+            // PaintColrGlyph: glyph 2
+            11, 0, 2,
+            // PaintGlyph: paint +6, glyph 7
+            10, 0, 0, 6, 0, 7,
+            // PaintColrGlyph: glyph 1
+            11, 0, 1,
+        ];
+
+        // 1 -> 2 -> 1
+        let colr = colr_v1(&[(1, 0), (2, 3)], &[], paints);
+        assert_eq!(paint(&colr, 1), Some(vec![Event::PushClip(7), Event::PopClip]));
+    }
+
+    #[test]
+    fn unsupported_and_malformed() {
+        let paints = &[
+            // PaintColrLayers: 3 layers, starting at 0
+            1, 3, 0, 0, 0, 0,
+            // PaintSweepGradient, which is not supported
+            8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            // PaintSolid: red
+            2, 0, 0, 0x40, 0,
+            // PaintLinearGradient, truncated
+            4, 0, 0, 16,
+        ];
+
+        let colr = colr_v1(&[(1, 0)], &[6, 18, 23], paints);
+        assert_eq!(paint(&colr, 1), Some(vec![Event::Solid(RED)]));
+
+        assert_eq!(paint(&[], 1), None);
+        assert_eq!(paint(&colr[..40], 1), None);
+    }
+
+    #[test]
+    fn layers_v0() {
+        let colr = &[
+            0, 0, 0, 1, // version, numBaseGlyphRecords
+            0, 0, 0, 14, 0, 0, 0, 20, 0, 2, // offsets, numLayerRecords
+            0, 3, 0, 0, 0, 2, // glyph 3, 2 layers from 0
+            0, 4, 0, 1, // glyph 4, blue
+            0, 5, 0xFF, 0xFF, // glyph 5, foreground
+        ];
+
+        assert_eq!(paint(colr, 3), Some(vec![
+            Event::PushClip(4), Event::Solid(BLUE), Event::PopClip,
+            Event::PushClip(5), Event::Solid(Color::BLACK), Event::PopClip,
+        ]));
+
+        assert_eq!(paint(colr, 4), None);
+    }
+}
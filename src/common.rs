@@ -42,6 +42,56 @@ impl Direction {
         }
     }
 
+    /// Checks that the direction is `LeftToRight` or `RightToLeft`.
+    #[inline]
+    pub fn is_horizontal(self) -> bool {
+        match self {
+            Direction::LeftToRight | Direction::RightToLeft => true,
+            _ => false,
+        }
+    }
+
+    /// Checks that the direction is `TopToBottom` or `BottomToTop`.
+    #[inline]
+    pub fn is_vertical(self) -> bool {
+        match self {
+            Direction::TopToBottom | Direction::BottomToTop => true,
+            _ => false,
+        }
+    }
+
+    /// Checks that the direction is `LeftToRight` or `TopToBottom`.
+    #[inline]
+    pub fn is_forward(self) -> bool {
+        match self {
+            Direction::LeftToRight | Direction::TopToBottom => true,
+            _ => false,
+        }
+    }
+
+    /// Checks that the direction is `RightToLeft` or `BottomToTop`.
+    #[inline]
+    pub fn is_backward(self) -> bool {
+        match self {
+            Direction::RightToLeft | Direction::BottomToTop => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the opposite direction.
+    ///
+    /// `Invalid` stays `Invalid`.
+    #[inline]
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Invalid => Direction::Invalid,
+            Direction::LeftToRight => Direction::RightToLeft,
+            Direction::RightToLeft => Direction::LeftToRight,
+            Direction::TopToBottom => Direction::BottomToTop,
+            Direction::BottomToTop => Direction::TopToBottom,
+        }
+    }
+
     pub(crate) fn from_script(script: Script) -> Option<Self> {
         // https://docs.google.com/spreadsheets/d/1Y90M0Ie3MUJ6UVCRDOypOtijlMDLNNyyLk36T6iMu0o
 
@@ -150,7 +200,7 @@ impl std::str::FromStr for Direction {
 
 #[no_mangle]
 pub extern "C" fn rb_script_get_horizontal_direction(script: ffi::hb_script_t) -> ffi::hb_direction_t {
-    Direction::from_script(Script(Tag(script))).unwrap_or_default().to_raw()
+    Direction::from_script(Script::from_hb(script)).unwrap_or_default().to_raw()
 }
 
 
@@ -163,6 +213,33 @@ impl Language {
     pub fn as_str(&self) -> &str {
         self.0.to_str().unwrap()
     }
+
+    /// Checks that the language maps to the OpenType language system `ot_tag`.
+    ///
+    /// Uses the same mapping as `ot_tags_from_script_and_language`.
+    /// A language can match multiple language systems.
+    pub fn matches_ot(&self, ot_tag: Tag) -> bool {
+        ot_tags_from_script_and_language(script::COMMON, Some(self)).1.contains(&ot_tag)
+    }
+}
+
+/// Text properties that select how a run is shaped.
+///
+/// See `shape_run`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SegmentProperties {
+    /// A text direction.
+    pub direction: Direction,
+    /// A text script.
+    pub script: Script,
+    /// A text language. `None` means no language-specific behavior.
+    pub language: Option<Language>,
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl std::str::FromStr for Language {
@@ -188,7 +265,22 @@ impl std::str::FromStr for Language {
 pub struct Script(pub(crate) Tag);
 
 impl Script {
-    pub(crate) const fn from_raw(script: ffi::hb_script_t) -> Self {
+    /// Creates a `Script` from a raw harfbuzz script value, i.e. an ISO 15924 tag as `u32`.
+    ///
+    /// Unlike `from_iso15924_tag`, the case is not adjusted and aliases are not resolved.
+    /// Zero, which is `HB_SCRIPT_INVALID`, and values that do not look like
+    /// a script tag are mapped to `script::UNKNOWN`.
+    #[inline]
+    pub fn from_raw(script: u32) -> Self {
+        if script & 0xE0E0E0E0 == 0x40606060 {
+            Script(Tag(script))
+        } else {
+            script::UNKNOWN
+        }
+    }
+
+    // Keeps `HB_SCRIPT_INVALID`, which harfbuzz uses for an unset script.
+    pub(crate) const fn from_hb(script: ffi::hb_script_t) -> Self {
         Script(Tag(script))
     }
 
@@ -233,6 +325,44 @@ impl Script {
     }
 }
 
+
+/// Returns OpenType script and language system tags for a script and a language.
+///
+/// `GSUB` and `GPOS` tables use their own tags, which differ from ISO 15924 and BCP 47 ones.
+/// Tags are returned in the order they are looked up in a font.
+/// For example, Devanagari maps to `dev3`, `dev2` and the old Indic `deva` tag,
+/// so the new shaping model is preferred when the font supports it.
+///
+/// The default `DFLT` script and `dflt` language system are not included,
+/// but are used as a fallback when a font has none of the returned tags.
+/// Only a few scripts, like Common, have no tags at all.
+///
+/// Tags can also be set explicitly via the `-hbsc-` and `-hbot-` private use subtags,
+/// like in `x-hbscdflt-hbot-abc`.
+pub fn ot_tags_from_script_and_language(script: Script, language: Option<&Language>) -> (Vec<Tag>, Vec<Tag>) {
+    // HB_OT_MAX_TAGS_PER_SCRIPT and HB_OT_MAX_TAGS_PER_LANGUAGE
+    let mut script_tags = [Tag(0); 3];
+    let mut language_tags = [Tag(0); 3];
+    let mut script_count = script_tags.len() as u32;
+    let mut language_count = language_tags.len() as u32;
+
+    unsafe {
+        ffi::hb_ot_tags_from_script_and_language(
+            script.0.as_u32(),
+            language.map(|l| l.0.as_ptr()).unwrap_or(std::ptr::null()),
+            &mut script_count,
+            script_tags.as_mut_ptr(),
+            &mut language_count,
+            language_tags.as_mut_ptr(),
+        );
+    }
+
+    (
+        script_tags[..script_count as usize].to_vec(),
+        language_tags[..language_count as usize].to_vec(),
+    )
+}
+
 impl std::str::FromStr for Script {
     type Err = &'static str;
 
@@ -429,6 +559,13 @@ pub mod script {
 
 /// A feature tag with an accompanying range specifying on which subslice of
 /// `shape`s input it should be applied.
+///
+/// The range is compared against clusters, which are byte offsets
+/// when the text was added via `UnicodeBuffer::push_str`.
+/// With the default `BufferClusterLevel::MonotoneGraphemes`, combining marks share
+/// the cluster of their base, which makes ranges apply to whole grapheme clusters:
+/// a mark is affected only when its base is.
+/// Use `BufferClusterLevel::Characters` to apply ranges to individual characters.
 #[repr(C)]
 #[allow(missing_docs)]
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -461,8 +598,46 @@ impl Feature {
             end,
         }
     }
+
+    /// Create a new `Feature` that applies to the whole input.
+    ///
+    /// Use `1` to enable a feature and `0` to disable it.
+    pub fn tag_value(tag: Tag, value: u32) -> Feature {
+        Feature::new(tag, value, ..)
+    }
 }
 
+
+/// Features enabled by default for horizontal text in all scripts.
+///
+/// Complex shapers enable additional ones, like `init` and `fina` for Arabic.
+/// For vertical text, `vert` is enabled instead of the `calt` .. `rclt` group.
+///
+/// User features are applied on top of the defaults: a feature with the same tag
+/// overrides the default value, so `Feature::tag_value(Tag::from_bytes(b"kern"), 0)`
+/// disables kerning. This includes features enabled by complex shapers,
+/// like `rlig` or `stch` in the Arabic one.
+///
+/// Mark positioning features, `abvm`, `blwm`, `mark` and `mkmk`, are enabled for all shapers,
+/// including the Arabic one. Like all `GPOS` features, they are applied in a single stage,
+/// lookup by lookup, in the font's lookup order, so the font decides their relative order.
+pub const DEFAULT_FEATURES: &[Tag] = &[
+    Tag::from_bytes(b"abvm"),
+    Tag::from_bytes(b"blwm"),
+    Tag::from_bytes(b"ccmp"),
+    Tag::from_bytes(b"locl"),
+    Tag::from_bytes(b"mark"),
+    Tag::from_bytes(b"mkmk"),
+    Tag::from_bytes(b"rlig"),
+    Tag::from_bytes(b"calt"),
+    Tag::from_bytes(b"clig"),
+    Tag::from_bytes(b"curs"),
+    Tag::from_bytes(b"dist"),
+    Tag::from_bytes(b"kern"),
+    Tag::from_bytes(b"liga"),
+    Tag::from_bytes(b"rclt"),
+];
+
 impl std::str::FromStr for Feature {
     type Err = &'static str;
 
@@ -565,6 +740,133 @@ impl std::str::FromStr for Feature {
     }
 }
 
+#[cfg(test)]
+mod tests_ot_tags {
+    use super::*;
+    use std::str::FromStr;
+
+    fn tags(script: Script, language: Option<&str>) -> (Vec<Tag>, Vec<Tag>) {
+        let language = language.map(|s| Language::from_str(s).unwrap());
+        ot_tags_from_script_and_language(script, language.as_ref())
+    }
+
+    #[test]
+    fn arabic() {
+        assert_eq!(tags(script::ARABIC, None), (vec![Tag::from_bytes(b"arab")], vec![]));
+        assert_eq!(tags(script::ARABIC, Some("ur")),
+                   (vec![Tag::from_bytes(b"arab")], vec![Tag::from_bytes(b"URD ")]));
+        assert_eq!(tags(script::ARABIC, Some("fa-IR")),
+                   (vec![Tag::from_bytes(b"arab")], vec![Tag::from_bytes(b"FAR ")]));
+    }
+
+    #[test]
+    fn indic() {
+        let tags = tags(script::DEVANAGARI, Some("hi")).0;
+        assert_eq!(tags, vec![Tag::from_bytes(b"dev3"), Tag::from_bytes(b"dev2"), Tag::from_bytes(b"deva")]);
+
+        assert_eq!(tags(script::BENGALI, None).0,
+                   vec![Tag::from_bytes(b"bng3"), Tag::from_bytes(b"bng2"), Tag::from_bytes(b"beng")]);
+
+        // No `mym3`.
+        assert_eq!(tags(script::MYANMAR, None).0, vec![Tag::from_bytes(b"mym2"), Tag::from_bytes(b"mymr")]);
+    }
+
+    #[test]
+    fn private_use() {
+        assert_eq!(tags(script::ARABIC, Some("x-hbscabcd-hbotefgh")),
+                   (vec![Tag::from_bytes(b"abcd")], vec![Tag::from_bytes(b"EFGH")]));
+    }
+
+    #[test]
+    fn common() {
+        assert_eq!(tags(script::COMMON, None), (vec![], vec![]));
+    }
+
+    #[test]
+    fn language_matches_ot() {
+        let language = |s| Language::from_str(s).unwrap();
+
+        assert!(language("ar").matches_ot(Tag::from_bytes(b"ARA ")));
+        assert!(!language("ar").matches_ot(Tag::from_bytes(b"URD ")));
+
+        assert!(language("zh-Hant").matches_ot(Tag::from_bytes(b"ZHT ")));
+        assert!(!language("zh-Hant").matches_ot(Tag::from_bytes(b"ZHH ")));
+        assert!(language("zh-Hant-HK").matches_ot(Tag::from_bytes(b"ZHH ")));
+        assert!(language("zh").matches_ot(Tag::from_bytes(b"ZHS ")));
+    }
+
+    #[test]
+    fn language_to_string() {
+        assert_eq!(Language::from_str("zh-Hant").unwrap().to_string(), "zh-hant");
+        assert_eq!(Language::from_str("ar").unwrap().to_string(), "ar");
+    }
+}
+
+#[cfg(test)]
+mod tests_direction {
+    use super::*;
+
+    #[test]
+    fn classification() {
+        // (direction, horizontal, vertical, forward, backward)
+        let directions = [
+            (Direction::LeftToRight, true,  false, true,  false),
+            (Direction::RightToLeft, true,  false, false, true),
+            (Direction::TopToBottom, false, true,  true,  false),
+            (Direction::BottomToTop, false, true,  false, true),
+            (Direction::Invalid,     false, false, false, false),
+        ];
+
+        for &(direction, horizontal, vertical, forward, backward) in &directions {
+            assert_eq!(direction.is_horizontal(), horizontal, "{:?}", direction);
+            assert_eq!(direction.is_vertical(), vertical, "{:?}", direction);
+            assert_eq!(direction.is_forward(), forward, "{:?}", direction);
+            assert_eq!(direction.is_backward(), backward, "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn reverse() {
+        assert_eq!(Direction::LeftToRight.reverse(), Direction::RightToLeft);
+        assert_eq!(Direction::RightToLeft.reverse(), Direction::LeftToRight);
+        assert_eq!(Direction::TopToBottom.reverse(), Direction::BottomToTop);
+        assert_eq!(Direction::BottomToTop.reverse(), Direction::TopToBottom);
+        assert_eq!(Direction::Invalid.reverse(), Direction::Invalid);
+    }
+}
+
+#[cfg(test)]
+mod tests_script {
+    use super::*;
+
+    #[test]
+    fn from_raw() {
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"Arab").as_u32()), script::ARABIC);
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"Zyyy").as_u32()), script::COMMON);
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"Zinh").as_u32()), script::INHERITED);
+
+        // Unknown, but well-formed scripts are preserved.
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"Qaaa").as_u32()).tag(), Tag::from_bytes(b"Qaaa"));
+
+        // Invalid.
+        assert_eq!(Script::from_raw(0), script::UNKNOWN);
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"arab").as_u32()), script::UNKNOWN);
+        assert_eq!(Script::from_raw(Tag::from_bytes(b"1234").as_u32()), script::UNKNOWN);
+        assert_eq!(Script::from_raw(0xFFFFFFFF), script::UNKNOWN);
+
+        // FFI values are kept as is.
+        assert_eq!(Script::from_hb(0).tag(), Tag(0));
+    }
+
+    #[test]
+    fn from_iso15924_tag() {
+        assert_eq!(Script::from_iso15924_tag(Tag::from_bytes(b"arab")), Some(script::ARABIC));
+        assert_eq!(Script::from_iso15924_tag(Tag::from_bytes(b"Qaai")), Some(script::INHERITED));
+        assert_eq!(Script::from_iso15924_tag(Tag::from_bytes(b"12 4")), Some(script::UNKNOWN));
+        assert_eq!(Script::from_iso15924_tag(Tag(0)), None);
+    }
+}
+
 #[cfg(test)]
 mod tests_features {
     use super::*;
@@ -602,6 +904,12 @@ mod tests_features {
     test!(parse_18, "kern=off",     b"kern", 0, ..);
     test!(parse_19, "kern=oN",      b"kern", 1, ..);
     test!(parse_20, "kern=oFf",     b"kern", 0, ..);
+
+    #[test]
+    fn tag_value() {
+        assert_eq!(Feature::tag_value(Tag::from_bytes(b"kern"), 0), Feature::from_str("-kern").unwrap());
+        assert_eq!(Feature::tag_value(Tag::from_bytes(b"liga"), 1), Feature::from_str("liga").unwrap());
+    }
 }
 
 
@@ -1,14 +1,21 @@
 use std::os::raw::c_void;
 
 use crate::{ffi, script, Tag, Font, GlyphInfo, Mask, Script};
-use crate::buffer::{Buffer, BufferScratchFlags};
+use crate::buffer::{Buffer, BufferContentType, BufferFlags, BufferScratchFlags};
 use crate::ot::*;
-use crate::unicode::{CharExt, GeneralCategory, GeneralCategoryExt, modified_combining_class};
-use super::{hb_flag, hb_flag_unsafe};
+use crate::unicode::{CharExt, GeneralCategory, GeneralCategoryExt, GeneralCategoryGroups, modified_combining_class};
+use super::{hb_flag, hb_flag_unsafe, sort_combining_marks};
 
 const ARABIC_HAS_STCH: BufferScratchFlags = BufferScratchFlags::COMPLEX0;
 
-const ARABIC_FEATURES: &[Tag] = &[
+// Not a registered feature, but used by some fonts to substitute
+// European digits with Arabic-Indic ones.
+//
+// Digits are a part of the `stch` context of subtending marks, like U+06DD ARABIC END OF AYAH.
+// The context is measured after positioning, so a mark spans the substituted digits.
+const ARABIC_NUMERALS: Tag = Tag::from_bytes(b"anum");
+
+pub(crate) const ARABIC_FEATURES: &[Tag] = &[
     feature::ISOLATED_FORMS,
     feature::TERMINAL_FORMS_1,
     feature::TERMINAL_FORMS_2,
@@ -49,7 +56,12 @@ impl Action {
 }
 
 
-const STATE_TABLE: &[[(Action, Action, u16); 6]] = &[
+// Rows are states, columns are joining types, except `T`.
+// Each entry is the action for the previous character, the action for the current one
+// and the next state.
+type StateTable = [[(Action, Action, u16); 6]];
+
+const STATE_TABLE: &StateTable = &[
     // jt_U,          jt_L,          jt_R,
     // jt_D,          jg_ALAPH,      jg_DALATH_RISH
 
@@ -125,8 +137,37 @@ impl GlyphInfo {
             v.var_u8[2] = action as u8;
         }
     }
+
+    pub(crate) fn arabic_positional_form(&self) -> Option<ArabicForm> {
+        let n = unsafe {
+            let v: &ffi::hb_var_int_t = std::mem::transmute(&self.var2);
+            v.var_u8[3]
+        };
+
+        if n & POSITIONAL_FORM_SET == 0 || n & !POSITIONAL_FORM_SET > Action::NONE as u8 {
+            return None;
+        }
+
+        let action: Action = unsafe { std::mem::transmute(n & !POSITIONAL_FORM_SET) };
+        match ArabicForm::from_action(action) {
+            ArabicForm::None => None,
+            form => Some(form),
+        }
+    }
+
+    fn set_arabic_positional_form(&mut self, action: Action) {
+        unsafe {
+            let v: &mut ffi::hb_var_int_t = std::mem::transmute(&mut self.var2);
+            v.var_u8[3] = POSITIONAL_FORM_SET | action as u8;
+        }
+    }
 }
 
+// Marks that the byte after the shaping action holds a positional form.
+// The shaping action byte is reused by stch, so the form is snapshotted right after joining.
+// Other shapers store only small values in this byte, and it's zeroed before shaping.
+const POSITIONAL_FORM_SET: u8 = 0x80;
+
 
 pub struct ArabicShapePlan {
     // The "+ 1" in the next array is to accommodate for the "NONE" command,
@@ -178,6 +219,10 @@ fn collect_features(planner: &mut ShapePlanner) {
 
     planner.ot_map.enable_feature(feature::GLYPH_COMPOSITION_DECOMPOSITION, FeatureFlags::NONE, 1);
     planner.ot_map.enable_feature(feature::LOCALIZED_FORMS, FeatureFlags::NONE, 1);
+    // Off by default, but `+anum` is applied in this stage instead of the last one,
+    // so digits are substituted before joining. Persian and Urdu use different
+    // digits, which fonts select via `locl`, so it's not enabled by language.
+    planner.ot_map.add_feature(ARABIC_NUMERALS, FeatureFlags::NONE, 0);
 
     planner.ot_map.add_gsub_pause(None);
 
@@ -201,6 +246,9 @@ fn collect_features(planner: &mut ShapePlanner) {
 
     // No pause after rclt.
     // See 98460779bae19e4d64d29461ff154b3527bf8420
+    //
+    // User features are added after the shaper ones and override them,
+    // so `-calt` disables calt, while rclt stays on.
     planner.ot_map.enable_feature(feature::REQUIRED_CONTEXTUAL_ALTERNATES, FeatureFlags::MANUAL_ZWJ, 1);
     planner.ot_map.enable_feature(feature::CONTEXTUAL_ALTERNATES, FeatureFlags::MANUAL_ZWJ, 1);
     planner.ot_map.add_gsub_pause(None);
@@ -232,6 +280,9 @@ extern "C" fn fallback_shape_raw(
 // https://docs.microsoft.com/en-us/typography/script-development/syriac
 // We implement this in a generic way, such that the Arabic subtending
 // marks can use it as well.
+// Subtending marks (U+0600..U+0605, U+06DD, U+08E2) precede the digits
+// they span. Digits are part of the stretching context (see `is_word`),
+// so a font can stretch the mark over them via its own `stch` lookup.
 extern "C" fn record_stch_raw(
     plan: *const ffi::hb_ot_shape_plan_t,
     font: *mut ffi::hb_font_t,
@@ -259,12 +310,7 @@ fn record_stch(plan: &ShapePlan, _: &Font, buffer: &mut Buffer) {
     let mut has_stch = false;
     for i in 0..len {
         if info[i].is_multiplied() {
-            let comp = if info[i].lig_comp() % 2 != 0 {
-                Action::StretchingRepeating
-            } else {
-                Action::StretchingFixed
-            };
-
+            let comp = stretching_action(info[i].lig_comp());
             info[i].set_arabic_shaping_action(comp);
             has_stch = true;
         }
@@ -275,6 +321,132 @@ fn record_stch(plan: &ShapePlan, _: &Font, buffer: &mut Buffer) {
     }
 }
 
+// Returns the start of the text the stretched glyphs before `end` must span, and its width.
+//
+// The text is a run of word characters and default ignorables, including marks,
+// and the width is the sum of their advances, which is how far the pen moves over it.
+// Offsets don't move the pen and are ignored. Marks usually have zero advances after
+// positioning, so they don't add any width, but don't end the run either.
+//
+// The same applies to ZWJ and ZWNJ. They affect joining, not stretching.
+// Default ignorables are zero width by now, unless they are preserved and
+// the font gives them an advance, in which case the pen moves over them as well.
+// They never have a stretching action, so they are never copied as tiles.
+//
+// Unlike harfbuzz, punctuation between two digits, like the ARABIC THOUSANDS SEPARATOR
+// in a long ayah number, doesn't end the run either, so a subtending mark
+// spans the whole number.
+fn stch_context(buffer: &Buffer, end: usize) -> (usize, i32) {
+    let mut context = end;
+    let mut width = 0;
+    while context != 0 &&
+        !buffer.info()[context - 1].arabic_shaping_action().is_stch() &&
+        (buffer.info()[context - 1].is_default_ignorable() ||
+            buffer.info()[context - 1].general_category().is_word() ||
+            is_number_separator(buffer, context - 1))
+    {
+        context -= 1;
+        width += buffer.pos()[context].x_advance;
+    }
+
+    (context, width)
+}
+
+fn is_number_separator(buffer: &Buffer, i: usize) -> bool {
+    let infos = buffer.info();
+    i != 0 && i + 1 < buffer.len() &&
+        infos[i].general_category() == GeneralCategory::OtherPunctuation &&
+        infos[i - 1].general_category() == GeneralCategory::DecimalNumber &&
+        infos[i + 1].general_category() == GeneralCategory::DecimalNumber
+}
+
+/// Classifies a single component produced by the `stch` feature.
+///
+/// The `stch` lookup must decompose a glyph into an odd number of components
+/// that alternate between fixed and repeating pieces, starting and ending with
+/// a fixed one: `fixed, repeating, fixed, ..., fixed`.
+/// So the 0-based component index alone is enough to tell them apart.
+/// Fonts that produce a different order are not supported, same as in harfbuzz.
+///
+/// `lig_comp` is stored in 4 bits, so it wraps at 16 for long sequences,
+/// which preserves the parity.
+fn stretching_action(lig_comp: u8) -> Action {
+    if lig_comp % 2 != 0 {
+        Action::StretchingRepeating
+    } else {
+        Action::StretchingFixed
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hb_ot_complex_preprocess_text_arabic(
+    _: *const ffi::hb_ot_shape_plan_t,
+    buffer: *mut ffi::hb_buffer_t,
+    _: *mut ffi::hb_font_t,
+) {
+    let mut buffer = Buffer::from_ptr_mut(buffer);
+    preprocess_text(&mut buffer)
+}
+
+fn preprocess_text(buffer: &mut Buffer) {
+    if !buffer.flags().contains(BufferFlags::DECOMPOSE_PRESENTATION_FORMS) {
+        return;
+    }
+
+    debug_assert_eq!(buffer.content_type(), BufferContentType::Unicode);
+
+    let len = buffer.len();
+    if !buffer.info_slice()[..len].iter().any(|info| is_presentation_form(info.as_char())) {
+        return;
+    }
+
+    buffer.clear_output();
+    buffer.set_idx(0);
+    while buffer.idx() < buffer.len() {
+        let decomposition = presentation_form_decomposition(buffer.cur(0).as_char());
+        let chars = match decomposition {
+            Some(chars) => chars,
+            None => {
+                buffer.next_glyph();
+                continue;
+            }
+        };
+
+        let codepoints: Vec<u32> = chars.iter().map(|c| *c as u32).collect();
+        buffer.replace_glyphs(1, codepoints.len(), &codepoints);
+
+        // Ligatures can decompose into marks.
+        let end = buffer.out_len();
+        for (info, c) in buffer.out_info_mut()[end - chars.len()..end].iter_mut().zip(chars) {
+            info.set_general_category(c.general_category());
+            info.set_modified_combining_class(c.modified_combining_class());
+        }
+    }
+
+    buffer.swap_buffers();
+}
+
+fn is_presentation_form(c: char) -> bool {
+    matches!(c, '\u{FB50}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+/// Returns the compatibility decomposition of an Arabic presentation form.
+///
+/// Isolated forms of spacing marks, like FATHATAN ISOLATED FORM,
+/// are decomposed into a space and a mark, therefore are left as is.
+fn presentation_form_decomposition(c: char) -> Option<&'static [char]> {
+    if !is_presentation_form(c) {
+        return None;
+    }
+
+    let chars = unic_ucd_normal::compatibility_decomposition(c)?;
+    if chars.first() == Some(&' ') {
+        return None;
+    }
+
+    Some(chars)
+}
+
 #[no_mangle]
 pub extern "C" fn hb_ot_complex_postprocess_glyphs_arabic(
     plan: *const ffi::hb_ot_shape_plan_t,
@@ -296,6 +468,8 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
         return;
     }
 
+    debug_assert_eq!(buffer.content_type(), BufferContentType::Glyphs);
+
     // The Arabic shaper currently always processes in RTL mode, so we should
     // stretch / position the stretched pieces to the left / preceding glyphs.
 
@@ -334,7 +508,7 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
             let end = i;
             while i != 0 && buffer.info()[i - 1].arabic_shaping_action().is_stch() {
                 i -= 1;
-                let width = font.glyph_h_advance(buffer.info()[i].codepoint) as i32;
+                let width = font.glyph_h_advance(buffer.info()[i].as_glyph()) as i32;
 
                 if buffer.info()[i].arabic_shaping_action() == Action::StretchingFixed {
                     w_fixed += width;
@@ -345,44 +519,43 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
             }
 
             let start = i;
-            let mut context = i;
-            while context != 0 &&
-                !buffer.info()[context - 1].arabic_shaping_action().is_stch() &&
-                (buffer.info()[context - 1].is_default_ignorable() ||
-                    is_word_category(buffer.info()[context - 1].general_category()))
-            {
-                context -= 1;
-                w_total += buffer.pos()[context].x_advance;
-            }
+            let (context, context_width) = stch_context(buffer, start);
+            w_total += context_width;
 
             i += 1; // Don't touch i again.
 
-            // Number of additional times to repeat each repeating tile.
-            let mut n_copies: i32 = 0;
+            let (n_copies, extra_repeat_overlap) = stretch_fit(w_total - w_fixed, w_repeating, n_repeating);
 
-            let w_remaining = w_total - w_fixed;
-            if w_remaining > w_repeating && w_repeating > 0 {
-                n_copies = w_remaining / (w_repeating) - 1;
-            }
-
-            // See if we can improve the fit by adding an extra repeat and squeezing them together a bit.
-            let mut extra_repeat_overlap = 0;
-            let shortfall = w_remaining - w_repeating * (n_copies + 1);
-            if shortfall > 0 && n_repeating > 0 {
-                n_copies += 1;
-                let excess = (n_copies + 1) * w_repeating - w_remaining;
-                if excess > 0 {
-                    extra_repeat_overlap = excess / (n_copies * n_repeating);
+            if step == MEASURE {
+                if cfg!(debug_assertions) && buffer.messaging() {
+                    let message = format!(
+                        "stch w_total={} w_fixed={} w_repeating={} n_repeating={} n_copies={} extra_repeat_overlap={}",
+                        w_total, w_fixed, w_repeating, n_repeating, n_copies, extra_repeat_overlap,
+                    );
+                    let _ = buffer.message(font, &message);
                 }
-            }
 
-            if step == MEASURE {
                 extra_glyphs_needed += (n_copies * n_repeating) as usize;
             } else {
+                // Only the tiles themselves are moved by default. Marks that were attached to
+                // the stretched glyph during GPOS keep their offsets, like in harfbuzz.
+                // With `MOVE_MARKS_WITH_STCH_TILES`, they follow the tile they are attached to.
+                // Marks of the context glyphs are never touched, since the context is not moved.
+                //
+                // Nastaliq and similar styles stretch diagonally. When tiles have a vertical
+                // advance, they are stepped vertically as well, the same way as horizontally.
+                // The step is added to their own vertical offset and their vertical advance
+                // is dropped, so the pen doesn't move twice and glyphs after the run stay in place.
+                // Otherwise, vertical offsets are left as is, like in harfbuzz.
                 buffer.unsafe_to_break(context, end);
+                let has_vertical_step = buffer.pos()[start..end].iter().any(|pos| pos.y_advance != 0);
+                let start_offset = (buffer.pos()[start].x_offset, buffer.pos()[start].y_offset);
                 let mut x_offset = 0;
+                let mut y_offset = 0;
                 for k in (start+1..=end).rev() {
-                    let width = font.glyph_h_advance(buffer.info()[k - 1].codepoint) as i32;
+                    let width = font.glyph_h_advance(buffer.info()[k - 1].as_glyph()) as i32;
+                    let height = buffer.pos()[k - 1].y_advance;
+                    let base_y_offset = buffer.pos()[k - 1].y_offset;
 
                     let mut repeat = 1;
                     if buffer.info()[k - 1].arabic_shaping_action() == Action::StretchingRepeating {
@@ -391,11 +564,19 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
 
                     for n in 0..repeat {
                         x_offset -= width;
+                        y_offset -= height;
                         if n > 0 {
                             x_offset += extra_repeat_overlap;
+                            if width != 0 {
+                                y_offset += extra_repeat_overlap * height / width;
+                            }
                         }
 
                         buffer.pos_mut()[k - 1].x_offset = x_offset;
+                        if has_vertical_step {
+                            buffer.pos_mut()[k - 1].y_offset = base_y_offset + y_offset;
+                            buffer.pos_mut()[k - 1].y_advance = 0;
+                        }
 
                         // Append copy.
                         j -= 1;
@@ -403,13 +584,19 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
                         buffer.pos_mut()[j] = buffer.pos()[k - 1];
                     }
                 }
+
+                if buffer.flags().contains(BufferFlags::MOVE_MARKS_WITH_STCH_TILES) {
+                    move_stch_marks(buffer, start, start_offset);
+                }
             }
 
             i -= 1;
         }
 
         if step == MEASURE {
-            buffer.ensure(buffer.len() + extra_glyphs_needed);
+            if !buffer.ensure(buffer.len() + extra_glyphs_needed) {
+                break;
+            }
         } else {
             debug_assert_eq!(j, 0);
             buffer.set_len(new_len);
@@ -417,25 +604,48 @@ fn apply_stch(font: &Font, buffer: &mut Buffer) {
     }
 }
 
-// See:
-// https://github.com/harfbuzz/harfbuzz/commit/6e6f82b6f3dde0fc6c3c7d991d9ec6cfff57823d#commitcomment-14248516
-fn is_word_category(gc: GeneralCategory) -> bool {
-    (hb_flag_unsafe(gc.to_hb()) &
-        (   hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_UNASSIGNED) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_PRIVATE_USE) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MODIFIER_LETTER) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_LETTER) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_SPACING_MARK) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_ENCLOSING_MARK) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_NON_SPACING_MARK) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_DECIMAL_NUMBER) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_LETTER_NUMBER) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_NUMBER) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_CURRENCY_SYMBOL) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MODIFIER_SYMBOL) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MATH_SYMBOL) |
-            hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_SYMBOL)
-        )) != 0
+// Moves marks of the stretched glyph by the same amount as its tile at `start`.
+//
+// GPOS attachments are already resolved into offsets by now. But the stretched glyph
+// is right-to-left and the buffer is in visual order, so its marks directly precede
+// its tiles, while marks of the context glyphs precede their own bases.
+// The tile at `start` is the last one in logical order, which marks are attached to.
+// The marks are not copied yet, so they are updated in place.
+fn move_stch_marks(buffer: &mut Buffer, start: usize, (x_offset, y_offset): (i32, i32)) {
+    let dx = buffer.pos()[start].x_offset - x_offset;
+    let dy = buffer.pos()[start].y_offset - y_offset;
+    let mut i = start;
+    while i != 0 && buffer.info()[i - 1].general_category().is_mark() {
+        i -= 1;
+        buffer.pos_mut()[i].x_offset += dx;
+        buffer.pos_mut()[i].y_offset += dy;
+    }
+}
+
+// Returns the number of additional times to repeat each repeating tile
+// and how much the repeated tiles must overlap to fill `w_remaining`.
+//
+// When all tiles are fixed, `w_repeating` and `n_repeating` are zero,
+// so nothing is repeated and neither division is reached.
+fn stretch_fit(w_remaining: i32, w_repeating: i32, n_repeating: i32) -> (i32, i32) {
+    let mut n_copies = 0;
+    if w_remaining > w_repeating && w_repeating > 0 {
+        n_copies = w_remaining / w_repeating - 1;
+    }
+
+    // See if we can improve the fit by adding an extra repeat and squeezing them together a bit.
+    let mut extra_repeat_overlap = 0;
+    let shortfall = w_remaining - w_repeating * (n_copies + 1);
+    if shortfall > 0 && n_repeating > 0 {
+        n_copies += 1;
+        let excess = (n_copies + 1) * w_repeating - w_remaining;
+        if excess > 0 {
+            // `n_copies` is at least 1 here.
+            extra_repeat_overlap = excess / (n_copies * n_repeating);
+        }
+    }
+
+    (n_copies, extra_repeat_overlap)
 }
 
 #[no_mangle]
@@ -458,86 +668,211 @@ pub extern "C" fn hb_ot_complex_setup_masks_arabic_plan(
 ) {
     let arabic_plan = ArabicShapePlan::from_ptr(arabic_plan);
     let mut buffer = Buffer::from_ptr_mut(buffer);
-    setup_masks(arabic_plan, Script::from_raw(script), &mut buffer)
+    setup_masks(arabic_plan, Script::from_hb(script), &mut buffer)
 }
 
 pub(crate) fn setup_masks(plan: &ArabicShapePlan, script: Script, buffer: &mut Buffer) {
-    arabic_joining(buffer);
     if script == script::MONGOLIAN {
+        // Variation selectors take the action of the preceding character
+        // after joining, so masks have to be applied in a separate pass.
+        arabic_joining(buffer, None);
         mongolian_variation_selectors(buffer);
+
+        let len = buffer.len();
+        let info = buffer.info_mut();
+        for i in 0..len {
+            info[i].mask |= plan.mask_array[info[i].arabic_shaping_action() as usize];
+        }
+    } else {
+        arabic_joining(buffer, Some(&plan.mask_array));
     }
 
-    let len = buffer.len();
-    let info = buffer.info_mut();
-    for i in 0..len {
-        info[i].mask |= plan.mask_array[info[i].arabic_shaping_action() as usize];
+    for info in buffer.info_slice_mut() {
+        info.set_arabic_positional_form(info.arabic_shaping_action());
     }
 }
 
-fn arabic_joining(buffer: &mut Buffer) {
-    let mut prev: Option<usize> = None;
-    let mut state = 0;
+fn arabic_joining(buffer: &mut Buffer, mask_array: Option<&[Mask]>) {
+    ARABIC_JOINER.apply(buffer, mask_array)
+}
+
+/// A positional form of a character, selected by cursive joining.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArabicForm {
+    /// Isolated form, `isol`.
+    Isolated,
+    /// Final form, `fina`.
+    Final,
+    /// Syriac ALAPH final form after a non-joining character, `fin2`.
+    Final2,
+    /// Syriac ALAPH final form after DALATH or RISH, `fin3`.
+    Final3,
+    /// Medial form, `medi`.
+    Medial,
+    /// Syriac ALAPH medial form, `med2`.
+    Medial2,
+    /// Initial form, `init`.
+    Initial,
+    /// No positional form. Used for non-joining characters, like spaces,
+    /// and transparent ones, like marks.
+    None,
+}
 
-    // Check pre-context.
-    for i in 0..buffer.context_len(0) {
-        let c = buffer.context(0, i);
-        let this_type = get_joining_type(c, c.general_category());
-        if this_type == JoiningType::T {
-            continue;
+impl ArabicForm {
+    fn from_action(action: Action) -> Self {
+        match action {
+            Action::ISOL => ArabicForm::Isolated,
+            Action::FINA => ArabicForm::Final,
+            Action::FIN2 => ArabicForm::Final2,
+            Action::FIN3 => ArabicForm::Final3,
+            Action::MEDI => ArabicForm::Medial,
+            Action::MED2 => ArabicForm::Medial2,
+            Action::INIT => ArabicForm::Initial,
+            _ => ArabicForm::None,
         }
+    }
+}
 
-        state = STATE_TABLE[state][this_type as usize].2 as usize;
-        break;
+/// Returns the positional form of each character of `text`, in logical order.
+///
+/// Uses the same joining rules as the Arabic shaper, but doesn't need a font,
+/// so it can be used to pick Unicode presentation forms, e.g. in a terminal.
+/// Mongolian free variation selectors are not taken into account,
+/// and `text` is treated as a whole paragraph, without any context.
+pub fn arabic_joining_forms(text: &str) -> Vec<ArabicForm> {
+    let mut buffer = crate::UnicodeBuffer::new();
+    buffer.push_str(text);
+    let mut buffer = buffer.0;
+    for info in buffer.info_slice_mut() {
+        let c = info.as_char();
+        info.set_general_category(c.general_category());
     }
 
-    for i in 0..buffer.len() {
-        let this_type = get_joining_type(
-            buffer.info()[i].as_char(),
-            buffer.info()[i].general_category(),
-        );
-        if this_type == JoiningType::T {
-            buffer.info_mut()[i].set_arabic_shaping_action(Action::NONE);
-            continue;
-        }
+    arabic_joining(&mut buffer, None);
+    buffer.info_slice().iter().map(|info| ArabicForm::from_action(info.arabic_shaping_action())).collect()
+}
 
-        let entry = &STATE_TABLE[state][this_type as usize];
-        if entry.0 != Action::NONE && prev.is_some() {
-            if let Some(prev) = prev {
-                buffer.info_mut()[prev].set_arabic_shaping_action(entry.0);
-                buffer.unsafe_to_break(prev, i + 1);
+/// A cursive joining engine.
+///
+/// Selects positional forms using a state table and a joining type classifier.
+/// Arabic, Syriac, Mongolian and other scripts handled by the Arabic shaper
+/// use `ARABIC_JOINER`. A script with the same joining model, but different
+/// joining types, can reuse the state table with its own classifier.
+pub(crate) struct CursiveJoiner {
+    state_table: &'static StateTable,
+    // Must not return `JoiningType::X`.
+    joining_type: fn(char, GeneralCategory) -> JoiningType,
+}
+
+pub(crate) const ARABIC_JOINER: CursiveJoiner = CursiveJoiner {
+    state_table: STATE_TABLE,
+    joining_type: get_joining_type,
+};
+
+impl CursiveJoiner {
+    // Sets shaping actions and, when `mask_array` is set, the corresponding masks.
+    //
+    // A mask is applied as soon as the action of a character cannot change anymore,
+    // which is when the next non-transparent character was processed.
+    // This way the buffer is traversed only once.
+    fn apply(&self, buffer: &mut Buffer, mask_array: Option<&[Mask]>) {
+        debug_assert_eq!(buffer.content_type(), BufferContentType::Unicode);
+
+        let apply_mask = |buffer: &mut Buffer, i: usize| {
+            if let Some(mask_array) = mask_array {
+                let info = &mut buffer.info_mut()[i];
+                info.mask |= mask_array[info.arabic_shaping_action() as usize];
             }
-        }
+        };
 
-        buffer.info_mut()[i].set_arabic_shaping_action(entry.1);
+        let mut prev: Option<usize> = None;
+        let mut state = 0;
 
-        prev = Some(i);
-        state = entry.2 as usize;
-    }
+        // Check pre-context.
+        for i in 0..buffer.context_len(0) {
+            let c = buffer.context(0, i);
+            let this_type = (self.joining_type)(c, c.general_category());
+            if this_type == JoiningType::T {
+                continue;
+            }
 
-    for i in 0..buffer.context_len(1) {
-        let c = buffer.context(1, i);
-        let this_type = get_joining_type(c, c.general_category());
-        if this_type == JoiningType::T {
-            continue;
+            state = self.state_table[state][this_type as usize].2 as usize;
+            break;
         }
 
-        let entry = &STATE_TABLE[state][this_type as usize];
-        if entry.0 != Action::NONE && prev.is_some() {
+        for i in 0..buffer.len() {
+            let this_type = (self.joining_type)(
+                buffer.info()[i].as_char(),
+                buffer.info()[i].general_category(),
+            );
+            if this_type == JoiningType::T {
+                buffer.info_mut()[i].set_arabic_shaping_action(Action::NONE);
+                apply_mask(buffer, i);
+                continue;
+            }
+
+            let entry = &self.state_table[state][this_type as usize];
+            match prev {
+                Some(prev) if entry.0 != Action::NONE => {
+                    buffer.info_mut()[prev].set_arabic_shaping_action(entry.0);
+                    buffer.safe_to_insert_tatweel(prev, i + 1);
+                }
+                Some(prev) => {
+                    // States that have a possible previous action.
+                    if this_type as u8 >= JoiningType::R as u8 || (2..=5).contains(&state) {
+                        buffer.unsafe_to_concat(prev, i + 1);
+                    }
+                }
+                None => {
+                    if this_type as u8 >= JoiningType::R as u8 {
+                        buffer.unsafe_to_concat(0, i + 1);
+                    }
+                }
+            }
+
             if let Some(prev) = prev {
-                buffer.info_mut()[prev].set_arabic_shaping_action(entry.0);
+                apply_mask(buffer, prev);
             }
+
+            buffer.info_mut()[i].set_arabic_shaping_action(entry.1);
+
+            prev = Some(i);
+            state = entry.2 as usize;
         }
 
-        break;
+        for i in 0..buffer.context_len(1) {
+            let c = buffer.context(1, i);
+            let this_type = (self.joining_type)(c, c.general_category());
+            if this_type == JoiningType::T {
+                continue;
+            }
+
+            let entry = &self.state_table[state][this_type as usize];
+            if entry.0 != Action::NONE && prev.is_some() {
+                if let Some(prev) = prev {
+                    buffer.info_mut()[prev].set_arabic_shaping_action(entry.0);
+                    let len = buffer.len();
+                    buffer.safe_to_insert_tatweel(prev, len);
+                }
+            }
+
+            break;
+        }
+
+        if let Some(prev) = prev {
+            apply_mask(buffer, prev);
+        }
     }
 }
 
 fn mongolian_variation_selectors(buffer: &mut Buffer) {
    // Copy arabic_shaping_action() from base to Mongolian variation selectors.
+    debug_assert_eq!(buffer.content_type(), BufferContentType::Unicode);
+
     let len = buffer.len();
     let info = buffer.info_mut();
     for i in 1..len {
-        if (0x180B..=0x180D).contains(&info[i].codepoint) {
+        if ('\u{180B}'..='\u{180D}').contains(&info[i].as_char()) {
             info[i].set_arabic_shaping_action(info[i - 1].arabic_shaping_action());
         }
     }
@@ -557,6 +892,9 @@ pub fn data_create(plan: &ShapePlan) -> ArabicShapePlan {
         has_stch: false,
     };
 
+    // The mask is zero when the font doesn't have `stch`
+    // or when it was disabled by the user via `-stch`.
+    // In both cases `record_stch` and `apply_stch` are no-ops.
     arabic_plan.has_stch = plan.ot_map.get_1_mask(feature::STRETCHING_GLYPH_DECOMPOSITION) != 0;
     for i in 0..ARABIC_FEATURES.len() {
         arabic_plan.mask_array[i] = plan.ot_map.get_1_mask(ARABIC_FEATURES[i]);
@@ -596,21 +934,26 @@ pub extern "C" fn hb_ot_complex_reorder_marks_arabic(
 }
 
 // http://www.unicode.org/reports/tr53/
-const MODIFIER_COMBINING_MARKS: &[u32] = &[
-    0x0654, // ARABIC HAMZA ABOVE
-    0x0655, // ARABIC HAMZA BELOW
-    0x0658, // ARABIC MARK NOON GHUNNA
-    0x06DC, // ARABIC SMALL HIGH SEEN
-    0x06E3, // ARABIC SMALL LOW SEEN
-    0x06E7, // ARABIC SMALL HIGH YEH
-    0x06E8, // ARABIC SMALL HIGH NOON
-    0x08D3, // ARABIC SMALL LOW WAW
-    0x08F3, // ARABIC SMALL HIGH WAW
+const MODIFIER_COMBINING_MARKS: &[char] = &[
+    '\u{0654}', // ARABIC HAMZA ABOVE
+    '\u{0655}', // ARABIC HAMZA BELOW
+    '\u{0658}', // ARABIC MARK NOON GHUNNA
+    '\u{06DC}', // ARABIC SMALL HIGH SEEN
+    '\u{06E3}', // ARABIC SMALL LOW SEEN
+    '\u{06E7}', // ARABIC SMALL HIGH YEH
+    '\u{06E8}', // ARABIC SMALL HIGH NOON
+    '\u{08D3}', // ARABIC SMALL LOW WAW
+    '\u{08F3}', // ARABIC SMALL HIGH WAW
 ];
 
 fn reorder_marks(mut start: usize, end: usize, buffer: &mut Buffer) {
     const MAX_COMBINING_MARKS: usize = 32;
 
+    debug_assert_eq!(buffer.content_type(), BufferContentType::Unicode);
+
+    // A no-op after normalization, but the code below relies on it.
+    sort_combining_marks(buffer, start, end);
+
     let mut i = start;
     for cc in [220u8, 230].iter().cloned() {
         while i < end && buffer.info()[i].modified_combining_class() < cc {
@@ -628,7 +971,7 @@ fn reorder_marks(mut start: usize, end: usize, buffer: &mut Buffer) {
         let mut j = i;
         while j < end &&
             buffer.info()[j].modified_combining_class() == cc &&
-            MODIFIER_COMBINING_MARKS.contains(&buffer.info()[j].codepoint)
+            MODIFIER_COMBINING_MARKS.contains(&buffer.info()[j].as_char())
         {
             j += 1;
         }
@@ -656,7 +999,8 @@ fn reorder_marks(mut start: usize, end: usize, buffer: &mut Buffer) {
 
         // Renumber CC such that the reordered sequence is still sorted.
         // 22 and 26 are chosen because they are smaller than all Arabic categories,
-        // and are folded back to 220/230 respectively during fallback mark positioning.
+        // and are folded back to 220/230 respectively during fallback mark positioning
+        // (see `unicode::recategorize_combining_class`).
         //
         // We do this because the CGJ-handling logic in the normalizer relies on
         // mark sequences having an increasing order even after this reordering.
@@ -680,3 +1024,541 @@ fn reorder_marks(mut start: usize, end: usize, buffer: &mut Buffer) {
         i = j;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretching_action_alternates() {
+        // fixed, repeating, fixed, repeating, fixed
+        assert_eq!(stretching_action(0), Action::StretchingFixed);
+        assert_eq!(stretching_action(1), Action::StretchingRepeating);
+        assert_eq!(stretching_action(2), Action::StretchingFixed);
+        assert_eq!(stretching_action(3), Action::StretchingRepeating);
+        assert_eq!(stretching_action(4), Action::StretchingFixed);
+    }
+
+    #[test]
+    fn stretching_action_wraps() {
+        // `lig_comp` is 4 bits, so component 16 is stored as 0.
+        assert_eq!(stretching_action(15), Action::StretchingRepeating);
+        assert_eq!(stretching_action(16 & 0x0F), Action::StretchingFixed);
+    }
+
+    const SUBTENDING_MARKS: &[char] = &[
+        '\u{0600}', '\u{0601}', '\u{0602}', '\u{0603}', '\u{0604}', '\u{0605}',
+        '\u{06DD}', '\u{08E2}',
+    ];
+
+    #[test]
+    fn subtending_marks_context() {
+        // Digits that follow a subtending mark must be a part of the stch context.
+        for c in "0123456789\u{0660}\u{0669}\u{06F0}\u{06F9}".chars() {
+            assert!(c.general_category().is_word(), "{:?}", c);
+        }
+
+        for c in SUBTENDING_MARKS.iter().cloned() {
+            // Marks themselves are not a part of the context.
+            assert!(!c.general_category().is_word(), "{:?}", c);
+            assert!(!c.is_default_ignorable(), "{:?}", c);
+            // And do not join with neighbors.
+            assert_eq!(get_joining_type(c, c.general_category()), JoiningType::U, "{:?}", c);
+        }
+    }
+
+    fn marks_buffer(text: &str) -> Buffer {
+        let mut buffer = crate::UnicodeBuffer::new();
+        buffer.push_str(text);
+        let mut buffer = buffer.0;
+        for info in buffer.info_slice_mut() {
+            let c = info.as_char();
+            info.set_general_category(c.general_category());
+            info.set_modified_combining_class(c.modified_combining_class());
+        }
+
+        buffer
+    }
+
+    // A Syriac font with the `stch` feature.
+    const STCH_FONT: &[u8] = include_bytes!("../../tests/fonts/in-house/d9b8bc10985f24796826c29f7ccba3d0ae11ec02.ttf");
+
+    // Returns the `stch` test font. All glyphs are `advance` units wide, when set.
+    fn stch_font(advance: Option<i32>) -> Font<'static> {
+        let mut font = Font::from_slice(STCH_FONT, 0).unwrap();
+        if let Some(advance) = advance {
+            font.set_glyph_h_advance_func(move |_| advance);
+        }
+
+        font
+    }
+
+    // Returns a buffer of `glyphs`, in visual order, ready for `apply_stch`.
+    //
+    // Unicode properties are taken from `text`, which has a character per glyph.
+    fn stch_glyph_buffer(text: &str, glyphs: &[u32], actions: &[Action]) -> Buffer {
+        let mut buffer = marks_buffer(text);
+        assert_eq!(buffer.len(), glyphs.len());
+        assert_eq!(buffer.len(), actions.len());
+        for ((info, &glyph), &action) in buffer.info_slice_mut().iter_mut().zip(glyphs).zip(actions) {
+            info.codepoint = glyph;
+            info.set_arabic_shaping_action(action);
+        }
+
+        unsafe { ffi::hb_buffer_set_content_type(buffer.as_ptr(), ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS) };
+        buffer.set_scratch_flags(buffer.scratch_flags() | ARABIC_HAS_STCH);
+        buffer
+    }
+
+    fn codepoints_and_clusters(buffer: &mut Buffer) -> Vec<(u32, u32)> {
+        buffer.info_slice().iter().map(|info| (info.codepoint, info.cluster)).collect()
+    }
+
+    #[test]
+    fn reorder_hamza_below() {
+        // KASRA, HAMZA BELOW, BEH
+        let mut buffer = marks_buffer("\u{0650}\u{0655}\u{0628}");
+        reorder_marks(0, 2, &mut buffer);
+
+        // HAMZA BELOW is moved first and the marks are merged into a single cluster.
+        // BEH is not affected.
+        assert_eq!(codepoints_and_clusters(&mut buffer), vec![(0x0655, 0), (0x0650, 0), (0x0628, 4)]);
+        assert_eq!(buffer.info()[0].modified_combining_class(), modified_combining_class::CCC22);
+        assert_eq!(buffer.info()[1].modified_combining_class(), 32);
+    }
+
+    #[test]
+    fn reorder_hamza_above() {
+        // BEH, FATHA, HAMZA ABOVE
+        let mut buffer = marks_buffer("\u{0628}\u{064E}\u{0654}");
+        reorder_marks(1, 3, &mut buffer);
+
+        // The merged cluster starts at FATHA, not at BEH.
+        assert_eq!(codepoints_and_clusters(&mut buffer), vec![(0x0628, 0), (0x0654, 2), (0x064E, 2)]);
+        assert_eq!(buffer.info()[1].modified_combining_class(), modified_combining_class::CCC26);
+        assert_eq!(buffer.info()[2].modified_combining_class(), 30);
+    }
+
+    #[test]
+    fn reorder_nothing() {
+        // FATHA, KASRA. No modifier marks.
+        let mut buffer = marks_buffer("\u{064E}\u{0650}");
+        reorder_marks(0, 2, &mut buffer);
+        assert_eq!(codepoints_and_clusters(&mut buffer), vec![(0x064E, 0), (0x0650, 2)]);
+    }
+
+    fn shaping_actions(buffer: &mut Buffer) -> Vec<Action> {
+        buffer.info_slice().iter().map(|info| info.arabic_shaping_action()).collect()
+    }
+
+    #[test]
+    fn joining_empty() {
+        let mut buffer = marks_buffer("");
+        arabic_joining(&mut buffer, None);
+        reorder_marks(0, 0, &mut buffer);
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn joining_whitespace() {
+        let mut buffer = marks_buffer(" \t ");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer), vec![Action::NONE; 3]);
+    }
+
+    #[test]
+    fn joining_single_char() {
+        // BEH
+        let mut buffer = marks_buffer("\u{0628}");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer), vec![Action::ISOL]);
+
+        // A lone mark.
+        let mut buffer = marks_buffer("\u{064E}");
+        arabic_joining(&mut buffer, None);
+        reorder_marks(0, 1, &mut buffer);
+        assert_eq!(shaping_actions(&mut buffer), vec![Action::NONE]);
+    }
+
+    #[test]
+    fn stch_context_width() {
+        // SPACE + TEH + FATHA + TEH + SYRIAC ABBREVIATION MARK, in visual order.
+        let mut buffer = marks_buffer(" \u{062A}\u{064E}\u{062A}\u{070F}");
+        buffer.info_mut()[4].set_arabic_shaping_action(Action::StretchingRepeating);
+        for (i, &(x_advance, x_offset)) in [(200, 0), (502, 0), (0, -300), (502, 0), (68, 0)].iter().enumerate() {
+            buffer.pos_mut()[i].x_advance = x_advance;
+            buffer.pos_mut()[i].x_offset = x_offset;
+        }
+
+        // The mark is part of the context, but has no width. SPACE ends it.
+        assert_eq!(stch_context(&buffer, 4), (1, 1004));
+
+        // Nothing before the first glyph.
+        assert_eq!(stch_context(&buffer, 0), (0, 0));
+    }
+
+    #[test]
+    fn stch_context_joiners() {
+        let make_buffer = |text: &str, advances: &[i32]| {
+            let mut buffer = marks_buffer(text);
+            for (i, &x_advance) in advances.iter().enumerate() {
+                let info = &mut buffer.info_mut()[i];
+                if info.as_char().is_default_ignorable() {
+                    info.set_default_ignorable();
+                }
+
+                assert!(!info.arabic_shaping_action().is_stch());
+                buffer.pos_mut()[i].x_advance = x_advance;
+            }
+
+            let last = advances.len();
+            buffer.info_mut()[last].set_arabic_shaping_action(Action::StretchingRepeating);
+            buffer
+        };
+
+        // SPACE + ARABIC-INDIC ONE + ZWJ + ARABIC-INDIC TWO + ARABIC NUMBER SIGN, in visual order.
+        // ZWJ doesn't end the context and has no width.
+        let buffer = make_buffer(" \u{0661}\u{200D}\u{0662}\u{0600}", &[200, 500, 0, 500]);
+        assert_eq!(stch_context(&buffer, 4), (1, 1000));
+
+        // ZWNJ right before the subtending mark.
+        let buffer = make_buffer(" \u{0661}\u{0662}\u{200C}\u{0600}", &[200, 500, 500, 0]);
+        assert_eq!(stch_context(&buffer, 4), (1, 1000));
+
+        // A preserved ZWJ with a visible glyph is spanned as well.
+        let buffer = make_buffer(" \u{0661}\u{200D}\u{0662}\u{0600}", &[200, 500, 300, 500]);
+        assert_eq!(stch_context(&buffer, 4), (1, 1300));
+    }
+
+    #[test]
+    fn stch_context_number_separators() {
+        let make_buffer = |text: &str| {
+            let mut buffer = marks_buffer(text);
+            let last = buffer.len() - 1;
+            for pos in &mut buffer.pos_mut()[..last] {
+                pos.x_advance = 500;
+            }
+
+            buffer.info_mut()[last].set_arabic_shaping_action(Action::StretchingRepeating);
+            buffer
+        };
+
+        // SPACE + ARABIC-INDIC ONE + ARABIC THOUSANDS SEPARATOR + ARABIC-INDIC TWO
+        // + END OF AYAH, in visual order. The separator is a part of the number.
+        let buffer = make_buffer(" \u{0661}\u{066C}\u{0662}\u{06DD}");
+        assert_eq!(stch_context(&buffer, 4), (1, 1500));
+
+        // A trailing separator is not a part of the number.
+        let buffer = make_buffer("\u{066C}\u{0661}\u{0662}\u{06DD}");
+        assert_eq!(stch_context(&buffer, 3), (1, 1000));
+
+        // Neither is one between a digit and a letter.
+        let buffer = make_buffer("\u{0628}\u{066C}\u{0661}\u{06DD}");
+        assert_eq!(stch_context(&buffer, 3), (2, 500));
+    }
+
+    #[test]
+    fn stch_subtending_ayah_number() {
+        let font = stch_font(Some(100));
+
+        for &mark in &['\u{06DD}', '\u{08E2}'] {
+            // ARABIC-INDIC THREE + TWO + ONE in visual order, followed by a subtending mark
+            // decomposed into fixed, repeating and fixed tiles.
+            let text: String = ['\u{0663}', '\u{0662}', '\u{0661}', mark, mark, mark].iter().collect();
+            let mut buffer = stch_glyph_buffer(&text, &[1, 1, 1, 4, 5, 4], &[
+                Action::NONE, Action::NONE, Action::NONE,
+                Action::StretchingFixed, Action::StretchingRepeating, Action::StretchingFixed,
+            ]);
+            for pos in &mut buffer.pos_mut()[..3] {
+                pos.x_advance = 300;
+            }
+
+            apply_stch(&font, &mut buffer);
+
+            // The repeating tile fills the 700 units between the fixed ones exactly.
+            assert_eq!(buffer.len(), 3 + 1 + 7 + 1);
+            // And the mark spans all three digits.
+            let offsets: Vec<_> = buffer.pos()[3..12].iter().map(|pos| pos.x_offset).collect();
+            assert_eq!(offsets, vec![-900, -800, -700, -600, -500, -400, -300, -200, -100]);
+        }
+    }
+
+    #[test]
+    fn stretch_fit_math() {
+        // Two repeating tiles, 200 units together, over 1000 units.
+        assert_eq!(stretch_fit(1000, 200, 2), (4, 0));
+        // 1050 units need an extra copy of each tile, squeezed by 15 units.
+        assert_eq!(stretch_fit(1050, 200, 2), (5, 15));
+        // Nothing to fill.
+        assert_eq!(stretch_fit(0, 200, 2), (0, 0));
+        assert_eq!(stretch_fit(-100, 200, 2), (0, 0));
+    }
+
+    #[test]
+    fn stretch_fit_fixed_tiles_only() {
+        // No repeating tiles, so nothing is copied and nothing is divided by zero.
+        assert_eq!(stretch_fit(1000, 0, 0), (0, 0));
+        assert_eq!(stretch_fit(0, 0, 0), (0, 0));
+        assert_eq!(stretch_fit(-100, 0, 0), (0, 0));
+    }
+
+    #[test]
+    fn stch_fixed_tiles_only() {
+        let font = stch_font(None);
+
+        // Two letters followed by a glyph decomposed only into fixed tiles, in visual order.
+        let mut buffer = stch_glyph_buffer("\u{0718}\u{0718}\u{070F}\u{070F}", &[1, 2, 3, 4], &[
+            Action::NONE, Action::NONE, Action::StretchingFixed, Action::StretchingFixed,
+        ]);
+        buffer.pos_mut()[0].x_advance = 500;
+        buffer.pos_mut()[1].x_advance = 500;
+
+        apply_stch(&font, &mut buffer);
+
+        // No copies, the tiles are only placed over the context.
+        let glyphs: Vec<_> = buffer.info_slice().iter().map(|info| info.codepoint).collect();
+        assert_eq!(glyphs, vec![1, 2, 3, 4]);
+
+        let w3 = font.glyph_h_advance(3) as i32;
+        let w4 = font.glyph_h_advance(4) as i32;
+        let offsets: Vec<_> = buffer.pos()[..4].iter().map(|pos| pos.x_offset).collect();
+        assert_eq!(offsets, vec![0, 0, -w4 - w3, -w4]);
+    }
+
+    #[test]
+    fn stch_vertical_step() {
+        let font = stch_font(None);
+        let w = |glyph: u32| font.glyph_h_advance(glyph) as i32;
+
+        // A letter followed by a glyph decomposed into fixed, repeating and fixed tiles
+        // and another letter, in visual order. The context fits exactly two copies
+        // of the repeating tile.
+        let make_buffer = |y_advance: i32| {
+            let mut buffer = stch_glyph_buffer("\u{0718}\u{070F}\u{070F}\u{070F}\u{0718}", &[1, 2, 3, 4, 1], &[
+                Action::NONE, Action::StretchingFixed, Action::StretchingRepeating, Action::StretchingFixed,
+                Action::NONE,
+            ]);
+            buffer.pos_mut()[0].x_advance = w(2) + w(3) * 2 + w(4);
+            buffer.pos_mut()[4].x_advance = 100;
+            for pos in &mut buffer.pos_mut()[..5] {
+                pos.y_offset = 7;
+            }
+
+            for pos in &mut buffer.pos_mut()[1..4] {
+                pos.y_advance = y_advance;
+            }
+
+            buffer
+        };
+
+        let positions = |buffer: Buffer| -> Vec<_> {
+            crate::GlyphBuffer(buffer).positioned_glyphs((0, 0)).map(|glyph| (glyph.x, glyph.y)).collect()
+        };
+
+        let mut buffer = make_buffer(10);
+        apply_stch(&font, &mut buffer);
+        let glyphs: Vec<_> = buffer.info_slice().iter().map(|info| info.codepoint).collect();
+        assert_eq!(glyphs, vec![1, 2, 3, 3, 4, 1]);
+
+        // Tiles are stepped down by 10 units each, starting from their own offset,
+        // and the letter after the run stays on the baseline.
+        assert_eq!(positions(buffer), vec![
+            (0, 7),
+            (0, 7 - 40),
+            (w(2), 7 - 30),
+            (w(2) + w(3), 7 - 20),
+            (w(2) + w(3) * 2, 7 - 10),
+            (w(2) + w(3) * 2 + w(4), 7),
+        ]);
+
+        // Without vertical advances, vertical offsets are not touched.
+        let mut buffer = make_buffer(0);
+        apply_stch(&font, &mut buffer);
+        assert_eq!(buffer.len(), 6);
+        assert!(positions(buffer).iter().all(|&(_, y)| y == 7));
+    }
+
+    #[test]
+    fn stch_h_advance_func() {
+        let font = stch_font(Some(100));
+
+        // A letter followed by a glyph decomposed into fixed, repeating and fixed tiles,
+        // in visual order.
+        let mut buffer = stch_glyph_buffer("\u{0718}\u{070F}\u{070F}\u{070F}", &[1, 2, 3, 4], &[
+            Action::NONE, Action::StretchingFixed, Action::StretchingRepeating, Action::StretchingFixed,
+        ]);
+        buffer.pos_mut()[0].x_advance = 450;
+
+        apply_stch(&font, &mut buffer);
+
+        // 250 units are left for the repeating tile, which is 100 units wide,
+        // so it's copied twice and the copies overlap by 25 units.
+        let glyphs: Vec<_> = buffer.info_slice().iter().map(|info| info.codepoint).collect();
+        assert_eq!(glyphs, vec![1, 2, 3, 3, 3, 4]);
+        let offsets: Vec<_> = buffer.pos()[..6].iter().map(|pos| pos.x_offset).collect();
+        assert_eq!(offsets, vec![0, -450, -350, -275, -200, -100]);
+    }
+
+    #[test]
+    fn stch_vowelized_marks() {
+        let font = stch_font(Some(100));
+
+        // RBASA BELOW + WAW + PTHAHA ABOVE + BETH + PTHAHA ABOVE + LAMADH, in visual order.
+        // LAMADH is decomposed by `stch` into fixed, repeating and fixed tiles,
+        // and each vowel was positioned on its base during GPOS. The vowel of LAMADH
+        // is attached to its last tile in logical order, 50 units left of it.
+        let make_buffer = |flags: BufferFlags| {
+            let text = "\u{0737}\u{0718}\u{0730}\u{0712}\u{0730}\u{0720}\u{0720}\u{0720}";
+            let mut buffer = stch_glyph_buffer(text, &[1, 3, 1, 3, 1, 4, 5, 4], &[
+                Action::NONE, Action::NONE, Action::NONE, Action::NONE, Action::NONE,
+                Action::StretchingFixed, Action::StretchingRepeating, Action::StretchingFixed,
+            ]);
+            for (i, &(x_advance, x_offset, y_offset)) in [
+                (0, -200, -300), (300, 0, 0), (0, -100, 400), (150, 0, 0), (0, -50, 600),
+            ].iter().enumerate() {
+                buffer.pos_mut()[i].x_advance = x_advance;
+                buffer.pos_mut()[i].x_offset = x_offset;
+                buffer.pos_mut()[i].y_offset = y_offset;
+            }
+
+            buffer.set_flags(flags);
+            buffer
+        };
+
+        let positions = |buffer: Buffer| -> Vec<_> {
+            crate::GlyphBuffer(buffer).positioned_glyphs((0, 0)).map(|glyph| (glyph.x, glyph.y)).collect()
+        };
+
+        // 250 units are left for the repeating tile, so it's copied twice,
+        // the same way as without marks. Vowels of the context letters stay on their bases.
+        let mut buffer = make_buffer(BufferFlags::MOVE_MARKS_WITH_STCH_TILES);
+        apply_stch(&font, &mut buffer);
+        assert_eq!(buffer.len(), 10);
+        let positioned = positions(buffer);
+        assert_eq!(positioned, vec![
+            (-200, -300), (0, 0), (200, 400), (300, 0),
+            // The vowel of LAMADH moves with its tile.
+            (-50, 600),
+            (0, 0), (100, 0), (175, 0), (250, 0), (350, 0),
+        ]);
+        assert_eq!(positioned[4].0 - positioned[5].0, -50);
+
+        // By default, it stays where GPOS placed it, like in harfbuzz.
+        let mut buffer = make_buffer(BufferFlags::empty());
+        apply_stch(&font, &mut buffer);
+        assert_eq!(buffer.len(), 10);
+        let positioned = positions(buffer);
+        assert_eq!(positioned[4], (400, 600));
+        assert_eq!(positioned[5], (0, 0));
+    }
+
+    #[test]
+    fn joining_tatweel() {
+        assert_eq!(super::super::arabic_table::joining_type('\u{0640}'), JoiningType::D);
+
+        // KAF + TATWEEL + TATWEEL + TEH + BEH
+        let mut buffer = marks_buffer("\u{0643}\u{0640}\u{0640}\u{062A}\u{0628}");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer),
+                   vec![Action::INIT, Action::MEDI, Action::MEDI, Action::MEDI, Action::FINA]);
+
+        // BEH + TATWEEL + FATHA + ALEF. A mark on TATWEEL doesn't break joining.
+        let mut buffer = marks_buffer("\u{0628}\u{0640}\u{064E}\u{0627}");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer),
+                   vec![Action::INIT, Action::MEDI, Action::NONE, Action::FINA]);
+
+        // ALEF + TATWEEL. ALEF doesn't join to the left, so TATWEEL is isolated.
+        let mut buffer = marks_buffer("\u{0627}\u{0640}");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer), vec![Action::ISOL, Action::ISOL]);
+    }
+
+    #[test]
+    fn mongolian_variation_selectors_copy_action() {
+        // UE + FVS1 + UE + FVS2. A variation selector takes the form of its base,
+        // so it's covered by the same positional feature.
+        let mut buffer = marks_buffer("\u{1826}\u{180B}\u{1826}\u{180C}");
+        arabic_joining(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer),
+                   vec![Action::INIT, Action::NONE, Action::FINA, Action::NONE]);
+
+        mongolian_variation_selectors(&mut buffer);
+        assert_eq!(shaping_actions(&mut buffer),
+                   vec![Action::INIT, Action::INIT, Action::FINA, Action::FINA]);
+    }
+
+    #[test]
+    fn joining_masks() {
+        let mut mask_array = [0; ARABIC_FEATURES.len() + 1];
+        for (i, mask) in mask_array.iter_mut().enumerate().skip(1) {
+            *mask = 1 << i;
+        }
+
+        // BEH + FATHA + BEH + SPACE + BEH + BEH, followed by BEH in the post-context.
+        let make_buffer = || {
+            let mut buffer = crate::UnicodeBuffer::new();
+            buffer.push_str("\u{0628}\u{064E}\u{0628} \u{0628}\u{0628}");
+            buffer.set_post_context("\u{0628}");
+            let mut buffer = buffer.0;
+            for info in buffer.info_slice_mut() {
+                let c = info.as_char();
+                info.set_general_category(c.general_category());
+            }
+            buffer
+        };
+
+        let mut buffer = make_buffer();
+        arabic_joining(&mut buffer, None);
+        let expected: Vec<_> = buffer.info_slice().iter()
+            .map(|info| mask_array[info.arabic_shaping_action() as usize])
+            .collect();
+
+        let mut buffer = make_buffer();
+        arabic_joining(&mut buffer, Some(&mask_array));
+        let masks: Vec<_> = buffer.info_slice().iter().map(|info| info.mask).collect();
+        assert_eq!(masks, expected);
+    }
+
+    #[test]
+    fn custom_joiner() {
+        // A made up cursive Latin, which reuses the Arabic joining model.
+        fn joining_type(c: char, _: GeneralCategory) -> JoiningType {
+            match c {
+                'l' => JoiningType::L,
+                'r' => JoiningType::R,
+                '\'' => JoiningType::T,
+                'a'..='z' => JoiningType::D,
+                _ => JoiningType::U,
+            }
+        }
+
+        let joiner = CursiveJoiner {
+            state_table: STATE_TABLE,
+            joining_type,
+        };
+
+        let mut buffer = marks_buffer("lr ab'c");
+        joiner.apply(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer), vec![
+            Action::INIT, Action::FINA, Action::NONE,
+            Action::INIT, Action::MEDI, Action::NONE, Action::FINA,
+        ]);
+
+        // The same text is not cursive for the Arabic joiner.
+        let mut buffer = marks_buffer("lr ab'c");
+        ARABIC_JOINER.apply(&mut buffer, None);
+        assert_eq!(shaping_actions(&mut buffer), vec![Action::NONE; 7]);
+    }
+
+    #[test]
+    fn presentation_forms() {
+        // ALEF FINAL FORM
+        assert_eq!(presentation_form_decomposition('\u{FE8E}'), Some(&['\u{0627}'][..]));
+        // LAM WITH ALEF ISOLATED FORM
+        assert_eq!(presentation_form_decomposition('\u{FEFB}'), Some(&['\u{0644}', '\u{0627}'][..]));
+        // FATHATAN ISOLATED FORM
+        assert_eq!(presentation_form_decomposition('\u{FE70}'), None);
+        assert_eq!(presentation_form_decomposition('\u{0627}'), None);
+    }
+}
@@ -1,7 +1,7 @@
 use std::os::raw::c_void;
 
 use crate::{ffi, Font, GlyphInfo, Mask};
-use crate::buffer::{Buffer, BufferFlags, BufferClusterLevel};
+use crate::buffer::{Buffer, BufferContentType, BufferFlags, BufferClusterLevel};
 use crate::ot::*;
 
 
@@ -158,6 +158,8 @@ fn preprocess_text(_: &ShapePlan, font: &Font, buffer: &mut Buffer) {
     // - <LV,T> we should compose if the whole thing can be composed, otherwise we should
     //   decompose.
 
+    debug_assert_eq!(buffer.content_type(), BufferContentType::Unicode);
+
     buffer.clear_output();
     // Extent of most recently seen syllable; valid only if start < end
     let mut start = 0;
@@ -4,7 +4,7 @@ use std::os::raw::c_void;
 
 use crate::{ffi, script, Tag, Script, Mask, Font, GlyphInfo};
 use crate::buffer::{Buffer, BufferFlags};
-use crate::unicode::{CharExt, GeneralCategoryExt};
+use crate::unicode::{CharExt, GeneralCategoryExt, GeneralCategoryGroups};
 use crate::ot::*;
 use super::{hb_flag, hb_flag_unsafe, hb_flag_range};
 
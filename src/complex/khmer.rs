@@ -3,7 +3,7 @@ use std::os::raw::c_void;
 
 use crate::{ffi, Tag, Mask, Font, GlyphInfo};
 use crate::buffer::{Buffer, BufferFlags};
-use crate::unicode::{CharExt, GeneralCategoryExt};
+use crate::unicode::{CharExt, GeneralCategoryGroups};
 use crate::ot::*;
 use super::indic::{Category, Position};
 
@@ -15,6 +15,12 @@ mod universal_machine;
 mod universal_table;
 mod vowel_constraints;
 
+pub(crate) use arabic::ARABIC_FEATURES;
+pub use arabic::{arabic_joining_forms, ArabicForm};
+
+use crate::{ffi, GlyphInfo};
+use crate::buffer::Buffer;
+
 
 #[inline]
 pub const fn hb_flag(x: u32) -> u32 {
@@ -40,3 +46,75 @@ pub const fn hb_flag64(x: u32) -> u64 {
 pub fn hb_flag64_unsafe(x: u32) -> u64 {
     if x < 64 { 1 << (x as u64) } else { 0 }
 }
+
+/// Sorts marks in `start..end` by the modified combining class.
+///
+/// The sort is stable, so marks with the same class keep their order.
+/// Moved marks are merged into a single cluster.
+///
+/// Same as the normalizer's sort, which runs before `reorder_marks`.
+pub(crate) fn sort_combining_marks(buffer: &mut Buffer, start: usize, end: usize) {
+    buffer.sort(start, end, compare_combining_class);
+}
+
+unsafe extern "C" fn compare_combining_class(
+    pa: *const ffi::hb_glyph_info_t,
+    pb: *const ffi::hb_glyph_info_t,
+) -> i32 {
+    let a = std::mem::transmute::<ffi::hb_glyph_info_t, GlyphInfo>(*pa).modified_combining_class();
+    let b = std::mem::transmute::<ffi::hb_glyph_info_t, GlyphInfo>(*pb).modified_combining_class();
+    a.cmp(&b) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unicode::CharExt;
+
+    fn marks_buffer(text: &str) -> Buffer {
+        let mut buffer = crate::UnicodeBuffer::new();
+        buffer.push_str(text);
+        let mut buffer = buffer.0;
+        for info in buffer.info_slice_mut() {
+            let c = info.as_char();
+            info.set_modified_combining_class(c.modified_combining_class());
+        }
+
+        buffer
+    }
+
+    fn codepoints_and_clusters(buffer: &mut Buffer) -> Vec<(u32, u32)> {
+        buffer.info_slice().iter().map(|info| (info.codepoint, info.cluster)).collect()
+    }
+
+    #[test]
+    fn sort_out_of_order_marks() {
+        // a, DOT ABOVE (230), DOT BELOW (220), CEDILLA (202)
+        let mut buffer = marks_buffer("a\u{0307}\u{0323}\u{0327}");
+        sort_combining_marks(&mut buffer, 1, 4);
+        assert_eq!(
+            codepoints_and_clusters(&mut buffer),
+            vec![(0x61, 0), (0x0327, 1), (0x0323, 1), (0x0307, 1)]
+        );
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        // GRAVE (230), DOT BELOW (220), ACUTE (230), CIRCUMFLEX (230)
+        let mut buffer = marks_buffer("\u{0300}\u{0323}\u{0301}\u{0302}");
+        sort_combining_marks(&mut buffer, 0, 4);
+        let codepoints: Vec<_> = buffer.info_slice().iter().map(|info| info.codepoint).collect();
+        assert_eq!(codepoints, vec![0x0323, 0x0300, 0x0301, 0x0302]);
+    }
+
+    #[test]
+    fn sort_sorted_marks() {
+        // DOT BELOW (220), DOT ABOVE (230). Already in order, so clusters are kept.
+        let mut buffer = marks_buffer("\u{0323}\u{0307}");
+        sort_combining_marks(&mut buffer, 0, 2);
+        assert_eq!(codepoints_and_clusters(&mut buffer), vec![(0x0323, 0), (0x0307, 2)]);
+
+        sort_combining_marks(&mut buffer, 0, 0);
+        assert_eq!(buffer.len(), 2);
+    }
+}
@@ -3,7 +3,7 @@ use std::os::raw::c_void;
 
 use crate::{ffi, script, Tag, Font, GlyphInfo, Mask, Script};
 use crate::buffer::{Buffer, BufferFlags};
-use crate::unicode::{CharExt, GeneralCategoryExt};
+use crate::unicode::{CharExt, GeneralCategoryGroups};
 use crate::ot::*;
 use super::{hb_flag, hb_flag_unsafe, hb_flag64, hb_flag64_unsafe};
 use super::arabic::ArabicShapePlan;
@@ -0,0 +1,160 @@
+use std::ops::Range;
+
+use crate::{Direction, Feature, Font, GlyphInfo, GlyphPosition, Language, Script, UnicodeBuffer};
+
+/// A list of fonts tried in order, when a font doesn't have a glyph for a character.
+///
+/// See `shape_with_fallback`.
+#[derive(Default)]
+pub struct FontFallbackList<'a, 'b> {
+    fonts: Vec<&'a Font<'b>>,
+}
+
+impl<'a, 'b> FontFallbackList<'a, 'b> {
+    /// Creates a new, empty list.
+    pub fn new() -> Self {
+        FontFallbackList::default()
+    }
+
+    /// Appends a font to the end of the list.
+    pub fn push(&mut self, font: &'a Font<'b>) {
+        self.fonts.push(font);
+    }
+
+    /// Returns the number of fonts in the list.
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    /// Checks that the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+}
+
+
+/// A glyph produced by `shape_with_fallback`.
+#[derive(Clone, Copy, Debug)]
+pub struct FallbackGlyph {
+    /// A glyph info.
+    ///
+    /// The cluster is a byte offset into the shaped text.
+    pub info: GlyphInfo,
+    /// A glyph position.
+    pub position: GlyphPosition,
+    /// An index of the font in the `FontFallbackList` the glyph is from.
+    pub font_index: usize,
+}
+
+
+/// Shapes text using a list of fonts.
+///
+/// The text is shaped with the first font. Clusters that contain `.notdef` glyphs
+/// are collected into runs, which are then reshaped with the next font, and so on.
+/// Glyphs missing from all fonts are `.notdef` glyphs of the last font.
+///
+/// Text around a reshaped run is used as a context, therefore Arabic joining is preserved
+/// across font boundaries. Glyphs from different fonts are never ligated or kerned together.
+///
+/// Segment properties are guessed once, from the whole text.
+///
+/// Glyphs are returned in visual order.
+/// Glyph clusters are byte offsets into `text`.
+pub fn shape_with_fallback(
+    fonts: &FontFallbackList,
+    features: &[Feature],
+    text: &str,
+) -> Vec<FallbackGlyph> {
+    let mut glyphs = Vec::new();
+    if fonts.is_empty() || text.is_empty() {
+        return glyphs;
+    }
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+
+    let shaper = FallbackShaper {
+        fonts: &fonts.fonts,
+        features,
+        text,
+        direction: buffer.direction(),
+        script: buffer.script(),
+        language: buffer.language(),
+    };
+
+    shaper.shape(0, 0..text.len(), &mut glyphs);
+    glyphs
+}
+
+
+struct FallbackShaper<'a, 'b> {
+    fonts: &'a [&'a Font<'b>],
+    features: &'a [Feature],
+    text: &'a str,
+    direction: Direction,
+    script: Script,
+    language: Option<Language>,
+}
+
+impl FallbackShaper<'_, '_> {
+    fn shape(&self, font_index: usize, range: Range<usize>, glyphs: &mut Vec<FallbackGlyph>) {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str_with_context(self.text, range.clone());
+        buffer.set_direction(self.direction);
+        buffer.set_script(self.script);
+        if let Some(ref language) = self.language {
+            buffer.set_language(language.clone());
+        }
+
+        let shaped = crate::shape(self.fonts[font_index], self.features, buffer);
+        let infos = shaped.glyph_infos();
+        let positions = shaped.glyph_positions();
+        let has_fallback = font_index + 1 < self.fonts.len();
+
+        // Sorted cluster starts, used to find where a cluster ends in the text.
+        let mut clusters: Vec<_> = infos.iter().map(|info| info.cluster as usize).collect();
+        clusters.sort_unstable();
+        clusters.dedup();
+
+        // A text range of consecutive clusters with missing glyphs.
+        let mut missing: Option<Range<usize>> = None;
+
+        let mut start = 0;
+        while start < infos.len() {
+            let cluster = infos[start].cluster;
+            let mut end = start + 1;
+            while end < infos.len() && infos[end].cluster == cluster {
+                end += 1;
+            }
+
+            if has_fallback && infos[start..end].iter().any(|info| info.codepoint == 0) {
+                let text_start = cluster as usize;
+                let text_end = clusters.iter().cloned()
+                    .find(|&c| c > text_start)
+                    .unwrap_or(range.end);
+
+                missing = Some(match missing {
+                    Some(r) => r.start.min(text_start)..r.end.max(text_end),
+                    None => text_start..text_end,
+                });
+            } else {
+                if let Some(r) = missing.take() {
+                    self.shape(font_index + 1, r, glyphs);
+                }
+
+                glyphs.extend((start..end).map(|i| FallbackGlyph {
+                    info: infos[i],
+                    position: positions[i],
+                    font_index,
+                }));
+            }
+
+            start = end;
+        }
+
+        if let Some(r) = missing {
+            self.shape(font_index + 1, r, glyphs);
+        }
+    }
+}
@@ -30,6 +30,11 @@ pub const HB_BUFFER_CLUSTER_LEVEL_MONOTONE_CHARACTERS: hb_buffer_cluster_level_t
 pub const HB_BUFFER_CLUSTER_LEVEL_CHARACTERS: hb_buffer_cluster_level_t = 2;
 pub type hb_buffer_cluster_level_t = u32;
 
+pub const HB_BUFFER_CONTENT_TYPE_INVALID: hb_buffer_content_type_t = 0;
+pub const HB_BUFFER_CONTENT_TYPE_UNICODE: hb_buffer_content_type_t = 1;
+pub const HB_BUFFER_CONTENT_TYPE_GLYPHS: hb_buffer_content_type_t = 2;
+pub type hb_buffer_content_type_t = u32;
+
 pub const HB_MEMORY_MODE_READONLY: hb_memory_mode_t = 1;
 pub type hb_memory_mode_t = u32;
 
@@ -123,6 +128,16 @@ pub struct hb_ot_map_lookup_map_t {
 
 pub type hb_language_t = *const c_char;
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct hb_segment_properties_t {
+    pub direction: hb_direction_t,
+    pub script: hb_script_t,
+    pub language: hb_language_t,
+    pub reserved1: *mut c_void,
+    pub reserved2: *mut c_void,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct hb_buffer_t {
@@ -158,6 +173,10 @@ pub struct hb_ot_map_t { _unused: [u8; 0] }
 #[derive(Clone, Copy)]
 pub struct hb_ot_map_builder_t { _unused: [u8; 0] }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct hb_shape_plan_t { _unused: [u8; 0] }
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct hb_ot_shape_plan_t { _unused: [u8; 0] }
@@ -170,6 +189,15 @@ pub struct hb_ot_shape_planner_t { _unused: [u8; 0] }
 #[derive(Clone, Copy)]
 pub struct hb_ot_shape_normalize_context_t { _unused: [u8; 0] }
 
+pub type hb_buffer_message_func_t = Option<
+    unsafe extern "C" fn(
+        buffer: *mut hb_buffer_t,
+        font: *mut hb_font_t,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ) -> hb_bool_t,
+>;
+
 pub type hb_ot_pause_func_t = Option<
     unsafe extern "C" fn(
         plan: *const hb_ot_shape_plan_t,
@@ -195,6 +223,8 @@ extern "C" {
 
     pub fn hb_blob_destroy(blob: *mut hb_blob_t);
 
+    pub fn hb_blob_get_data(blob: *mut hb_blob_t, length: *mut u32) -> *const c_char;
+
     pub fn hb_buffer_create() -> *mut hb_buffer_t;
 
     pub fn hb_buffer_destroy(buffer: *mut hb_buffer_t);
@@ -220,6 +250,10 @@ extern "C" {
 
     pub fn hb_buffer_get_cluster_level(buffer: *mut hb_buffer_t) -> hb_buffer_cluster_level_t;
 
+    pub fn hb_buffer_set_content_type(buffer: *mut hb_buffer_t, content_type: hb_buffer_content_type_t);
+
+    pub fn hb_buffer_get_content_type(buffer: *mut hb_buffer_t) -> hb_buffer_content_type_t;
+
     pub fn hb_buffer_reset_clusters(buffer: *mut hb_buffer_t);
 
     pub fn hb_buffer_next_glyph(buffer: *mut hb_buffer_t);
@@ -245,6 +279,10 @@ extern "C" {
 
     pub fn hb_buffer_unsafe_to_break_from_outbuffer(buffer: *mut hb_buffer_t, start: u32, end: u32);
 
+    pub fn hb_buffer_unsafe_to_concat(buffer: *mut hb_buffer_t, start: u32, end: u32);
+
+    pub fn hb_buffer_safe_to_insert_tatweel(buffer: *mut hb_buffer_t, start: u32, end: u32);
+
     pub fn hb_buffer_swap_buffers(buffer: *mut hb_buffer_t);
 
     pub fn hb_buffer_sort(buffer: *mut hb_buffer_t, start: u32, end: u32, p: hb_sort_funct_t);
@@ -253,6 +291,8 @@ extern "C" {
 
     pub fn hb_buffer_clear_output(buffer: *mut hb_buffer_t);
 
+    pub fn hb_buffer_add(buffer: *mut hb_buffer_t, codepoint: hb_codepoint_t, cluster: u32);
+
     pub fn hb_buffer_add_utf8(
         buffer: *mut hb_buffer_t,
         text: *const c_char,
@@ -267,6 +307,8 @@ extern "C" {
 
     pub fn hb_buffer_get_length(buffer: *mut hb_buffer_t) -> u32;
 
+    pub fn hb_buffer_set_length(buffer: *mut hb_buffer_t, len: u32) -> hb_bool_t;
+
     pub fn hb_buffer_set_length_force(buffer: *mut hb_buffer_t, len: u32);
 
     pub fn hb_buffer_get_out_length(buffer: *mut hb_buffer_t) -> u32;
@@ -279,7 +321,13 @@ extern "C" {
 
     pub fn hb_buffer_context(buffer: *mut hb_buffer_t, context_index: u32, index: u32) -> hb_codepoint_t;
 
+    pub fn hb_buffer_allocation_successful(buffer: *mut hb_buffer_t) -> hb_bool_t;
+
     pub fn hb_buffer_get_flags(buffer: *mut hb_buffer_t) -> u32;
+    pub fn hb_buffer_set_flags(buffer: *mut hb_buffer_t, flags: u32);
+
+    pub fn hb_buffer_get_not_found_glyph(buffer: *mut hb_buffer_t) -> hb_codepoint_t;
+    pub fn hb_buffer_set_not_found_glyph(buffer: *mut hb_buffer_t, not_found: hb_codepoint_t);
 
     pub fn hb_buffer_get_scratch_flags(buffer: *mut hb_buffer_t) -> u32;
     pub fn hb_buffer_set_scratch_flags(buffer: *mut hb_buffer_t, flags: u32);
@@ -306,6 +354,8 @@ extern "C" {
 
     pub fn hb_face_destroy(face: *mut hb_face_t);
 
+    pub fn hb_face_reference_table(face: *const hb_face_t, tag: Tag) -> *mut hb_blob_t;
+
     pub fn hb_ot_map_get_1_mask(map: *const hb_ot_map_t, tag: Tag) -> hb_mask_t;
 
     pub fn hb_ot_map_global_mask(map: *const hb_ot_map_t) -> hb_mask_t;
@@ -324,6 +374,24 @@ extern "C" {
         lookup_count: *mut u32,
     );
 
+    pub fn hb_ot_map_get_feature_count(map: *const hb_ot_map_t) -> u32;
+
+    pub fn hb_ot_map_get_feature(
+        map: *const hb_ot_map_t,
+        table_index: u32,
+        index: u32,
+        tag: *mut Tag,
+        stage: *mut u32,
+    ) -> bool;
+
+    pub fn hb_ot_map_get_stage_count(map: *const hb_ot_map_t, table_index: u32) -> u32;
+
+    pub fn hb_ot_map_stage_has_pause(map: *const hb_ot_map_t, table_index: u32, stage: u32) -> bool;
+
+    pub fn hb_ot_map_needs_fallback(map: *const hb_ot_map_t, tag: Tag) -> bool;
+
+    pub fn hb_ot_map_disable_lookup(map: *mut hb_ot_map_t, table_index: u32, lookup_index: u32) -> bool;
+
     pub fn hb_ot_shape_plan_get_ot_map(plan: *const hb_ot_shape_plan_t) -> *const hb_ot_map_t;
 
     pub fn hb_ot_shape_plan_get_data(plan: *mut hb_ot_shape_plan_t) -> *const c_void;
@@ -332,6 +400,8 @@ extern "C" {
 
     pub fn hb_ot_shape_plan_has_gpos_mark(plan: *mut hb_ot_shape_plan_t) -> bool;
 
+    pub fn hb_ot_shape_plan_fallback_mark_positioning(plan: *mut hb_ot_shape_plan_t) -> bool;
+
     pub fn hb_ot_shape_planner_get_ot_map(
         planner: *mut hb_ot_shape_planner_t,
     ) -> *mut hb_ot_map_builder_t;
@@ -360,6 +430,42 @@ extern "C" {
         ctx: *const hb_ot_shape_normalize_context_t,
     ) -> *const hb_font_t;
 
+    pub fn hb_ot_layout_table_select_script(
+        face: *mut hb_face_t,
+        table_tag: Tag,
+        script_count: u32,
+        script_tags: *const Tag,
+        script_index: *mut u32,
+        chosen_script: *mut Tag,
+    ) -> hb_bool_t;
+
+    pub fn hb_ot_layout_script_select_language(
+        face: *mut hb_face_t,
+        table_tag: Tag,
+        script_index: u32,
+        language_count: u32,
+        language_tags: *const Tag,
+        language_index: *mut u32,
+    ) -> hb_bool_t;
+
+    pub fn hb_ot_layout_language_get_required_feature(
+        face: *mut hb_face_t,
+        table_tag: Tag,
+        script_index: u32,
+        language_index: u32,
+        feature_index: *mut u32,
+        feature_tag: *mut Tag,
+    ) -> hb_bool_t;
+
+    pub fn hb_ot_layout_language_find_feature(
+        face: *mut hb_face_t,
+        table_tag: Tag,
+        script_index: u32,
+        language_index: u32,
+        feature_tag: Tag,
+        feature_index: *mut u32,
+    ) -> hb_bool_t;
+
     pub fn hb_ot_layout_lookup_would_substitute(
         face: *mut hb_face_t,
         lookup_index: u32,
@@ -380,6 +486,65 @@ extern "C" {
         buffer: *mut hb_buffer_t,
     );
 
+    pub fn hb_buffer_set_message_func(
+        buffer: *mut hb_buffer_t,
+        func: hb_buffer_message_func_t,
+        user_data: *mut c_void,
+        destroy: hb_destroy_func_t,
+    );
+
+    pub fn hb_buffer_messaging(buffer: *mut hb_buffer_t) -> hb_bool_t;
+
+    pub fn hb_buffer_message(buffer: *mut hb_buffer_t, font: *mut hb_font_t, message: *const c_char) -> hb_bool_t;
+
+    pub fn hb_buffer_get_segment_properties(buffer: *mut hb_buffer_t, props: *mut hb_segment_properties_t);
+
+    pub fn hb_shape_plan_create(
+        face: *mut hb_face_t,
+        props: *const hb_segment_properties_t,
+        user_features: *const crate::Feature,
+        num_user_features: u32,
+        coords: *const i32,
+        num_coords: u32,
+    ) -> *mut hb_shape_plan_t;
+
+    pub fn hb_shape_plan_destroy(plan: *mut hb_shape_plan_t);
+
+    pub fn hb_ot_tags_from_script_and_language(
+        script: hb_script_t,
+        language: hb_language_t,
+        script_count: *mut u32,
+        script_tags: *mut Tag,
+        language_count: *mut u32,
+        language_tags: *mut Tag,
+    );
+
+    pub fn hb_font_get_glyph_h_origin(
+        font: *const hb_font_t,
+        glyph: hb_codepoint_t,
+        x: *mut hb_position_t,
+        y: *mut hb_position_t,
+    ) -> hb_bool_t;
+
+    pub fn hb_font_get_glyph_v_origin(
+        font: *const hb_font_t,
+        glyph: hb_codepoint_t,
+        x: *mut hb_position_t,
+        y: *mut hb_position_t,
+    ) -> hb_bool_t;
+
+    pub fn hb_shape_plan_execute(
+        plan: *mut hb_shape_plan_t,
+        font: *const hb_font_t,
+        buffer: *mut hb_buffer_t,
+        features: *const crate::Feature,
+        num_features: u32,
+    ) -> hb_bool_t;
+
+    pub fn hb_shape_plan_get_ot_shape_plan(plan: *const hb_shape_plan_t) -> *const hb_ot_shape_plan_t;
+
+    pub fn hb_shape_plan_get_ot_map_mut(plan: *mut hb_shape_plan_t) -> *mut hb_ot_map_t;
+
     pub fn hb_shape(
         font: *const hb_font_t,
         buffer: *mut hb_buffer_t,
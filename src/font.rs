@@ -2,11 +2,15 @@ use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::os::raw::c_char;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use ttf_parser::{Tag, GlyphId};
+use ttf_parser::{Tag, GlyphId, OutlineBuilder};
 
-use crate::common::Variation;
+use crate::colr::PaintSink;
+use crate::common::{Language, Script, Variation};
+use crate::fvar::{AxisInfo, NamedInstance};
 use crate::ffi;
+use crate::stream::Stream;
 
 
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#windows-platform-platform-id--3
@@ -14,6 +18,8 @@ const WINDOWS_SYMBOL_ENCODING: u16 = 0;
 const WINDOWS_UNICODE_BMP_ENCODING: u16 = 1;
 const WINDOWS_UNICODE_FULL_ENCODING: u16 = 10;
 
+static NEXT_FONT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
 // https://docs.microsoft.com/en-us/typography/opentype/spec/name#platform-specific-encoding-and-language-ids-unicode-platform-platform-id--0
 const UNICODE_1_0_ENCODING: u16 = 0;
 const UNICODE_1_1_ENCODING: u16 = 1;
@@ -95,6 +101,8 @@ pub struct Font<'a> {
     points_per_em: Option<f32>,
     coords: Vec<i32>,
     prefered_cmap_encoding_subtable: Option<u16>,
+    h_advance_func: Option<Box<dyn Fn(u32) -> i32>>,
+    generation: usize,
 }
 
 impl<'a> Font<'a> {
@@ -114,6 +122,8 @@ impl<'a> Font<'a> {
             points_per_em: None,
             coords: Vec::new(),
             prefered_cmap_encoding_subtable,
+            h_advance_func: None,
+            generation: NEXT_FONT_GENERATION.fetch_add(1, Ordering::Relaxed),
         })
     }
 
@@ -129,6 +139,20 @@ impl<'a> Font<'a> {
         self.hb_face.as_ptr()
     }
 
+    pub(crate) fn coords(&self) -> &[i32] {
+        &self.coords
+    }
+
+    pub(crate) fn units_per_em(&self) -> i32 {
+        self.units_per_em
+    }
+
+    // Unique across all fonts and changed when glyph advances are overridden,
+    // so caches can tell fonts apart even when a dropped font's memory is reused.
+    pub(crate) fn generation(&self) -> usize {
+        self.generation
+    }
+
     /// Sets pixels per EM.
     ///
     /// Used during raster glyphs processing and hinting.
@@ -147,6 +171,18 @@ impl<'a> Font<'a> {
         self.points_per_em = ptem;
     }
 
+    /// Overrides horizontal glyph advances.
+    ///
+    /// The callback is called with a glyph ID and returns its advance in font units.
+    /// It's used everywhere the font's `hmtx` advance would be used: for initial glyph positions,
+    /// `stch` stretching and fallback positioning. GPOS adjustments are still applied on top.
+    pub fn set_glyph_h_advance_func<F>(&mut self, func: F)
+        where F: Fn(u32) -> i32 + 'static
+    {
+        self.h_advance_func = Some(Box::new(func));
+        self.generation = NEXT_FONT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Sets font variations.
     pub fn set_variations(&mut self, variations: &[Variation]) {
         for variation in variations {
@@ -159,6 +195,43 @@ impl<'a> Font<'a> {
         }
     }
 
+    /// Returns variation axes from the `fvar` table.
+    ///
+    /// Axis names are resolved via the `name` table.
+    /// Returns an empty list for non-variable fonts.
+    pub fn variation_axes(&self) -> Vec<AxisInfo> {
+        match self.table_data(Tag::from_bytes(b"fvar")) {
+            Some(fvar) => crate::fvar::variation_axes(fvar, self.table_data(Tag::from_bytes(b"name"))),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns named instances from the `fvar` table.
+    ///
+    /// Instance names are resolved via the `name` table.
+    /// Returns an empty list for non-variable fonts.
+    pub fn named_instances(&self) -> Vec<NamedInstance> {
+        match self.table_data(Tag::from_bytes(b"fvar")) {
+            Some(fvar) => crate::fvar::named_instances(fvar, self.table_data(Tag::from_bytes(b"name"))),
+            None => Vec::new(),
+        }
+    }
+
+    /// Sets font variations to the coordinates of a named instance.
+    ///
+    /// Same as calling `set_variations` with `NamedInstance::coordinates`.
+    ///
+    /// Returns `false` and keeps the current variations when `index` is out of bounds.
+    pub fn set_named_instance(&mut self, index: usize) -> bool {
+        match self.named_instances().into_iter().nth(index) {
+            Some(instance) => {
+                self.set_variations(&instance.coordinates);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub(crate) fn glyph_index(&self, c: u32) -> Option<GlyphId> {
         let subtable_idx = self.prefered_cmap_encoding_subtable?;
         let subtable = self.ttfp_face.character_mapping_subtables().nth(subtable_idx as usize)?;
@@ -185,6 +258,26 @@ impl<'a> Font<'a> {
         }
     }
 
+    /// Maps characters to nominal glyphs using the `cmap` table.
+    ///
+    /// Glyphs are written into `out`. Stops at the first character without a glyph
+    /// or when either slice ends.
+    ///
+    /// Returns the number of mapped characters.
+    pub fn nominal_glyphs(&self, chars: &[char], out: &mut [u32]) -> usize {
+        let mut done = 0;
+        for (c, glyph) in chars.iter().zip(out.iter_mut()) {
+            match self.glyph_index(*c as u32) {
+                Some(id) => *glyph = u32::from(id.0),
+                None => break,
+            }
+
+            done += 1;
+        }
+
+        done
+    }
+
     pub(crate) fn glyph_variation_index(&self, c: char, variation: char) -> Option<GlyphId> {
         let res = self.ttfp_face.character_mapping_subtables()
             .find(|e| e.format() == ttf_parser::cmap::Format::UnicodeVariationSequences)
@@ -233,6 +326,292 @@ impl<'a> Font<'a> {
         let glyph_id = GlyphId(u16::try_from(glyph).unwrap());
         self.ttfp_face.glyph_name(glyph_id)
     }
+
+    /// Returns a glyph ID by the glyph name from the `post` table.
+    ///
+    /// Glyphs are searched one by one, so this is meant for tests and debugging.
+    pub fn glyph_from_name(&self, name: &str) -> Option<u32> {
+        (0..self.ttfp_face.number_of_glyphs())
+            .find(|&id| self.ttfp_face.glyph_name(GlyphId(id)) == Some(name))
+            .map(u32::from)
+    }
+
+    /// Returns a glyph mark attachment class from the `GDEF` table.
+    ///
+    /// Returns 0 when the glyph has no class or the table is missing.
+    pub fn glyph_mark_attachment_class(&self, glyph: u32) -> u16 {
+        match u16::try_from(glyph) {
+            Ok(id) => self.ttfp_face.glyph_mark_attachment_class(GlyphId(id)).0,
+            Err(_) => 0,
+        }
+    }
+
+    /// Checks that a glyph is a part of the `GDEF` mark glyph set at `set_index`.
+    pub fn is_mark_glyph(&self, glyph: u32, set_index: u16) -> bool {
+        match u16::try_from(glyph) {
+            Ok(id) => self.ttfp_face.is_mark_glyph(GlyphId(id), Some(set_index)),
+            Err(_) => false,
+        }
+    }
+
+    /// Outlines a glyph and passes the outline segments to `builder`.
+    ///
+    /// Coordinates are in font units and take the current variation instance into account.
+    ///
+    /// Returns `false` when the glyph has no outline, like a space.
+    pub fn outline_glyph(&self, glyph: u32, builder: &mut dyn OutlineBuilder) -> bool {
+        match u16::try_from(glyph) {
+            Ok(id) => self.ttfp_face.outline_glyph(GlyphId(id), builder).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks that the font has a `GSUB` or `GPOS` feature for a script and language.
+    ///
+    /// The script and language systems are selected the same way as during shaping,
+    /// including the fallback to the `DFLT` script and the default language system.
+    /// A required feature of the selected language system is taken into account too.
+    ///
+    /// Only checks the font. A feature can still be skipped during shaping,
+    /// when the script shaper doesn't use it.
+    pub fn has_feature(&self, tag: Tag, script: Script, language: Option<&Language>) -> bool {
+        let (script_tags, language_tags) = crate::common::ot_tags_from_script_and_language(script, language);
+        let face = self.face_ptr();
+
+        [Tag::from_bytes(b"GSUB"), Tag::from_bytes(b"GPOS")].iter().any(|&table| unsafe {
+            let mut script_index = 0;
+            let mut chosen_script = Tag(0);
+            ffi::hb_ot_layout_table_select_script(
+                face,
+                table,
+                script_tags.len() as u32,
+                script_tags.as_ptr(),
+                &mut script_index,
+                &mut chosen_script,
+            );
+
+            let mut language_index = 0;
+            ffi::hb_ot_layout_script_select_language(
+                face,
+                table,
+                script_index,
+                language_tags.len() as u32,
+                language_tags.as_ptr(),
+                &mut language_index,
+            );
+
+            let mut feature_index = 0;
+            let mut required_tag = Tag(0);
+            let has_required = ffi::hb_ot_layout_language_get_required_feature(
+                face,
+                table,
+                script_index,
+                language_index,
+                &mut feature_index,
+                &mut required_tag,
+            ) != 0;
+
+            (has_required && required_tag == tag) || ffi::hb_ot_layout_language_find_feature(
+                face,
+                table,
+                script_index,
+                language_index,
+                tag,
+                &mut feature_index,
+            ) != 0
+        })
+    }
+
+    /// Paints a color glyph from the `COLR` table.
+    ///
+    /// Both `COLR` versions are supported. Version 0 layers are passed as
+    /// glyph clips filled with solid colors. Version 1 paint graphs are passed as is,
+    /// except that all transforms are converted into `Transform`s.
+    ///
+    /// Colors are taken from the first `CPAL` palette.
+    /// Variable paints use their default values. Sweep gradients and clip boxes
+    /// are not supported yet and are ignored. Cycles in a paint graph are skipped.
+    ///
+    /// Returns `false` when the glyph is not a color glyph.
+    pub fn paint_glyph(&self, glyph: u32, sink: &mut dyn PaintSink) -> bool {
+        let glyph = match u16::try_from(glyph) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let colr = match self.table_data(Tag::from_bytes(b"COLR")) {
+            Some(data) => data,
+            None => return false,
+        };
+
+        let cpal = self.table_data(Tag::from_bytes(b"CPAL"));
+        crate::colr::paint_glyph(colr, cpal, glyph, sink)
+    }
+
+    fn table_data(&self, tag: Tag) -> Option<&[u8]> {
+        unsafe {
+            let blob = ffi::hb_face_reference_table(self.face_ptr(), tag);
+            let mut len = 0;
+            let data = ffi::hb_blob_get_data(blob, &mut len);
+            // The table blob references the face blob, so the data outlives it.
+            ffi::hb_blob_destroy(blob);
+            if data.is_null() || len == 0 {
+                None
+            } else {
+                Some(std::slice::from_raw_parts(data as *const u8, len as usize))
+            }
+        }
+    }
+
+    /// Returns a glyph origin for horizontal text, relative to the glyph coordinate system origin.
+    ///
+    /// OpenType fonts don't have horizontal origins, so this is always `(0, 0)`.
+    pub fn glyph_h_origin(&self, glyph: u32) -> (i32, i32) {
+        let mut x = 0;
+        let mut y = 0;
+        unsafe {
+            if ffi::hb_font_get_glyph_h_origin(self.as_ptr(), glyph, &mut x, &mut y) == 0 {
+                return (0, 0);
+            }
+        }
+
+        (x, y)
+    }
+
+    /// Returns a glyph origin for vertical text, relative to the glyph coordinate system origin.
+    ///
+    /// The x coordinate is a half of the horizontal advance.
+    /// The y coordinate is taken from the `VORG` table, when present,
+    /// otherwise it's the glyph top plus its top side bearing from `vmtx`.
+    /// Vertical shaping subtracts this origin from glyph offsets.
+    ///
+    /// Values are in font units. Returns `(0, 0)` when the origin cannot be computed.
+    pub fn glyph_v_origin(&self, glyph: u32) -> (i32, i32) {
+        if u16::try_from(glyph).is_err() {
+            return (0, 0);
+        }
+
+        let mut x = 0;
+        let mut y = 0;
+        unsafe {
+            if ffi::hb_font_get_glyph_v_origin(self.as_ptr(), glyph, &mut x, &mut y) == 0 {
+                return (0, 0);
+            }
+        }
+
+        (x, y)
+    }
+
+    /// Returns font extents for horizontal text.
+    ///
+    /// Values are in font units and take the current variation instance into account via `MVAR`.
+    pub fn h_extents(&self) -> FontExtents {
+        self.extents(
+            metrics::HORIZONTAL_ASCENDER,
+            metrics::HORIZONTAL_DESCENDER,
+            metrics::HORIZONTAL_LINE_GAP,
+        ).unwrap_or_else(|| {
+            // Same as `hb_font_get_h_extents` fallback.
+            let ascender = (self.units_per_em as f32 * 0.8) as i32;
+            FontExtents {
+                ascender,
+                descender: ascender - self.units_per_em,
+                line_gap: 0,
+            }
+        })
+    }
+
+    /// Returns the recommended distance between baselines of horizontal lines.
+    ///
+    /// Same as `ascender - descender + line_gap` of `h_extents`,
+    /// so the `OS/2` typographic metrics are used when the font sets `USE_TYPO_METRICS`.
+    ///
+    /// The value is in font units. Multiply it by the font size
+    /// and divide by units per em to get the line height at that size.
+    pub fn recommended_line_height(&self) -> i32 {
+        let extents = self.h_extents();
+        extents.ascender - extents.descender + extents.line_gap
+    }
+
+    /// Returns font extents for vertical text.
+    ///
+    /// Values are in font units and take the current variation instance into account via `MVAR`.
+    pub fn v_extents(&self) -> FontExtents {
+        self.extents(
+            metrics::VERTICAL_ASCENDER,
+            metrics::VERTICAL_DESCENDER,
+            metrics::VERTICAL_LINE_GAP,
+        ).unwrap_or_else(|| {
+            // Same as `hb_font_get_v_extents` fallback.
+            let ascender = self.units_per_em / 2;
+            FontExtents {
+                ascender,
+                descender: -ascender,
+                line_gap: 0,
+            }
+        })
+    }
+
+    fn extents(&self, ascender: Tag, descender: Tag, line_gap: Tag) -> Option<FontExtents> {
+        Some(FontExtents {
+            ascender: self.metrics_position(ascender)?,
+            descender: self.metrics_position(descender)?,
+            line_gap: self.metrics_position(line_gap)?,
+        })
+    }
+
+    // Same as harfbuzz's `_hb_ot_metrics_get_position_common`.
+    //
+    // Tables are read directly, so `MVAR` deltas are applied exactly once.
+    fn metrics_position(&self, tag: Tag) -> Option<i32> {
+        const USE_TYPO_METRICS: u16 = 1 << 7;
+
+        let (table, index) = match tag {
+            metrics::HORIZONTAL_ASCENDER => (b"hhea", 0),
+            metrics::HORIZONTAL_DESCENDER => (b"hhea", 1),
+            metrics::HORIZONTAL_LINE_GAP => (b"hhea", 2),
+            metrics::VERTICAL_ASCENDER => (b"vhea", 0),
+            metrics::VERTICAL_DESCENDER => (b"vhea", 1),
+            metrics::VERTICAL_LINE_GAP => (b"vhea", 2),
+            _ => return None,
+        };
+
+        // `sTypoAscender`, `sTypoDescender` and `sTypoLineGap` replace the `hhea` ones.
+        let os2 = self.table_data(Tag::from_bytes(b"OS/2")).map(Stream);
+        let typo = os2.filter(|os2| os2.u16(62).map_or(false, |flags| flags & USE_TYPO_METRICS != 0));
+        let value = match typo {
+            Some(os2) if table == b"hhea" => os2.i16(68 + index * 2)?,
+            _ => Stream(self.table_data(Tag::from_bytes(table))?).i16(4 + index * 2)?,
+        };
+
+        let mut value = f32::from(value);
+        if !self.coords.is_empty() {
+            if let Some(mvar) = self.table_data(Tag::from_bytes(b"MVAR")) {
+                value += crate::mvar::metrics_delta(mvar, tag, &self.coords).unwrap_or(0.0);
+            }
+        }
+
+        let pos = value.round() as i32;
+        Some(if tag == metrics::HORIZONTAL_DESCENDER || tag == metrics::VERTICAL_DESCENDER {
+            -pos.abs()
+        } else {
+            pos
+        })
+    }
+}
+
+
+/// Font-wide extents.
+///
+/// All values are in font units.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FontExtents {
+    /// Typographic ascender.
+    pub ascender: i32,
+    /// Typographic descender. Usually negative.
+    pub descender: i32,
+    /// Suggested line spacing gap.
+    pub line_gap: i32,
 }
 
 fn find_best_cmap_subtable(face: &ttf_parser::Face) -> Option<u16> {
@@ -325,7 +704,14 @@ pub extern "C" fn hb_font_get_advance(
     glyph: ffi::hb_codepoint_t,
     is_vertical: ffi::hb_bool_t,
 ) -> u32 {
-    let face = &Font::from_ptr(font).ttfp_face;
+    let font = Font::from_ptr(font);
+    if is_vertical == 0 {
+        if let Some(ref func) = font.h_advance_func {
+            return func(glyph) as u32;
+        }
+    }
+
+    let face = &font.ttfp_face;
     let glyph = GlyphId(u16::try_from(glyph).unwrap());
 
     if  face.is_variable() &&
@@ -397,31 +783,13 @@ pub extern "C" fn hb_ot_metrics_get_position_common(
     tag: Tag,
     position: *mut i32,
 ) -> ffi::hb_bool_t {
-    let face = &Font::from_ptr(font).ttfp_face;
-    let pos = match tag {
-        metrics::HORIZONTAL_ASCENDER => {
-            i32::from(face.ascender())
-        }
-        metrics::HORIZONTAL_DESCENDER => {
-            -i32::from(face.descender()).abs()
-        }
-        metrics::HORIZONTAL_LINE_GAP => {
-            i32::from(face.line_gap())
-        }
-        metrics::VERTICAL_ASCENDER if face.has_table(ttf_parser::TableName::VerticalHeader) => {
-            i32::from(face.vertical_ascender().unwrap_or(0))
-        }
-        metrics::VERTICAL_DESCENDER if face.has_table(ttf_parser::TableName::VerticalHeader) => {
-            -i32::from(face.vertical_descender().unwrap_or(0)).abs()
-        }
-        metrics::VERTICAL_LINE_GAP if face.has_table(ttf_parser::TableName::VerticalHeader) => {
-            i32::from(face.vertical_line_gap().unwrap_or(0))
+    match Font::from_ptr(font).metrics_position(tag) {
+        Some(pos) => {
+            unsafe { *position = pos; }
+            1
         }
-        _ => return 0,
-    };
-
-    unsafe { *position = pos; }
-    1
+        None => 0,
+    }
 }
 
 #[no_mangle]
@@ -557,4 +925,98 @@ mod tests {
             assert_eq!(hb_ot_metrics_get_position_common(font.as_ptr(), metrics::VERTICAL_LINE_GAP, pos), 0);
         }
     }
+
+    #[test]
+    fn font_extents() {
+        let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestGVAROne.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        assert_eq!(font.h_extents(), FontExtents { ascender: 967, descender: -253, line_gap: 0 });
+        assert_eq!(font.v_extents(), FontExtents { ascender: 500, descender: -500, line_gap: 0 });
+    }
+
+    #[test]
+    fn font_extents_without_vertical_metrics() {
+        let font_data = std::fs::read("tests/fonts/in-house/1a3d8f381387dd29be1e897e4b5100ac8b4829e1.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        assert_eq!(font.h_extents(), FontExtents { ascender: 800, descender: -200, line_gap: 90 });
+
+        let half_upem = font.units_per_em / 2;
+        assert_eq!(font.v_extents(), FontExtents { ascender: half_upem, descender: -half_upem, line_gap: 0 });
+    }
+
+    #[test]
+    fn font_extents_variable() {
+        // Selawik with `MVAR` deltas of 100, -50 and 20 at the maximum weight.
+        let font_data = std::fs::read("tests/fonts/in-house/3c508ddad0479f6b2eb8d600c14a543a9c501b6e.ttf").unwrap();
+        let mut font = Font::from_slice(&font_data, 0).unwrap();
+        let default = FontExtents { ascender: 1012, descender: -216, line_gap: 0 };
+        assert_eq!(font.h_extents(), default);
+
+        let wght = |value| [Variation { tag: Tag::from_bytes(b"wght"), value }];
+        font.set_variations(&wght(700.0));
+        assert_eq!(font.h_extents(), FontExtents { ascender: 1112, descender: -266, line_gap: 20 });
+
+        // The variation region starts at the default weight.
+        font.set_variations(&wght(300.0));
+        assert_eq!(font.h_extents(), default);
+    }
+
+    #[test]
+    fn recommended_line_height() {
+        let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestGVAROne.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        assert_eq!(font.recommended_line_height(), 967 + 253);
+
+        // A font with OS/2.useTypographicMetrics flag set.
+        let font_data = std::fs::read("tests/fonts/in-house/1a3d8f381387dd29be1e897e4b5100ac8b4829e1.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        assert_eq!(font.recommended_line_height(), 800 + 200 + 90);
+    }
+
+    #[test]
+    fn mark_attachment() {
+        let font_data = std::fs::read("tests/fonts/in-house/e68a88939e0f06e34d2bc911f09b70890289c8fd.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+
+        assert_eq!(font.glyph_mark_attachment_class(5), 1);
+        assert_eq!(font.glyph_mark_attachment_class(6), 0);
+        assert_eq!(font.glyph_mark_attachment_class(1), 0);
+
+        assert!(font.is_mark_glyph(5, 0));
+        assert!(font.is_mark_glyph(7, 0));
+        assert!(!font.is_mark_glyph(9, 0)); // a mark, but not in the set
+        assert!(!font.is_mark_glyph(1, 0));
+        assert!(!font.is_mark_glyph(5, 1)); // no such set
+    }
+
+    #[test]
+    fn outline_glyph() {
+        struct ContoursCounter(usize);
+
+        impl OutlineBuilder for ContoursCounter {
+            fn move_to(&mut self, _: f32, _: f32) {}
+            fn line_to(&mut self, _: f32, _: f32) {}
+            fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+            fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+            fn close(&mut self) { self.0 += 1; }
+        }
+
+        let font_data = std::fs::read("tests/fonts/text-rendering-tests/NotoSansBalinese-Regular.ttf").unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+
+        // BALINESE LETTER PA
+        let mut counter = ContoursCounter(0);
+        assert!(font.outline_glyph(43, &mut counter));
+        assert_eq!(counter.0, 1);
+
+        // BALINESE LETTER KA
+        let mut counter = ContoursCounter(0);
+        assert!(font.outline_glyph(23, &mut counter));
+        assert_eq!(counter.0, 3);
+
+        // Space has no outline.
+        let mut counter = ContoursCounter(0);
+        assert!(!font.outline_glyph(3, &mut counter));
+        assert_eq!(counter.0, 0);
+    }
 }
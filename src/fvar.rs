@@ -0,0 +1,183 @@
+use ttf_parser::Tag;
+
+use crate::stream::Stream;
+use crate::common::Variation;
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/name#platform-ids
+const UNICODE_PLATFORM: u16 = 0;
+const WINDOWS_PLATFORM: u16 = 3;
+const WINDOWS_UNICODE_BMP_ENCODING: u16 = 1;
+const WINDOWS_UNICODE_FULL_ENCODING: u16 = 10;
+const WINDOWS_ENGLISH_US_LANGUAGE: u16 = 0x0409;
+
+/// A variation axis from the `fvar` table.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AxisInfo {
+    /// An axis tag, like `wght`.
+    pub tag: Tag,
+    /// The minimum axis value.
+    pub min: f32,
+    /// The default axis value.
+    pub default: f32,
+    /// The maximum axis value.
+    pub max: f32,
+    /// An axis name ID in the `name` table.
+    pub name_id: u16,
+    /// An axis name from the `name` table.
+    ///
+    /// `None` when the font has no Unicode name with `name_id`.
+    pub name: Option<String>,
+}
+
+pub(crate) fn variation_axes(fvar: &[u8], name: Option<&[u8]>) -> Vec<AxisInfo> {
+    let fvar = Stream(fvar);
+    let mut axes = Vec::new();
+    let (offset, count, size) = match (fvar.u16(4), fvar.u16(8), fvar.u16(10)) {
+        (Some(offset), Some(count), Some(size)) => (usize::from(offset), count, usize::from(size)),
+        _ => return axes,
+    };
+
+    for i in 0..usize::from(count) {
+        match axis(fvar, offset + i * size, name) {
+            Some(axis) => axes.push(axis),
+            None => break,
+        }
+    }
+
+    axes
+}
+
+fn axis(fvar: Stream, record: usize, name: Option<&[u8]>) -> Option<AxisInfo> {
+    let name_id = fvar.u16(record + 18)?;
+    Some(AxisInfo {
+        tag: Tag(fvar.u32(record)?),
+        min: fvar.fixed(record + 4)?,
+        default: fvar.fixed(record + 8)?,
+        max: fvar.fixed(record + 12)?,
+        name_id,
+        name: name.and_then(|name| name_string(name, name_id)),
+    })
+}
+
+/// A named instance from the `fvar` table.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NamedInstance {
+    /// A subfamily name ID in the `name` table.
+    pub subfamily_name_id: u16,
+    /// A subfamily name from the `name` table, like `Bold`.
+    ///
+    /// `None` when the font has no Unicode name with `subfamily_name_id`.
+    pub name: Option<String>,
+    /// A PostScript name ID in the `name` table, when present.
+    pub postscript_name_id: Option<u16>,
+    /// Instance coordinates, one per axis, in the `variation_axes` order.
+    pub coordinates: Vec<Variation>,
+}
+
+pub(crate) fn named_instances(fvar: &[u8], name: Option<&[u8]>) -> Vec<NamedInstance> {
+    let fvar = Stream(fvar);
+    let mut instances = Vec::new();
+    let header = (fvar.u16(4), fvar.u16(8), fvar.u16(10), fvar.u16(12), fvar.u16(14));
+    let (offset, axis_count, axis_size, count, size) = match header {
+        (Some(offset), Some(axis_count), Some(axis_size), Some(count), Some(size)) => (
+            usize::from(offset),
+            usize::from(axis_count),
+            usize::from(axis_size),
+            count,
+            usize::from(size),
+        ),
+        _ => return instances,
+    };
+
+    let axes = variation_axes(fvar.0, None);
+    if axes.len() != axis_count || size < 4 + axis_count * 4 {
+        return instances;
+    }
+
+    // Instances follow axes.
+    let offset = offset + axis_count * axis_size;
+    for i in 0..usize::from(count) {
+        match instance(fvar, offset + i * size, size, &axes, name) {
+            Some(instance) => instances.push(instance),
+            None => break,
+        }
+    }
+
+    instances
+}
+
+fn instance(
+    fvar: Stream,
+    record: usize,
+    size: usize,
+    axes: &[AxisInfo],
+    name: Option<&[u8]>,
+) -> Option<NamedInstance> {
+    let subfamily_name_id = fvar.u16(record)?;
+
+    let mut coordinates = Vec::with_capacity(axes.len());
+    for (i, axis) in axes.iter().enumerate() {
+        coordinates.push(Variation {
+            tag: axis.tag,
+            value: fvar.fixed(record + 4 + i * 4)?,
+        });
+    }
+
+    // The PostScript name ID is optional and is detected by the record size.
+    let postscript_offset = 4 + axes.len() * 4;
+    let postscript_name_id = if size >= postscript_offset + 2 {
+        match fvar.u16(record + postscript_offset)? {
+            0xFFFF => None,
+            id => Some(id),
+        }
+    } else {
+        None
+    };
+
+    Some(NamedInstance {
+        subfamily_name_id,
+        name: name.and_then(|name| name_string(name, subfamily_name_id)),
+        postscript_name_id,
+        coordinates,
+    })
+}
+
+// Only UTF-16 names are supported. US English is preferred.
+fn name_string(name: &[u8], name_id: u16) -> Option<String> {
+    let name = Stream(name);
+    let count = usize::from(name.u16(2)?);
+    let storage = usize::from(name.u16(4)?);
+
+    let mut best: Option<(u8, usize, usize)> = None;
+    for i in 0..count {
+        let record = 6 + i * 12;
+        if name.u16(record + 6)? != name_id {
+            continue;
+        }
+
+        let platform = name.u16(record)?;
+        let encoding = name.u16(record + 2)?;
+        let language = name.u16(record + 4)?;
+        let rank = match platform {
+            WINDOWS_PLATFORM
+                if encoding == WINDOWS_UNICODE_BMP_ENCODING
+                || encoding == WINDOWS_UNICODE_FULL_ENCODING =>
+            {
+                if language == WINDOWS_ENGLISH_US_LANGUAGE { 0 } else { 1 }
+            }
+            UNICODE_PLATFORM => 2,
+            _ => continue,
+        };
+
+        if best.map_or(true, |(best_rank, ..)| rank < best_rank) {
+            let len = usize::from(name.u16(record + 8)?);
+            let offset = storage + usize::from(name.u16(record + 10)?);
+            best = Some((rank, offset, len));
+        }
+    }
+
+    let (_, offset, len) = best?;
+    let data = name.0.get(offset..offset.checked_add(len)?)?;
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+    String::from_utf16(&units).ok()
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+
+use crate::{script, Script};
+use crate::unicode::{self, CharExt, GeneralCategory};
+
+/// Splits text into same-script runs.
+///
+/// `Common` and `Inherited` characters are resolved according to
+/// [UAX #24](https://www.unicode.org/reports/tr24/): they are merged into the surrounding run,
+/// and a closing bracket gets the script of its opening pair.
+/// Leading `Common` characters, like digits, belong to the first resolved script.
+///
+/// Text that consists only of `Common` and `Inherited` characters is a single `Common` run.
+///
+/// Returned ranges are byte offsets into `text`.
+pub fn script_runs(text: &str) -> Vec<(Range<usize>, Script)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut run_script = script::COMMON;
+    // Opening brackets with the script they were opened in.
+    let mut brackets: Vec<(char, Script)> = Vec::new();
+
+    for (offset, c) in text.char_indices() {
+        let mut sc = unicode::char_script(c);
+        if is_unresolved(sc) {
+            sc = run_script;
+
+            match c.general_category() {
+                GeneralCategory::OpenPunctuation if c.mirrored().is_some() => {
+                    brackets.push((c, run_script));
+                }
+                GeneralCategory::ClosePunctuation => {
+                    if let Some(open) = c.mirrored() {
+                        if let Some(idx) = brackets.iter().rposition(|b| b.0 == open) {
+                            sc = brackets[idx].1;
+                            brackets.truncate(idx);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if is_unresolved(sc) || sc == run_script {
+            continue;
+        }
+
+        if is_unresolved(run_script) {
+            // Leading Common characters and brackets opened so far belong to the first script.
+            run_script = sc;
+            for bracket in &mut brackets {
+                if is_unresolved(bracket.1) {
+                    bracket.1 = sc;
+                }
+            }
+
+            continue;
+        }
+
+        runs.push((start..offset, run_script));
+        start = offset;
+        run_script = sc;
+    }
+
+    if start < text.len() {
+        runs.push((start..text.len(), run_script));
+    }
+
+    runs
+}
+
+#[inline]
+fn is_unresolved(sc: Script) -> bool {
+    sc == script::COMMON || sc == script::INHERITED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(script_runs(""), vec![]);
+    }
+
+    #[test]
+    fn common_only() {
+        assert_eq!(script_runs("123 !"), vec![(0..5, script::COMMON)]);
+    }
+
+    #[test]
+    fn latin_and_arabic() {
+        // Digits and spaces follow the preceding script.
+        assert_eq!(script_runs("abc 123 مرحبا"), vec![
+            (0..8, script::LATIN),
+            (8..18, script::ARABIC),
+        ]);
+    }
+
+    #[test]
+    fn leading_digits() {
+        assert_eq!(script_runs("123 مرحبا abc"), vec![
+            (0..15, script::ARABIC),
+            (15..18, script::LATIN),
+        ]);
+    }
+
+    #[test]
+    fn paired_brackets() {
+        // The closing bracket belongs to the run of the opening one.
+        assert_eq!(script_runs("abc (مرحبا) def"), vec![
+            (0..5, script::LATIN),
+            (5..15, script::ARABIC),
+            (15..20, script::LATIN),
+        ]);
+    }
+
+    #[test]
+    fn combining_marks() {
+        // ARABIC FATHA is Inherited.
+        assert_eq!(script_runs("a\u{064E}ب\u{064E}"), vec![
+            (0..3, script::LATIN),
+            (3..7, script::ARABIC),
+        ]);
+    }
+}
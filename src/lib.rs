@@ -6,22 +6,51 @@
 #![warn(missing_docs)]
 
 mod buffer;
+mod chunks;
+mod colr;
 mod common;
+mod fallback;
 mod ffi;
 mod font;
+mod fvar;
+mod mvar;
+mod itemize;
+mod shape_plan;
+mod stream;
 mod text_parser;
 mod unicode;
+mod word_cache;
+mod words;
 mod complex;
 mod ot;
 
+/// An OpenType tag.
+///
+/// `Tag::to_bytes` and `Tag::as_u32` use the same big-endian layout as harfbuzz's `hb_tag_t`,
+/// i.e. the first character is the most significant byte, so `Tag::as_u32` can be compared
+/// with tags from harfbuzz-based code directly.
 pub use ttf_parser::Tag;
+pub use ttf_parser::OutlineBuilder;
 
 pub use crate::buffer::{
-    GlyphPosition, GlyphInfo, BufferClusterLevel,
-    SerializeFlags, UnicodeBuffer, GlyphBuffer
+    GlyphPosition, GlyphInfo, GlyphFlags, BufferClusterLevel, BufferFlags,
+    SerializeFlags, UnicodeBuffer, GlyphBuffer, PositionedGlyph, RoundMode, ShapingResult, SplitError
+};
+pub use crate::chunks::{shape_chunks, ShapeChunks};
+pub use crate::colr::{Color, ColorStop, CompositeMode, GradientExtend, Paint, PaintSink, Transform};
+pub use crate::fallback::{shape_with_fallback, FontFallbackList, FallbackGlyph};
+pub use crate::complex::{arabic_joining_forms, ArabicForm};
+pub use crate::common::{
+    Direction, Script, Language, Feature, Variation, DEFAULT_FEATURES, script,
+    ot_tags_from_script_and_language, SegmentProperties
 };
-pub use crate::common::{Direction, Script, Language, Feature, Variation, script};
-pub use crate::font::Font;
+pub use crate::font::{Font, FontExtents};
+pub use crate::fvar::{AxisInfo, NamedInstance};
+pub use crate::itemize::script_runs;
+pub use crate::shape_plan::{ShapePlan, FeatureStage, PlanMismatch};
+pub use crate::word_cache::WordCache;
+pub use crate::words::shape_words;
+pub use crate::unicode::{GeneralCategory, GeneralCategoryGroups, UNICODE_VERSION, mirrored, compose, decompose};
 
 type Mask = u32;
 
@@ -30,8 +59,116 @@ type Mask = u32;
 ///
 /// Consumes the buffer. You can then run `GlyphBuffer::clear` to get the `UnicodeBuffer` back
 /// without allocating a new one.
+///
+/// The text is normalized during shaping, so canonically equivalent texts,
+/// like NFC and NFD forms of the same string, produce the same glyphs.
+/// Only clusters differ, since they refer to different characters.
+/// The known exception is Arabic ALEF + MADDA ABOVE + HAMZA ABOVE: U+0622 + U+0654 (NFC)
+/// is shaped as is, while U+0627 + U+0653 + U+0654 (NFD) becomes U+0623 + U+0653,
+/// because HAMZA is moved before MADDA according to Unicode Arabic Mark Rendering,
+/// which prevents the NFC composition.
+///
+/// When the buffer content was changed to glyphs via a raw pointer,
+/// the glyphs are returned unshaped. Use `shape_with_result` to detect this.
 pub fn shape(font: &Font<'_>, features: &[Feature], mut buffer: UnicodeBuffer) -> GlyphBuffer {
+    if buffer.contains_glyphs() {
+        return GlyphBuffer(buffer.0);
+    }
+
+    buffer.guess_segment_properties();
+    unsafe {
+        ffi::hb_shape(
+            font.as_ptr(),
+            buffer.0.as_ptr(),
+            features.as_ptr() as *mut _,
+            features.len() as u32,
+        )
+    };
+
+    GlyphBuffer(buffer.0)
+}
+
+/// Shapes the buffer content like `shape` and reports how it was shaped.
+///
+/// When the buffer content was changed to glyphs via a raw pointer,
+/// the buffer is returned as is, with `ShapingResult::had_error` set.
+pub fn shape_with_result(font: &Font<'_>, features: &[Feature], mut buffer: UnicodeBuffer) -> ShapingResult {
+    // Properties can't be guessed from glyphs.
+    if buffer.contains_glyphs() {
+        return ShapingResult {
+            had_error: true,
+            used_fallback: false,
+            direction: buffer.direction(),
+            script: buffer.script(),
+            glyphs: GlyphBuffer(buffer.0),
+        };
+    }
+
+    buffer.guess_segment_properties();
+    let direction = buffer.direction();
+    let script = buffer.script();
+
+    let plan = ShapePlan::new(font, &buffer, features);
+    let glyphs = plan.execute(font, buffer);
+
+    ShapingResult {
+        had_error: !glyphs.0.allocation_successful(),
+        used_fallback: plan.uses_fallback_mark_positioning(),
+        glyphs,
+        direction,
+        script,
+    }
+}
+
+/// Shapes the buffer content using a prebuilt shaping plan.
+///
+/// Same as `shape`, but skips the planning step, which makes it faster
+/// when many runs with the same properties are shaped.
+/// Unset buffer properties are guessed first, like in `shape`.
+///
+/// Returns an error when `font`, the buffer properties or `features`
+/// are different from the ones the plan was created for.
+/// Features must be in the same order.
+///
+/// When the buffer content was changed to glyphs via a raw pointer,
+/// the glyphs are returned unshaped, like in `shape`.
+pub fn shape_with_plan(
+    font: &Font<'_>,
+    plan: &ShapePlan<'_>,
+    mut buffer: UnicodeBuffer,
+    features: &[Feature],
+) -> Result<GlyphBuffer, PlanMismatch> {
+    if buffer.contains_glyphs() {
+        return Ok(GlyphBuffer(buffer.0));
+    }
+
     buffer.guess_segment_properties();
+    plan.check(font, &buffer, features)?;
+    Ok(plan.execute(font, buffer))
+}
+
+/// Shapes a text run with explicit segment properties.
+///
+/// Unlike `shape`, nothing is guessed, so this is the entry point for layout engines
+/// that do their own itemization. Properties that don't match the text are still used:
+/// for example, Arabic text with `LeftToRight` direction is shaped as if it's
+/// in visual order, since the direction is reversed to the script native one
+/// before shaping and the text is reversed along with it.
+///
+/// # Panics
+///
+/// Panics when `props.direction` is `Direction::Invalid`.
+pub fn shape_run(font: &Font<'_>, text: &str, props: SegmentProperties, features: &[Feature]) -> GlyphBuffer {
+    assert_ne!(props.direction, Direction::Invalid, "direction must be set");
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_direction(props.direction);
+    buffer.set_script(props.script);
+    if let Some(language) = props.language {
+        buffer.set_language(language);
+    }
+
     unsafe {
         ffi::hb_shape(
             font.as_ptr(),
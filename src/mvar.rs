@@ -0,0 +1,116 @@
+use ttf_parser::Tag;
+
+use crate::stream::Stream;
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#item-variation-store
+const LONG_WORDS: u16 = 0x8000;
+const WORD_DELTA_COUNT_MASK: u16 = 0x7FFF;
+
+/// Returns the `MVAR` delta of a font-wide metric, like `hasc`.
+///
+/// `coords` are normalized variation coordinates in F2DOT14.
+/// `None` when the metric has no variations.
+pub(crate) fn metrics_delta(mvar: &[u8], tag: Tag, coords: &[i32]) -> Option<f32> {
+    let mvar = Stream(mvar);
+    let record_size = usize::from(mvar.u16(6)?);
+    let count = usize::from(mvar.u16(8)?);
+    let store = usize::from(mvar.u16(10)?);
+    if record_size < 8 || store == 0 {
+        return None;
+    }
+
+    // Value records are sorted by tag.
+    let (mut lo, mut hi) = (0, count);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let record = 12 + mid * record_size;
+        let record_tag = mvar.u32(record)?;
+        if record_tag == tag.as_u32() {
+            let outer = usize::from(mvar.u16(record + 4)?);
+            let inner = usize::from(mvar.u16(record + 6)?);
+            return item_delta(Stream(mvar.0.get(store..)?), outer, inner, coords);
+        } else if record_tag < tag.as_u32() {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    None
+}
+
+fn item_delta(store: Stream, outer: usize, inner: usize, coords: &[i32]) -> Option<f32> {
+    if store.u16(0)? != 1 || outer >= usize::from(store.u16(6)?) {
+        return None;
+    }
+
+    let regions = Stream(store.0.get(store.u32(2)? as usize..)?);
+    let data = Stream(store.0.get(store.u32(8 + outer * 4)? as usize..)?);
+
+    let item_count = usize::from(data.u16(0)?);
+    let word_delta_count = data.u16(2)?;
+    let region_count = usize::from(data.u16(4)?);
+    if inner >= item_count {
+        return None;
+    }
+
+    let is_long = word_delta_count & LONG_WORDS != 0;
+    let word_count = usize::from(word_delta_count & WORD_DELTA_COUNT_MASK);
+    let (word_size, short_size) = if is_long { (4, 2) } else { (2, 1) };
+    let row_size = word_count * word_size + region_count.checked_sub(word_count)? * short_size;
+    let row = 6 + region_count * 2 + inner * row_size;
+
+    let mut delta = 0.0;
+    for i in 0..region_count {
+        let scalar = region_scalar(regions, usize::from(data.u16(6 + i * 2)?), coords)?;
+        if scalar == 0.0 {
+            continue;
+        }
+
+        let value = if i < word_count {
+            let offset = row + i * word_size;
+            if is_long { data.u32(offset)? as i32 } else { i32::from(data.i16(offset)?) }
+        } else {
+            let offset = row + word_count * word_size + (i - word_count) * short_size;
+            if is_long { i32::from(data.i16(offset)?) } else { i32::from(data.u8(offset)? as i8) }
+        };
+
+        delta += value as f32 * scalar;
+    }
+
+    Some(delta)
+}
+
+// Same as harfbuzz's `VarRegionList::evaluate`.
+fn region_scalar(regions: Stream, index: usize, coords: &[i32]) -> Option<f32> {
+    let axis_count = usize::from(regions.u16(0)?);
+    if index >= usize::from(regions.u16(2)?) {
+        return None;
+    }
+
+    let mut scalar = 1.0;
+    let region = 4 + index * axis_count * 6;
+    for axis in 0..axis_count {
+        let record = region + axis * 6;
+        let start = i32::from(regions.i16(record)?);
+        let peak = i32::from(regions.i16(record + 2)?);
+        let end = i32::from(regions.i16(record + 4)?);
+        let coord = coords.get(axis).cloned().unwrap_or(0);
+
+        if peak == 0 || coord == peak || start > peak || peak > end || (start < 0 && end > 0) {
+            continue;
+        }
+
+        if coord <= start || end <= coord {
+            return Some(0.0);
+        }
+
+        scalar *= if coord < peak {
+            (coord - start) as f32 / (peak - start) as f32
+        } else {
+            (end - coord) as f32 / (end - peak) as f32
+        };
+    }
+
+    Some(scalar)
+}
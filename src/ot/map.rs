@@ -55,6 +55,46 @@ impl Map {
         }
     }
 
+    pub fn feature_count(&self) -> usize {
+        unsafe { ffi::hb_ot_map_get_feature_count(self.as_ptr()) as usize }
+    }
+
+    /// Returns a feature tag and its stage.
+    ///
+    /// Returns `None` when the feature is not present in the specified table.
+    pub fn feature(&self, table_index: TableIndex, index: usize) -> Option<(Tag, usize)> {
+        let mut tag = Tag(0);
+        let mut stage = 0;
+        let found = unsafe {
+            ffi::hb_ot_map_get_feature(
+                self.as_ptr(),
+                table_index as u32,
+                index as u32,
+                &mut tag as *mut _,
+                &mut stage as *mut _,
+            )
+        };
+
+        if found {
+            Some((tag, stage as usize))
+        } else {
+            None
+        }
+    }
+
+    pub fn stage_count(&self, table_index: TableIndex) -> usize {
+        unsafe { ffi::hb_ot_map_get_stage_count(self.as_ptr(), table_index as u32) as usize }
+    }
+
+    pub fn stage_has_pause(&self, table_index: TableIndex, stage: usize) -> bool {
+        unsafe { ffi::hb_ot_map_stage_has_pause(self.as_ptr(), table_index as u32, stage as u32) }
+    }
+
+    /// Checks that the feature was requested with a fallback, but is missing in the font.
+    pub fn needs_fallback(&self, feature_tag: Tag) -> bool {
+        unsafe { ffi::hb_ot_map_needs_fallback(self.as_ptr(), feature_tag) }
+    }
+
     pub fn collect_stage_lookups(
         &self,
         table_index: TableIndex,
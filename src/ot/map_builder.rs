@@ -18,6 +18,9 @@ bitflags::bitflags! {
         const MANUAL_ZWNJ               = 0x04;
 
         /// Don't skip over ZWJ when matching **input**.
+        ///
+        /// By default, ZWJ is skipped, so it doesn't break ligatures.
+        /// With this flag, a ZWJ inside a sequence prevents it from being ligated.
         const MANUAL_ZWJ                = 0x08;
 
         const MANUAL_JOINERS            = Self::MANUAL_ZWNJ.bits | Self::MANUAL_ZWJ.bits;
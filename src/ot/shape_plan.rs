@@ -31,7 +31,7 @@ impl ShapePlan {
     #[inline]
     pub fn script(&self) -> Script {
         unsafe {
-            Script::from_raw(ffi::hb_ot_shape_plan_get_script(self.plan.as_ptr()))
+            Script::from_hb(ffi::hb_ot_shape_plan_get_script(self.plan.as_ptr()))
         }
     }
 
@@ -41,4 +41,11 @@ impl ShapePlan {
             ffi::hb_ot_shape_plan_has_gpos_mark(self.plan.as_ptr())
         }
     }
+
+    #[inline]
+    pub fn fallback_mark_positioning(&self) -> bool {
+        unsafe {
+            ffi::hb_ot_shape_plan_fallback_mark_positioning(self.plan.as_ptr())
+        }
+    }
 }
@@ -21,7 +21,7 @@ impl ShapePlanner {
     #[inline]
     pub fn script(&self) -> Script {
         unsafe {
-            Script::from_raw(ffi::hb_ot_shape_planner_get_script(self.planner.as_ptr()))
+            Script::from_hb(ffi::hb_ot_shape_planner_get_script(self.planner.as_ptr()))
         }
     }
 }
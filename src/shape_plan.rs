@@ -0,0 +1,357 @@
+use std::fmt;
+use std::ptr::NonNull;
+
+use crate::{ffi, ot, Feature, Font, GlyphBuffer, Language, Script, Tag, UnicodeBuffer};
+use crate::common::{Direction, SegmentProperties};
+use crate::ot::TableIndex;
+
+
+/// A shaping plan.
+///
+/// Contains the features and lookups that will be applied to a text
+/// with a specific direction, script and language.
+///
+/// The plan borrows the font it was created for, so the font can't be changed,
+/// e.g. via `Font::set_variations`, while the plan is alive.
+pub struct ShapePlan<'a> {
+    ptr: NonNull<ffi::hb_shape_plan_t>,
+    font: &'a Font<'a>,
+    direction: Direction,
+    script: Script,
+    language: Option<Language>, // Must outlive the plan.
+    features: Vec<Feature>,
+    disabled_lookups: Vec<(Tag, u16)>, // Sorted.
+}
+
+impl<'a> ShapePlan<'a> {
+    /// Creates a new shaping plan for the `buffer` properties.
+    ///
+    /// The buffer content is not used.
+    ///
+    /// Script and language systems are selected separately for `GSUB` and `GPOS`,
+    /// like in harfbuzz. The script is the first one found from:
+    ///
+    /// 1. the OpenType tags of the buffer script, see `ot_tags_from_script_and_language`
+    /// 2. `DFLT`
+    /// 3. `dflt`, which some fonts use by mistake
+    /// 4. `latn`
+    ///
+    /// Then the language system of the buffer language is used, or the default one
+    /// of the selected script. So a font with only a `DFLT` script still gets
+    /// its default features applied to any text, including the text with an unset script.
+    /// See `chosen_script`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the buffer direction is not set.
+    /// Use `UnicodeBuffer::guess_segment_properties` or `UnicodeBuffer::set_direction` first.
+    pub fn new(font: &'a Font<'a>, buffer: &UnicodeBuffer, features: &[Feature]) -> Self {
+        assert_ne!(buffer.direction(), Direction::Invalid, "buffer direction must be set");
+
+        let language = buffer.language();
+
+        let mut props = ffi::hb_segment_properties_t {
+            direction: 0,
+            script: 0,
+            language: std::ptr::null(),
+            reserved1: std::ptr::null_mut(),
+            reserved2: std::ptr::null_mut(),
+        };
+
+        unsafe { ffi::hb_buffer_get_segment_properties(buffer.0.as_ptr(), &mut props) };
+        props.language = language.as_ref().map(|l| l.0.as_ptr()).unwrap_or(std::ptr::null());
+
+        let coords = font.coords();
+        let ptr = unsafe {
+            ffi::hb_shape_plan_create(
+                font.face_ptr(),
+                &props,
+                features.as_ptr(),
+                features.len() as u32,
+                coords.as_ptr(),
+                coords.len() as u32,
+            )
+        };
+
+        ShapePlan {
+            ptr: NonNull::new(ptr).unwrap(),
+            font,
+            direction: buffer.direction(),
+            script: buffer.script(),
+            language,
+            features: features.to_vec(),
+            disabled_lookups: Vec::new(),
+        }
+    }
+
+    /// Checks that the plan was created for the same font, segment properties and features.
+    ///
+    /// Only the plan inputs are compared, not the lookups, so this is cheap.
+    /// A cached plan can be reused for a request when this returns `true`.
+    /// Features must be in the same order.
+    ///
+    /// Always `false` when some lookups were disabled via `disable_lookup`.
+    pub fn matches(&self, font: &Font, props: &SegmentProperties, features: &[Feature]) -> bool {
+        self.font.face_ptr() == font.face_ptr()
+            && self.disabled_lookups.is_empty()
+            && self.direction == props.direction
+            && self.script == props.script
+            && self.language == props.language
+            && self.features.as_slice() == features
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut ffi::hb_shape_plan_t {
+        self.ptr.as_ptr()
+    }
+
+    fn ot_plan(&self) -> ot::ShapePlan {
+        ot::ShapePlan::from_ptr(unsafe { ffi::hb_shape_plan_get_ot_shape_plan(self.as_ptr()) })
+    }
+
+    // Checks that the buffer with guessed segment properties can be shaped with this plan.
+    pub(crate) fn check(
+        &self,
+        font: &Font<'_>,
+        buffer: &UnicodeBuffer,
+        features: &[Feature],
+    ) -> Result<(), PlanMismatch> {
+        if font.face_ptr() != self.font.face_ptr() {
+            Err(PlanMismatch::Face)
+        } else if buffer.direction() != self.direction {
+            Err(PlanMismatch::Direction)
+        } else if buffer.script() != self.script {
+            Err(PlanMismatch::Script)
+        } else if buffer.language() != self.language {
+            Err(PlanMismatch::Language)
+        } else if self.features.as_slice() != features {
+            Err(PlanMismatch::Features)
+        } else {
+            Ok(())
+        }
+    }
+
+    // The buffer must contain characters and pass `check` first.
+    pub(crate) fn execute(&self, font: &Font<'_>, buffer: UnicodeBuffer) -> GlyphBuffer {
+        unsafe {
+            let res = ffi::hb_shape_plan_execute(
+                self.as_ptr(),
+                font.as_ptr(),
+                buffer.0.as_ptr(),
+                self.features.as_ptr(),
+                self.features.len() as u32,
+            );
+
+            if res != 0 {
+                ffi::hb_buffer_set_content_type(buffer.0.as_ptr(), ffi::HB_BUFFER_CONTENT_TYPE_GLYPHS);
+            }
+        }
+
+        GlyphBuffer(buffer.0)
+    }
+
+    /// Checks that the font lacks some of the Arabic joining features, like `init` or `fina`.
+    ///
+    /// harfbuzz has a fallback joining implementation for such fonts,
+    /// but it is not ported, so the missing forms are simply not applied.
+    /// A font that doesn't need a fallback will produce better results.
+    ///
+    /// Always `false` for non-Arabic scripts.
+    pub fn needs_fallback_joining(&self) -> bool {
+        let map = &self.ot_plan().ot_map;
+        crate::complex::ARABIC_FEATURES.iter().any(|tag| map.needs_fallback(*tag))
+    }
+
+    /// Checks that the `GPOS` `mark` feature will be applied.
+    pub fn has_gpos_mark(&self) -> bool {
+        self.ot_plan().has_gpos_mark()
+    }
+
+    /// Checks that marks will be positioned using glyph outline bounding boxes
+    /// and combining classes.
+    ///
+    /// Like in harfbuzz, this happens only when the font has no `GPOS` table at all
+    /// (and no `kern` table with cross-stream kerning) and the script shaper allows it.
+    /// A font with `GPOS`, but without the `mark` feature, leaves marks untouched,
+    /// because the font is expected to position them by other means.
+    pub fn uses_fallback_mark_positioning(&self) -> bool {
+        self.ot_plan().fallback_mark_positioning()
+    }
+
+    /// Checks that the Arabic `stch` feature will be applied.
+    ///
+    /// `false` when the font doesn't have it or it was disabled via features.
+    pub fn has_stch(&self) -> bool {
+        self.ot_plan().ot_map.get_1_mask(Tag::from_bytes(b"stch")) != 0
+    }
+
+    /// Disables a `GSUB` or `GPOS` lookup, so `shape_with_plan` skips it.
+    ///
+    /// Meant for debugging fonts, e.g. to find which lookup produces a wrong substitution.
+    /// Lookup indices are font-specific: they are indices in the lookup list
+    /// of the font's `table`, like the ones listed by `feature_stages`.
+    /// Disabled lookups are still listed there.
+    ///
+    /// Returns `false` when the plan doesn't use the lookup
+    /// or when `table` is neither `GSUB` nor `GPOS`.
+    pub fn disable_lookup(&mut self, table: Tag, index: u16) -> bool {
+        let table_index = match table_index(table) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        let found = unsafe {
+            let map = ffi::hb_shape_plan_get_ot_map_mut(self.as_ptr());
+            ffi::hb_ot_map_disable_lookup(map, table_index as u32, u32::from(index))
+        };
+
+        if found {
+            let key = (table.as_u32(), index);
+            if let Err(i) = self.disabled_lookups.binary_search_by_key(&key, |&(t, l)| (t.as_u32(), l)) {
+                self.disabled_lookups.insert(i, (table, index));
+            }
+        }
+
+        found
+    }
+
+    /// Returns lookups disabled via `disable_lookup`, sorted by table and index.
+    pub fn disabled_lookups(&self) -> &[(Tag, u16)] {
+        &self.disabled_lookups
+    }
+
+    /// Returns the script tag selected in the font's `GSUB` or `GPOS` table.
+    ///
+    /// `DFLT`, `dflt` or `latn` when the font has no script for the buffer script.
+    /// `None` when the table has none of them or `table` is neither `GSUB` nor `GPOS`.
+    /// See `ShapePlan::new`.
+    pub fn chosen_script(&self, table: Tag) -> Option<Tag> {
+        let table_index = table_index(table)?;
+
+        // harfbuzz stores `HB_OT_LAYOUT_NO_SCRIPT_INDEX` when nothing was found.
+        let tag = self.ot_plan().ot_map.chosen_script(table_index);
+        if tag == Tag(0xFFFF) || tag == Tag(0) {
+            None
+        } else {
+            Some(tag)
+        }
+    }
+
+    /// Returns the plan stages in the order they will be applied.
+    ///
+    /// All `GSUB` stages are applied before `GPOS` ones.
+    /// Features from the same stage are applied together, lookup by lookup.
+    /// Stages are separated by pauses, which are set by shapers
+    /// when some features must be fully applied before others.
+    ///
+    /// Only features found in the font are listed.
+    pub fn feature_stages(&self) -> Vec<FeatureStage> {
+        let map = &self.ot_plan().ot_map;
+
+        let mut stages = Vec::new();
+        for &(table_index, table) in &[(TableIndex::GSUB, Tag::from_bytes(b"GSUB")),
+                                       (TableIndex::GPOS, Tag::from_bytes(b"GPOS"))] {
+            for stage in 0..map.stage_count(table_index) {
+                let features = (0..map.feature_count())
+                    .filter_map(|i| map.feature(table_index, i))
+                    .filter(|&(_, feature_stage)| feature_stage == stage)
+                    .map(|(tag, _)| tag)
+                    .collect();
+
+                let lookups = map.collect_stage_lookups(table_index, stage)
+                    .iter()
+                    .map(|lookup| lookup.index)
+                    .collect();
+
+                stages.push(FeatureStage {
+                    table,
+                    features,
+                    lookups,
+                    has_pause: map.stage_has_pause(table_index, stage),
+                });
+            }
+        }
+
+        stages
+    }
+}
+
+fn table_index(table: Tag) -> Option<TableIndex> {
+    if table == Tag::from_bytes(b"GSUB") {
+        Some(TableIndex::GSUB)
+    } else if table == Tag::from_bytes(b"GPOS") {
+        Some(TableIndex::GPOS)
+    } else {
+        None
+    }
+}
+
+impl Drop for ShapePlan<'_> {
+    fn drop(&mut self) {
+        unsafe { ffi::hb_shape_plan_destroy(self.as_ptr()) }
+    }
+}
+
+/// Plans are equal when they were created for the same inputs
+/// and have the same lookups disabled. See `ShapePlan::matches`.
+impl PartialEq for ShapePlan<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.font.face_ptr() == other.font.face_ptr()
+            && self.direction == other.direction
+            && self.script == other.script
+            && self.language == other.language
+            && self.features == other.features
+            && self.disabled_lookups == other.disabled_lookups
+    }
+}
+
+impl std::fmt::Debug for ShapePlan<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("ShapePlan")
+            .field("feature_stages", &self.feature_stages())
+            .finish()
+    }
+}
+
+
+/// An error returned by `shape_with_plan` when the plan doesn't match the request.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlanMismatch {
+    /// The font has a different face than the one the plan was created for.
+    Face,
+    /// The buffer direction is different from the plan one.
+    Direction,
+    /// The buffer script is different from the plan one.
+    Script,
+    /// The buffer language is different from the plan one.
+    Language,
+    /// The features are different from the ones the plan was created with.
+    Features,
+}
+
+impl fmt::Display for PlanMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanMismatch::Face => f.write_str("font face doesn't match the plan one"),
+            PlanMismatch::Direction => f.write_str("buffer direction doesn't match the plan one"),
+            PlanMismatch::Script => f.write_str("buffer script doesn't match the plan one"),
+            PlanMismatch::Language => f.write_str("buffer language doesn't match the plan one"),
+            PlanMismatch::Features => f.write_str("features don't match the plan ones"),
+        }
+    }
+}
+
+impl std::error::Error for PlanMismatch {}
+
+
+/// A group of features applied together.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FeatureStage {
+    /// A table the features are from. Either `GSUB` or `GPOS`.
+    pub table: Tag,
+    /// Features of this stage, sorted by tag.
+    pub features: Vec<Tag>,
+    /// Lookup indices of this stage, in the order they will be applied.
+    pub lookups: Vec<u16>,
+    /// Whether a shaper-specific pause callback runs after this stage.
+    pub has_pause: bool,
+}
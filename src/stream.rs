@@ -0,0 +1,65 @@
+use std::convert::TryFrom;
+
+/// A big-endian reader of raw font table data.
+///
+/// Used for tables that are not parsed by `ttf-parser`.
+/// Out of bounds reads return `None`.
+#[derive(Clone, Copy)]
+pub(crate) struct Stream<'a>(pub(crate) &'a [u8]);
+
+impl Stream<'_> {
+    pub(crate) fn u8(&self, offset: usize) -> Option<u8> {
+        self.0.get(offset).cloned()
+    }
+
+    pub(crate) fn u16(&self, offset: usize) -> Option<u16> {
+        let data = self.0.get(offset..offset.checked_add(2)?)?;
+        Some(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    pub(crate) fn i16(&self, offset: usize) -> Option<i16> {
+        self.u16(offset).map(|n| n as i16)
+    }
+
+    pub(crate) fn u24(&self, offset: usize) -> Option<u32> {
+        let data = self.0.get(offset..offset.checked_add(3)?)?;
+        Some(u32::from_be_bytes([0, data[0], data[1], data[2]]))
+    }
+
+    pub(crate) fn u32(&self, offset: usize) -> Option<u32> {
+        let data = self.0.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    // FWORD and UFWORD are read as `f32` for convenience.
+    pub(crate) fn fword(&self, offset: usize) -> Option<f32> {
+        self.i16(offset).map(f32::from)
+    }
+
+    pub(crate) fn ufword(&self, offset: usize) -> Option<f32> {
+        self.u16(offset).map(f32::from)
+    }
+
+    pub(crate) fn f2dot14(&self, offset: usize) -> Option<f32> {
+        self.i16(offset).map(|n| f32::from(n) / 16384.0)
+    }
+
+    pub(crate) fn fixed(&self, offset: usize) -> Option<f32> {
+        self.u32(offset).map(|n| n as i32 as f32 / 65536.0)
+    }
+
+    // Offsets are relative to `base`, like a subtable or a paint. Zero means no target.
+    pub(crate) fn offset24(&self, base: usize, offset: usize) -> Option<usize> {
+        match self.u24(base + offset)? {
+            0 => None,
+            n => base.checked_add(n as usize),
+        }
+    }
+
+    pub(crate) fn offset32(&self, base: usize, offset: usize) -> Option<usize> {
+        match self.u32(base + offset)? {
+            0 => None,
+            n => base.checked_add(usize::try_from(n).ok()?),
+        }
+    }
+}
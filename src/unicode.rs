@@ -5,6 +5,14 @@ pub use unicode_ccc::CanonicalCombiningClass; // TODO: prefer unic-ucd-normal::C
 use unicode_script::UnicodeScript;
 
 use crate::ffi::{self, hb_codepoint_t};
+use crate::complex::{hb_flag, hb_flag_unsafe};
+
+/// The Unicode version of the character property tables.
+///
+/// Covers the joining types, scripts, combining classes and mirroring.
+/// General categories and normalization are provided by dependencies
+/// that are still behind: 12.1 and 10.0 respectively.
+pub const UNICODE_VERSION: (u8, u8, u8) = (13, 0, 0);
 
 // Space estimates based on:
 // https://unicode.org/charts/PDF/U2000.pdf
@@ -105,6 +113,96 @@ pub mod modified_combining_class {
     pub const CCC132: u8 = 131; // sign u
 }
 
+/// Maps a modified combining class to a positional one, used by the fallback mark positioning.
+///
+/// The shaper-specific classes, like Hebrew points, and the Arabic `CCC22`/`CCC26`
+/// renumbering done by `reorder_marks`, are folded back into the `Below`/`Above`
+/// family of classes, so the mark ends up on the correct side of the base.
+/// Positional classes (200 and up) are returned as is.
+pub(crate) fn recategorize_combining_class(u: char, mut class: u8) -> u8 {
+    use modified_combining_class as mcc;
+    use CanonicalCombiningClass as Class;
+
+    if class >= 200 {
+        return class;
+    }
+
+    // Thai / Lao need some per-character work.
+    if (u as u32) & !0xFF == 0x0E00 {
+        if class == 0 {
+            match u {
+                '\u{0E31}' | '\u{0E34}' | '\u{0E35}' | '\u{0E36}' |
+                '\u{0E37}' | '\u{0E47}' | '\u{0E4C}' | '\u{0E4D}' | '\u{0E4E}' => {
+                    class = Class::AboveRight as u8;
+                }
+                '\u{0EB1}' | '\u{0EB4}' | '\u{0EB5}' | '\u{0EB6}' |
+                '\u{0EB7}' | '\u{0EBB}' | '\u{0ECC}' | '\u{0ECD}' => {
+                    class = Class::Above as u8;
+                }
+                '\u{0EBC}' => {
+                    class = Class::Below as u8;
+                }
+                _ => {}
+            }
+        } else if u == '\u{0E3A}' {
+            // Thai virama is below-right.
+            class = Class::BelowRight as u8;
+        }
+    }
+
+    match class {
+        // Hebrew
+        mcc::CCC10 | // sheva
+        mcc::CCC11 | // hataf segol
+        mcc::CCC12 | // hataf patah
+        mcc::CCC13 | // hataf qamats
+        mcc::CCC14 | // hiriq
+        mcc::CCC15 | // tsere
+        mcc::CCC16 | // segol
+        mcc::CCC17 | // patah
+        mcc::CCC18 | // qamats
+        mcc::CCC20 | // qubuts
+        mcc::CCC22   // meteg
+            => Class::Below as u8,
+        mcc::CCC23 => Class::AttachedAbove as u8, // rafe
+        mcc::CCC24 => Class::AboveRight as u8, // shin dot
+        mcc::CCC25 | // sin dot
+        mcc::CCC19   // holam
+            => Class::AboveLeft as u8,
+        mcc::CCC26 => Class::Above as u8, // point varika
+        mcc::CCC21 => class, // dagesh
+
+        // Arabic and Syriac
+        mcc::CCC27 | // fathatan
+        mcc::CCC28 | // dammatan
+        mcc::CCC30 | // fatha
+        mcc::CCC31 | // damma
+        mcc::CCC33 | // shadda
+        mcc::CCC34 | // sukun
+        mcc::CCC35 | // superscript alef
+        mcc::CCC36   // superscript alaph
+            => Class::Above as u8,
+        mcc::CCC29 | // kasratan
+        mcc::CCC32   // kasra
+            => Class::Below as u8,
+
+        // Thai
+        mcc::CCC103 => Class::BelowRight as u8, // sara u / sara uu
+        mcc::CCC107 => Class::AboveRight as u8, // mai
+
+        // Lao
+        mcc::CCC118 => Class::Below as u8, // sign u / sign uu
+        mcc::CCC122 => Class::Above as u8, // mai
+
+        // Tibetan
+        mcc::CCC129 => Class::Below as u8, // sign aa
+        mcc::CCC130 => Class::Above as u8, // sign i
+        mcc::CCC132 => Class::Below as u8, // sign u
+
+        _ => class,
+    }
+}
+
 const MODIFIED_COMBINING_CLASS: &[u8; 256] = &[
     CanonicalCombiningClass::NotReordered as u8,
     CanonicalCombiningClass::Overlay as u8,
@@ -218,7 +316,6 @@ const MODIFIED_COMBINING_CLASS: &[u8; 256] = &[
 pub trait GeneralCategoryExt {
     fn to_hb(&self) -> u32;
     fn from_hb(gc: u32) -> Self;
-    fn is_mark(&self) -> bool;
 }
 
 impl GeneralCategoryExt for GeneralCategory {
@@ -293,13 +390,75 @@ impl GeneralCategoryExt for GeneralCategory {
         }
     }
 
+}
+
+/// `GeneralCategory` groups used by the shaper.
+pub trait GeneralCategoryGroups {
+    /// Checks that category is one of the `M*` categories.
+    fn is_mark(&self) -> bool;
+
+    /// Checks that category is one of the `L*` categories.
+    fn is_letter(&self) -> bool;
+
+    /// Checks that category is one of the `N*` categories.
+    fn is_number(&self) -> bool;
+
+    /// Checks that category can be a part of a word.
+    ///
+    /// Includes modifier and other letters, marks, numbers, symbols (except of
+    /// the connector punctuation), unassigned and private use code points.
+    /// Cased letters are not included.
+    ///
+    /// This is the set used by the Arabic shaper to find the `stch` context.
+    fn is_word(&self) -> bool;
+}
+
+impl GeneralCategoryGroups for GeneralCategory {
     fn is_mark(&self) -> bool {
-        match *self {
-            GeneralCategory::SpacingMark |
-            GeneralCategory::EnclosingMark |
-            GeneralCategory::NonspacingMark => true,
-            _ => false,
-        }
+        hb_flag_unsafe(self.to_hb()) &
+            (   hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_SPACING_MARK) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_ENCLOSING_MARK) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_NON_SPACING_MARK)
+            ) != 0
+    }
+
+    fn is_letter(&self) -> bool {
+        hb_flag_unsafe(self.to_hb()) &
+            (   hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_LOWERCASE_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MODIFIER_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_TITLECASE_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_UPPERCASE_LETTER)
+            ) != 0
+    }
+
+    fn is_number(&self) -> bool {
+        hb_flag_unsafe(self.to_hb()) &
+            (   hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_DECIMAL_NUMBER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_LETTER_NUMBER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_NUMBER)
+            ) != 0
+    }
+
+    // See:
+    // https://github.com/harfbuzz/harfbuzz/commit/6e6f82b6f3dde0fc6c3c7d991d9ec6cfff57823d#commitcomment-14248516
+    fn is_word(&self) -> bool {
+        hb_flag_unsafe(self.to_hb()) &
+            (   hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_UNASSIGNED) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_PRIVATE_USE) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MODIFIER_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_LETTER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_SPACING_MARK) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_ENCLOSING_MARK) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_NON_SPACING_MARK) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_DECIMAL_NUMBER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_LETTER_NUMBER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_NUMBER) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_CURRENCY_SYMBOL) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MODIFIER_SYMBOL) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_MATH_SYMBOL) |
+                hb_flag(ffi::HB_UNICODE_GENERAL_CATEGORY_OTHER_SYMBOL)
+            ) != 0
     }
 }
 
@@ -376,7 +535,7 @@ impl CharExt for char {
     }
 
     fn mirrored(self) -> Option<char> {
-        unicode_bidi_mirroring::get_mirrored(self)
+        mirrored(self)
     }
 
     fn is_emoji_extended_pictographic(self) -> bool {
@@ -547,6 +706,11 @@ pub extern "C" fn hb_ucd_modified_combining_class(u: hb_codepoint_t) -> u32 {
     char::try_from(u).unwrap().modified_combining_class() as u32
 }
 
+#[no_mangle]
+pub extern "C" fn hb_ucd_recategorize_combining_class(u: hb_codepoint_t, class: u32) -> u32 {
+    recategorize_combining_class(char::try_from(u).unwrap(), class as u8) as u32
+}
+
 #[no_mangle]
 pub extern "C" fn hb_ucd_general_category(u: hb_codepoint_t) -> u32 {
     char::try_from(u).unwrap().general_category().to_hb()
@@ -554,10 +718,13 @@ pub extern "C" fn hb_ucd_general_category(u: hb_codepoint_t) -> u32 {
 
 #[no_mangle]
 pub extern "C" fn hb_ucd_script(u: hb_codepoint_t) -> ffi::hb_script_t {
+    char_script(char::try_from(u).unwrap()).tag().as_u32()
+}
+
+pub fn char_script(c: char) -> crate::Script {
     use unicode_script as us;
     use crate::script;
 
-    let c = char::try_from(u).unwrap();
     match c.script() {
         us::Script::Common => script::COMMON,
         us::Script::Inherited => script::INHERITED,
@@ -716,7 +883,7 @@ pub extern "C" fn hb_ucd_script(u: hb_codepoint_t) -> ffi::hb_script_t {
         us::Script::Yi => script::YI,
         us::Script::Zanabazar_Square => script::ZANABAZAR_SQUARE,
         _ => script::UNKNOWN,
-    }.tag().as_u32()
+    }
 }
 
 #[no_mangle]
@@ -724,6 +891,14 @@ pub extern "C" fn hb_ucd_is_default_ignorable(u: hb_codepoint_t) -> ffi::hb_bool
     char::try_from(u).unwrap().is_default_ignorable() as i32
 }
 
+/// Returns a mirrored character, according to the Unicode `Bidi_Mirroring_Glyph` property.
+///
+/// Returns `None` for characters without a mirrored pair,
+/// including `Bidi_Mirrored` ones like `∑`, which must be mirrored by a font instead.
+pub fn mirrored(c: char) -> Option<char> {
+    unicode_bidi_mirroring::get_mirrored(c)
+}
+
 #[no_mangle]
 pub extern "C" fn hb_ucd_mirroring(u: hb_codepoint_t) -> hb_codepoint_t {
     char::try_from(u).unwrap().mirrored().map(u32::from).unwrap_or(0)
@@ -744,6 +919,10 @@ pub extern "C" fn hb_ucd_is_variation_selector(u: hb_codepoint_t) -> ffi::hb_boo
     char::try_from(u).unwrap().is_variation_selector() as i32
 }
 
+/// Composes two characters into one, according to the Unicode canonical composition.
+///
+/// Hangul syllables are composed algorithmically.
+/// Composition exclusions are not composed.
 pub fn compose(a: char, b: char) -> Option<char> {
     unic_ucd_normal::compose(a, b)
 }
@@ -751,7 +930,7 @@ pub fn compose(a: char, b: char) -> Option<char> {
 #[no_mangle]
 pub extern "C" fn hb_ucd_compose(a: hb_codepoint_t, b: hb_codepoint_t, ab: *mut hb_codepoint_t) -> ffi::hb_bool_t {
     unsafe {
-        let new = unic_ucd_normal::compose(
+        let new = compose(
             char::try_from(a).unwrap(),
             char::try_from(b).unwrap(),
         );
@@ -765,7 +944,24 @@ pub extern "C" fn hb_ucd_compose(a: hb_codepoint_t, b: hb_codepoint_t, ab: *mut
     }
 }
 
-fn hb_ucd_decompose_hangul(ab: hb_codepoint_t, a: *mut hb_codepoint_t, b: *mut hb_codepoint_t) -> bool {
+/// Decomposes a character one step, according to the Unicode canonical decomposition.
+///
+/// Returns the second character as `None` for singleton decompositions.
+/// Hangul syllables are decomposed algorithmically: an LVT syllable into LV and T.
+pub fn decompose(ab: char) -> Option<(char, Option<char>)> {
+    if let Some((a, b)) = decompose_hangul(ab) {
+        return Some((a, Some(b)));
+    }
+
+    let chars = unic_ucd_normal::canonical_decomposition(ab)?;
+    match chars.len() {
+        1 => Some((chars[0], None)),
+        2 => Some((chars[0], Some(chars[1]))),
+        _ => None,
+    }
+}
+
+fn decompose_hangul(ab: char) -> Option<(char, char)> {
     const S_BASE: u32 = 0xAC00;
     const L_BASE: u32 = 0x1100;
     const V_BASE: u32 = 0x1161;
@@ -776,24 +972,20 @@ fn hb_ucd_decompose_hangul(ab: hb_codepoint_t, a: *mut hb_codepoint_t, b: *mut h
     const N_COUNT: u32 = V_COUNT * T_COUNT;
     const S_COUNT: u32 = L_COUNT * N_COUNT;
 
-    let si = ab.wrapping_sub(S_BASE);
+    let si = (ab as u32).wrapping_sub(S_BASE);
     if si >= S_COUNT {
-        return false;
+        return None;
     }
 
-    unsafe {
-        if si % T_COUNT != 0 {
-            // LV,T
-            *a = S_BASE + (si / T_COUNT) * T_COUNT;
-            *b = T_BASE + (si % T_COUNT);
-        } else {
-            // L,V
-            *a = L_BASE + (si / N_COUNT);
-            *b = V_BASE + (si % N_COUNT) / T_COUNT;
-        }
-    }
+    let (a, b) = if si % T_COUNT != 0 {
+        // LV,T
+        (S_BASE + (si / T_COUNT) * T_COUNT, T_BASE + (si % T_COUNT))
+    } else {
+        // L,V
+        (L_BASE + (si / N_COUNT), V_BASE + (si % N_COUNT) / T_COUNT)
+    };
 
-    true
+    Some((char::try_from(a).unwrap(), char::try_from(b).unwrap()))
 }
 
 #[no_mangle]
@@ -802,42 +994,123 @@ pub extern "C" fn hb_ucd_decompose(
     a: *mut hb_codepoint_t,
     b: *mut hb_codepoint_t,
 ) -> ffi::hb_bool_t {
-    unsafe {
-        *a = ab as u32;
-        *b = 0;
-    }
-
-    if hb_ucd_decompose_hangul(ab, a, b) {
-        return 1;
-    }
-
-    let ab = char::try_from(ab).unwrap();
-    let chars = match unic_ucd_normal::canonical_decomposition(ab) {
-        Some(chars) => chars,
-        None => return 0,
+    let (new_a, new_b, res) = match decompose(char::try_from(ab).unwrap()) {
+        Some((c1, c2)) => (c1 as u32, c2.map(u32::from).unwrap_or(0), 1),
+        None => (ab, 0, 0),
     };
 
     unsafe {
-        match chars.len() {
-            1 => {
-                *a = chars[0] as u32;
-                *b = 0;
-                1
-            }
-            2 => {
-                *a = chars[0] as u32;
-                *b = chars[1] as u32;
-                1
-            }
-            _ => 0,
-        }
+        *a = new_a;
+        *b = new_b;
     }
+
+    res
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn general_category_groups() {
+        assert!(GeneralCategory::NonspacingMark.is_mark());
+        assert!(!GeneralCategory::OtherLetter.is_mark());
+
+        assert!(GeneralCategory::UppercaseLetter.is_letter());
+        assert!(!GeneralCategory::LetterNumber.is_letter());
+
+        assert!(GeneralCategory::LetterNumber.is_number());
+        assert!(!GeneralCategory::MathSymbol.is_number());
+
+        assert!(GeneralCategory::OtherLetter.is_word());
+        assert!(GeneralCategory::Unassigned.is_word());
+        assert!(GeneralCategory::MathSymbol.is_word());
+        // Cased letters and punctuation are not part of the set.
+        assert!(!GeneralCategory::LowercaseLetter.is_word());
+        assert!(!GeneralCategory::OtherPunctuation.is_word());
+        assert!(!GeneralCategory::Format.is_word());
+    }
+
+    #[test]
+    fn recategorize_marks() {
+        use CanonicalCombiningClass as Class;
+
+        // Classes renumbered by the Arabic `reorder_marks` are folded back to their side.
+        assert_eq!(recategorize_combining_class('\u{0655}', modified_combining_class::CCC22),
+                   Class::Below as u8);
+        assert_eq!(recategorize_combining_class('\u{0654}', modified_combining_class::CCC26),
+                   Class::Above as u8);
+
+        // ARABIC HAMZA BELOW 220 -> CCC22 -> 220.
+        let c = '\u{0655}';
+        assert_eq!(c.modified_combining_class(), Class::Below as u8);
+        assert_eq!(recategorize_combining_class(c, modified_combining_class::CCC22),
+                   c.modified_combining_class());
+
+        // ARABIC MADDAH ABOVE 230 -> CCC26 -> 230.
+        let c = '\u{0653}';
+        assert_eq!(c.modified_combining_class(), Class::Above as u8);
+        assert_eq!(recategorize_combining_class(c, modified_combining_class::CCC26),
+                   c.modified_combining_class());
+
+        // Untouched classes.
+        assert_eq!(recategorize_combining_class('\u{05BC}', modified_combining_class::CCC21),
+                   modified_combining_class::CCC21); // HEBREW POINT DAGESH
+        assert_eq!(recategorize_combining_class('\u{0301}', Class::Above as u8), Class::Above as u8);
+
+        // Arabic marks.
+        assert_eq!(recategorize_combining_class('\u{064E}', '\u{064E}'.modified_combining_class()),
+                   Class::Above as u8); // FATHA
+        assert_eq!(recategorize_combining_class('\u{0650}', '\u{0650}'.modified_combining_class()),
+                   Class::Below as u8); // KASRA
+
+        // Thai virama.
+        assert_eq!(recategorize_combining_class('\u{0E3A}', 9), Class::BelowRight as u8);
+    }
+
+    #[test]
+    fn mirrored_chars() {
+        assert_eq!(mirrored('('), Some(')'));
+        assert_eq!(mirrored(')'), Some('('));
+        assert_eq!(mirrored('['), Some(']'));
+        assert_eq!(mirrored('{'), Some('}'));
+        assert_eq!(mirrored('«'), Some('»'));
+
+        // Not brackets, but still have a mirrored pair.
+        assert_eq!(mirrored('∈'), Some('∋'));
+        assert_eq!(mirrored('\u{2215}'), Some('\u{29F5}')); // DIVISION SLASH
+
+        // Bidi_Mirrored, but without a Bidi_Mirroring_Glyph.
+        assert_eq!(mirrored('∑'), None);
+
+        assert_eq!(mirrored('a'), None);
+    }
+
+    #[test]
+    fn compose_chars() {
+        assert_eq!(compose('a', '\u{0301}'), Some('á'));
+        assert_eq!(compose('\u{0627}', '\u{0653}'), Some('\u{0622}')); // ALEF WITH MADDA ABOVE
+        assert_eq!(compose('\u{1100}', '\u{1161}'), Some('\u{AC00}')); // Hangul L,V
+        assert_eq!(compose('\u{AC00}', '\u{11A8}'), Some('\u{AC01}')); // Hangul LV,T
+        // A composition exclusion.
+        assert_eq!(compose('\u{0915}', '\u{093C}'), None);
+        assert_eq!(compose('a', 'b'), None);
+    }
+
+    #[test]
+    fn decompose_chars() {
+        assert_eq!(decompose('á'), Some(('a', Some('\u{0301}'))));
+        assert_eq!(decompose('\u{0622}'), Some(('\u{0627}', Some('\u{0653}'))));
+        assert_eq!(decompose('\u{AC00}'), Some(('\u{1100}', Some('\u{1161}'))));
+        assert_eq!(decompose('\u{AC01}'), Some(('\u{AC00}', Some('\u{11A8}'))));
+        // ANGSTROM SIGN is a singleton.
+        assert_eq!(decompose('\u{212B}'), Some(('\u{00C5}', None)));
+        assert_eq!(decompose('a'), None);
+    }
+
     #[test]
     fn check_unicode_version() {
+        assert_eq!(UNICODE_VERSION,                             (13, 0, 0));
         assert_eq!(unicode_bidi_mirroring::UNICODE_VERSION,     (13, 0, 0));
         assert_eq!(unicode_ccc::UNICODE_VERSION,                (13, 0, 0));
         assert_eq!(unicode_general_category::UNICODE_VERSION,   (12, 1, 0)); // TODO: update
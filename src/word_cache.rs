@@ -0,0 +1,153 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Direction, Feature, Font, GlyphBuffer, Language, SegmentProperties};
+
+/// A cache of shaped words.
+///
+/// Text often repeats the same words, so shaping each distinct word once
+/// and cloning the result saves time in high-throughput renderers.
+///
+/// Words are shaped in isolation, without any context, via `shape_run`.
+/// Therefore, a text must be split into words only where neighbouring words
+/// don't affect each other, like at spaces, which break Arabic cursive joining.
+/// Splitting a joined Arabic word, e.g. at a ZWJ or TATWEEL, produces wrong forms
+/// at the split, since the letters on both sides are shaped as if nothing follows or precedes them.
+/// The same applies to ligatures and kerning across the split.
+/// For such text, use `shape_words` or `shape` with `UnicodeBuffer::set_pre_context`
+/// and `UnicodeBuffer::set_post_context` instead.
+///
+/// A word is cached per font, features and segment properties.
+/// Words shaped before a change of font variations or glyph advances,
+/// via `Font::set_glyph_h_advance_func`, are not reused after it.
+///
+/// When the cache is full, the oldest word is evicted.
+pub struct WordCache {
+    entries: HashMap<WordKey, GlyphBuffer>,
+    order: VecDeque<WordKey>,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct WordKey {
+    word: String,
+    font: usize,
+    coords: Vec<i32>,
+    features: Vec<(u32, u32, u32, u32)>,
+    direction: Direction,
+    script: u32,
+    language: Option<Language>,
+}
+
+impl WordKey {
+    fn new(font: &Font, features: &[Feature], word: &str, props: &SegmentProperties) -> Self {
+        WordKey {
+            word: word.to_string(),
+            font: font.generation(),
+            coords: font.coords().to_vec(),
+            features: features.iter().map(|f| (f.tag.as_u32(), f.value, f.start, f.end)).collect(),
+            direction: props.direction,
+            script: props.script.0.as_u32(),
+            language: props.language.clone(),
+        }
+    }
+}
+
+impl WordCache {
+    /// Creates a new cache that holds up to `capacity` words.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert_ne!(capacity, 0, "capacity must be positive");
+
+        WordCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Shapes a word or returns a copy of the cached result.
+    ///
+    /// Same as `shape_run`, which is called on a cache miss.
+    /// Glyph clusters are byte offsets into `word`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `props.direction` is `Direction::Invalid`.
+    pub fn shape(
+        &mut self,
+        font: &Font<'_>,
+        features: &[Feature],
+        word: &str,
+        props: SegmentProperties,
+    ) -> GlyphBuffer {
+        let key = WordKey::new(font, features, word, &props);
+        if let Some(glyphs) = self.entries.get(&key) {
+            self.hits += 1;
+            return glyphs.clone();
+        }
+
+        self.misses += 1;
+        let glyphs = crate::shape_run(font, word, props, features);
+
+        if self.entries.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.order.push_back(key.clone());
+        self.entries.insert(key, glyphs.clone());
+        glyphs
+    }
+
+    /// Returns the number of cached words.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks that the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of cached words.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of `shape` calls that used a cached word.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of `shape` calls that had to shape a word.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Removes all cached words.
+    ///
+    /// Hit and miss counters are preserved.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+impl std::fmt::Debug for WordCache {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("WordCache")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
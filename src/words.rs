@@ -0,0 +1,62 @@
+use crate::{Direction, Feature, Font, GlyphBuffer, SegmentProperties, UnicodeBuffer};
+
+// Same as HB_BUFFER_CONTEXT_LENGTH.
+const CONTEXT_LEN: usize = 5;
+
+/// Shapes text split into words, one `GlyphBuffer` per word.
+///
+/// Unlike `shape_run` on each word, characters of the neighbouring words
+/// are used as a pre- and post-context, therefore Arabic joining is preserved
+/// across word boundaries, like at a ZWJ or TATWEEL which is a separate word.
+/// Ligatures and kerning across word boundaries are still not applied.
+///
+/// Words are shaped with the same segment properties and are returned in logical order,
+/// while glyphs of each word are in visual order.
+/// Glyph clusters are byte offsets into the word.
+///
+/// # Panics
+///
+/// Panics when `props.direction` is `Direction::Invalid`.
+pub fn shape_words(
+    font: &Font<'_>,
+    features: &[Feature],
+    words: &[&str],
+    props: SegmentProperties,
+) -> Vec<GlyphBuffer> {
+    assert_ne!(props.direction, Direction::Invalid, "direction must be set");
+
+    let mut glyphs = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_pre_context(&pre_context(&words[..i]));
+        buffer.push_str(word);
+        buffer.set_post_context(&post_context(&words[i + 1..]));
+        buffer.set_direction(props.direction);
+        buffer.set_script(props.script);
+        if let Some(ref language) = props.language {
+            buffer.set_language(language.clone());
+        }
+
+        glyphs.push(crate::shape(font, features, buffer));
+    }
+
+    glyphs
+}
+
+/// Returns up to `CONTEXT_LEN` last characters of `words`.
+fn pre_context(words: &[&str]) -> String {
+    let mut chars: Vec<_> = words.iter().rev()
+        .flat_map(|word| word.chars().rev())
+        .take(CONTEXT_LEN)
+        .collect();
+    chars.reverse();
+    chars.into_iter().collect()
+}
+
+/// Returns up to `CONTEXT_LEN` first characters of `words`.
+fn post_context(words: &[&str]) -> String {
+    words.iter()
+        .flat_map(|word| word.chars())
+        .take(CONTEXT_LEN)
+        .collect()
+}
@@ -0,0 +1,1821 @@
+use std::str::FromStr;
+
+use rustybuzz::{Feature, Font, UnicodeBuffer};
+
+fn shape(font_path: &str, text: &str, features: &str) -> rustybuzz::GlyphBuffer {
+    let font_data = std::fs::read(font_path).unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    let features: Vec<_> = features.split(',').filter(|s| !s.is_empty())
+        .map(|s| Feature::from_str(s).unwrap()).collect();
+    rustybuzz::shape(&font, &features, buffer)
+}
+
+#[test]
+fn cluster_mapping_ligature() {
+    // LAM + LAM + HEH is a single ligature.
+    let glyphs = shape(
+        "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+        "\u{0644}\u{0644}\u{0647}",
+        "",
+    );
+
+    assert_eq!(glyphs.len(), 1);
+    assert_eq!(glyphs.glyph_to_cluster(0), 0);
+    assert_eq!(glyphs.cluster_to_glyph_range(0), 0..1);
+    // Offsets inside of the ligature.
+    assert_eq!(glyphs.cluster_to_glyph_range(2), 0..1);
+    assert_eq!(glyphs.cluster_to_glyph_range(4), 0..1);
+}
+
+#[test]
+fn zwj_blocks_arabic_ligature() {
+    let font = "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf";
+
+    // The ligature comes from `rlig`, which the Arabic shaper applies with `MANUAL_ZWJ`.
+    assert_eq!(shape(font, "\u{0644}\u{0644}\u{0647}", "").len(), 1);
+
+    // LAM + ZWJ + LAM + HEH. The font has no space glyph, so ZWJ is removed
+    // and more than one glyph means that the ligature was blocked.
+    assert!(shape(font, "\u{0644}\u{200D}\u{0644}\u{0647}", "").len() > 1);
+}
+
+#[test]
+fn missing_variation() {
+    let font = "tests/fonts/text-rendering-tests/TestCMAP14.otf";
+    let base = shape(font, "\u{82A6}", "").glyph_infos()[0].codepoint;
+
+    // Nondefault and default variation sequences.
+    let glyphs = shape(font, "\u{82A6}\u{E0101}", "");
+    assert_ne!(glyphs.glyph_infos()[0].codepoint, base);
+    assert!(!glyphs.has_missing_variation());
+
+    let glyphs = shape(font, "\u{82A6}\u{E0100}", "");
+    assert_eq!(glyphs.glyph_infos()[0].codepoint, base);
+    assert!(!glyphs.has_missing_variation());
+
+    // The font doesn't have this sequence, so the base glyph is used.
+    let glyphs = shape(font, "\u{82A6}\u{E0102}", "");
+    assert_eq!(glyphs.glyph_infos()[0].codepoint, base);
+    assert!(glyphs.has_missing_variation());
+    assert!(glyphs.clone().has_missing_variation());
+    assert_eq!(glyphs.glyph_positions().iter().skip(1).map(|p| p.x_advance).sum::<i32>(), 0);
+
+    // Reset by the next shaping.
+    let glyphs = shape(font, "\u{82A6}", "");
+    assert!(!glyphs.has_missing_variation());
+}
+
+#[test]
+fn syllable_boundaries() {
+    use rustybuzz::GlyphFlags;
+
+    let font = "tests/fonts/in-house/d629e7fedc0b350222d7987345fe61613fa3929a.ttf";
+
+    // KA + I twice. The pre-base matra is reordered before KA,
+    // so each syllable is merged into a single cluster.
+    let glyphs = shape(font, "\u{0915}\u{093F}\u{0915}\u{093F}", "");
+    let clusters: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.cluster).collect();
+    assert_eq!(clusters, vec![0, 0, 6, 6]);
+    assert!(!glyphs.glyph_infos()[0].flags().contains(GlyphFlags::UNSAFE_TO_BREAK));
+
+    // The syllable boundary is safe to break, so breaking there gives the same glyphs.
+    assert!(!glyphs.glyph_infos()[2].flags().contains(GlyphFlags::UNSAFE_TO_BREAK));
+    let syllable = shape(font, "\u{0915}\u{093F}", "");
+    let expected: Vec<_> = syllable.glyph_infos().iter()
+        .chain(syllable.glyph_infos().iter())
+        .map(|info| info.codepoint)
+        .collect();
+    let actual: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.codepoint).collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn shape_with_fallback() {
+    use rustybuzz::FontFallbackList;
+
+    // The first font has LAM, but not SEEN.
+    let data0 = std::fs::read("tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf").unwrap();
+    let data1 = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let font0 = Font::from_slice(&data0, 0).unwrap();
+    let font1 = Font::from_slice(&data1, 0).unwrap();
+
+    let mut fonts = FontFallbackList::new();
+    assert!(rustybuzz::shape_with_fallback(&fonts, &[], "\u{0644}").is_empty());
+    fonts.push(&font0);
+    fonts.push(&font1);
+    assert_eq!(fonts.len(), 2);
+
+    // LAM + SEEN + LAM
+    let glyphs = rustybuzz::shape_with_fallback(&fonts, &[], "\u{0644}\u{0633}\u{0644}");
+    assert!(glyphs.iter().all(|g| g.info.codepoint != 0));
+
+    // Right-to-left.
+    assert_eq!(glyphs.first().map(|g| (g.info.cluster, g.font_index)), Some((4, 0)));
+    assert_eq!(glyphs.last().map(|g| (g.info.cluster, g.font_index)), Some((0, 0)));
+
+    let seen: Vec<_> = glyphs.iter().filter(|g| g.info.cluster == 2).collect();
+    assert!(!seen.is_empty());
+    assert!(seen.iter().all(|g| g.font_index == 1));
+
+    // SEEN is still joined to its neighbours.
+    let isolated: Vec<_> = shape("tests/fonts/text-rendering-tests/TestShapeAran.ttf", "\u{0633}", "")
+        .glyph_infos().iter().map(|info| info.codepoint).collect();
+    let seen: Vec<_> = seen.iter().map(|g| g.info.codepoint).collect();
+    assert_ne!(seen, isolated);
+}
+
+#[test]
+fn glyph_buffer_debug() {
+    let glyphs = shape("tests/fonts/in-house/1c04a16f32a39c26c851b7fc014d2e8d298ba2b8.ttf", "\u{2010}", "");
+    assert_eq!(
+        format!("{:?}", glyphs),
+        "GlyphBuffer [\n\
+         \x20   gid cluster  x_off  y_off  x_adv  y_adv flags\n\
+         \x20     1       0      0      0    739      0   0x0\n\
+         ]"
+    );
+}
+
+#[test]
+fn scaled_total_advance() {
+    let data = std::fs::read("tests/fonts/in-house/1c04a16f32a39c26c851b7fc014d2e8d298ba2b8.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{2010}");
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+
+    // 2048 units per EM.
+    assert_eq!(glyphs.total_advance(), (739, 0));
+    assert_eq!(glyphs.scaled_total_advance(&font, 2048.0), (739.0, 0.0));
+    assert_eq!(glyphs.scaled_total_advance(&font, 16.0), (5.7734375, 0.0));
+}
+
+#[test]
+fn buffer_flags_match_harfbuzz() {
+    use rustybuzz::BufferFlags;
+
+    extern "C" {
+        fn hb_buffer_get_flags(buffer: *mut std::os::raw::c_void) -> u32;
+    }
+
+    // `hb_buffer_flags_t` values.
+    let flags = [
+        (BufferFlags::BEGINNING_OF_TEXT, 0x00000001),
+        (BufferFlags::END_OF_TEXT, 0x00000002),
+        (BufferFlags::PRESERVE_DEFAULT_IGNORABLES, 0x00000004),
+        (BufferFlags::REMOVE_DEFAULT_IGNORABLES, 0x00000008),
+        (BufferFlags::DO_NOT_INSERT_DOTTED_CIRCLE, 0x00000010),
+        (BufferFlags::DECOMPOSE_PRESENTATION_FORMS, 0x00000020),
+        (BufferFlags::PRODUCE_UNSAFE_TO_CONCAT, 0x00000040),
+        (BufferFlags::PRODUCE_SAFE_TO_INSERT_TATWEEL, 0x00000080),
+        (BufferFlags::MOVE_MARKS_WITH_STCH_TILES, 0x00000100),
+    ];
+
+    for &(flag, value) in &flags {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_flags(flag);
+        assert_eq!(unsafe { hb_buffer_get_flags(buffer.as_mut_ptr()) }, value, "{:?}", flag);
+    }
+
+    assert_eq!(flags.iter().fold(BufferFlags::empty(), |acc, &(flag, _)| acc | flag), BufferFlags::all());
+}
+
+#[test]
+fn glyph_info_new() {
+    let info = rustybuzz::GlyphInfo::new(0x0644, 3);
+    assert_eq!(info.codepoint, 0x0644);
+    assert_eq!(info.cluster, 3);
+    assert!(info.flags().is_empty());
+    assert_eq!(info.as_char(), '\u{0644}');
+    assert_eq!(info.as_glyph(), 0x0644);
+}
+
+#[test]
+fn arabic_glyph_flags() {
+    use rustybuzz::{BufferFlags, GlyphFlags};
+
+    let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let flags = |text: &str, buffer_flags: BufferFlags| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_flags(buffer_flags);
+        buffer.push_str(text);
+        let mut glyphs: Vec<_> = rustybuzz::shape(&font, &[], buffer).glyph_infos().iter()
+            .map(|info| (info.cluster, info.flags()))
+            .collect();
+        glyphs.sort_by_key(|g| g.0);
+        glyphs
+    };
+
+    // BEH + BEH. Joined, so the second one is unsafe to break, which implies unsafe to concat.
+    let glyphs = flags("\u{0628}\u{0628}", BufferFlags::empty());
+    assert_eq!(glyphs[1].0, 2);
+    assert!(glyphs[1].1.contains(GlyphFlags::UNSAFE_TO_BREAK | GlyphFlags::UNSAFE_TO_CONCAT));
+    assert!(glyphs.iter().all(|g| !g.1.contains(GlyphFlags::SAFE_TO_INSERT_TATWEEL)));
+
+    // A tatweel can be inserted between joined letters, but not before the word.
+    let glyphs = flags("\u{0628}\u{0628}", BufferFlags::PRODUCE_SAFE_TO_INSERT_TATWEEL);
+    assert!(!glyphs[0].1.contains(GlyphFlags::SAFE_TO_INSERT_TATWEEL));
+    assert!(glyphs[1].1.contains(GlyphFlags::SAFE_TO_INSERT_TATWEEL));
+
+    // ALEF + BEH. Not joined, but ALEF could join a letter before it.
+    // Without the buffer flag, only unsafe to break glyphs are unsafe to concat.
+    for (_, glyph_flags) in flags("\u{0627}\u{0628}", BufferFlags::empty()) {
+        let is_unsafe_to_break = glyph_flags.contains(GlyphFlags::UNSAFE_TO_BREAK);
+        assert!(!glyph_flags.contains(GlyphFlags::UNSAFE_TO_CONCAT) || is_unsafe_to_break);
+    }
+
+    let glyphs = flags("\u{0627}\u{0628}", BufferFlags::PRODUCE_UNSAFE_TO_CONCAT);
+    assert!(glyphs.iter().all(|g| g.1.contains(GlyphFlags::UNSAFE_TO_CONCAT)));
+}
+
+#[test]
+fn decompose_presentation_forms() {
+    use rustybuzz::BufferFlags;
+
+    let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let codepoints = |text: &str, flags: BufferFlags| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_flags(flags);
+        buffer.push_str(text);
+        rustybuzz::shape(&font, &[], buffer).glyph_infos().iter()
+            .map(|info| (info.codepoint, info.cluster))
+            .collect::<Vec<_>>()
+    };
+
+    // LAM + ALEF FINAL FORM is shaped as LAM + ALEF.
+    let expected = codepoints("\u{0644}\u{0627}", BufferFlags::empty());
+    assert_eq!(codepoints("\u{0644}\u{FE8E}", BufferFlags::DECOMPOSE_PRESENTATION_FORMS), expected);
+
+    // The font doesn't have presentation forms.
+    assert!(codepoints("\u{0644}\u{FE8E}", BufferFlags::empty()).iter().any(|g| g.0 == 0));
+
+    // LAM + SEEN + ALEF FINAL FORM + NOON.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.set_flags(BufferFlags::DECOMPOSE_PRESENTATION_FORMS);
+    buffer.push_str("\u{0644}\u{0633}\u{FE8E}\u{0646}");
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+    assert!(glyphs.contains_glyph_named(&font, "LamIni.outT2"));
+    assert!(glyphs.contains_glyph_named(&font, "SeenMed.inT2outT1"));
+    assert!(glyphs.contains_glyph_named(&font, "AlefFin"));
+}
+
+#[test]
+fn glyph_from_name() {
+    let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+
+    let glyph = font.glyph_from_name("AlefFin").unwrap();
+    assert_ne!(glyph, 0);
+    assert_eq!(font.glyph_from_name("AlefFin"), Some(glyph));
+    assert_eq!(font.glyph_from_name("NoSuchGlyph"), None);
+
+    // LAM + SEEN + ALEF + NOON.
+    let glyphs = shape("tests/fonts/text-rendering-tests/TestShapeAran.ttf", "\u{0644}\u{0633}\u{0627}\u{0646}", "");
+    assert!(glyphs.contains_glyph_named(&font, "AlefFin"));
+    assert!(glyphs.contains_glyph_named(&font, "NoonxSep"));
+    assert!(!glyphs.contains_glyph_named(&font, "NoSuchGlyph"));
+}
+
+#[test]
+fn fallback_mark_positioning() {
+    fn plan_and_shape(font_path: &str, text: &str) -> (bool, bool, rustybuzz::GlyphBuffer) {
+        let font_data = std::fs::read(font_path).unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+        let flags = (plan.has_gpos_mark(), plan.uses_fallback_mark_positioning());
+        (flags.0, flags.1, rustybuzz::shape_with_plan(&font, &plan, buffer, &[]).unwrap())
+    }
+
+    // No GPOS.
+    let (has_gpos_mark, fallback, glyphs) = plan_and_shape(
+        "tests/fonts/in-house/8228d035fcd65d62ec9728fb34f42c63be93a5d3.ttf",
+        "\u{0078}\u{0301}",
+    );
+    assert!(!has_gpos_mark);
+    assert!(fallback);
+    assert!(!glyphs.used_gpos_attachments());
+    assert_ne!(glyphs.glyph_positions()[1].y_offset, 0);
+
+    let (has_gpos_mark, fallback, glyphs) = plan_and_shape(
+        "tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf",
+        "\u{0644}\u{064E}\u{0670}\u{0653}\u{0626}",
+    );
+    assert!(has_gpos_mark);
+    assert!(!fallback);
+    assert!(glyphs.used_gpos_attachments());
+    assert!(glyphs.clone().used_gpos_attachments());
+}
+
+#[test]
+fn round_positions() {
+    use rustybuzz::RoundMode;
+
+    let text = "\u{0644}\u{0633}\u{0627}\u{0646} ".repeat(20);
+    let glyphs = shape("tests/fonts/text-rendering-tests/TestShapeAran.ttf", &text, "");
+    let total = glyphs.total_advance();
+    let positions = |glyphs: &rustybuzz::GlyphBuffer| {
+        glyphs.glyph_positions().iter()
+            .map(|p| (p.x_advance, p.y_advance, p.x_offset, p.y_offset))
+            .collect::<Vec<_>>()
+    };
+    let original = positions(&glyphs);
+
+    let mut glyphs = glyphs;
+    glyphs.round_positions(37.5, RoundMode::None);
+    assert_eq!(positions(&glyphs), original);
+
+    // The error doesn't accumulate over the line.
+    glyphs.round_positions(37.5, RoundMode::Advances);
+    assert!((glyphs.total_advance().0 - total.0).abs() as f32 <= 37.5);
+    assert!(positions(&glyphs).iter().zip(&original).all(|(a, b)| (a.2, a.3) == (b.2, b.3)));
+
+    glyphs.round_positions(37.5, RoundMode::All);
+    assert!((glyphs.total_advance().0 - total.0).abs() as f32 <= 37.5);
+    assert!(positions(&glyphs).iter().all(|p| (p.2 as f32 / 37.5 - (p.2 as f32 / 37.5).round()).abs() < 0.05));
+}
+
+#[test]
+fn guess_direction() {
+    use rustybuzz::Direction;
+
+    let direction = |text: &str| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_direction()
+    };
+
+    assert_eq!(direction("a\u{05D0}"), Direction::LeftToRight);
+    assert_eq!(direction("\u{05D0}a"), Direction::RightToLeft);
+    assert_eq!(direction("123 (\u{0627})"), Direction::RightToLeft);
+    assert_eq!(direction("123 !"), Direction::Invalid);
+    assert_eq!(direction(""), Direction::Invalid);
+
+    // Independent of the buffer script.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{05D0}a");
+    buffer.set_script(rustybuzz::script::LATIN);
+    assert_eq!(buffer.guess_direction(), Direction::RightToLeft);
+    assert_eq!(buffer.direction(), Direction::Invalid);
+}
+
+#[test]
+fn raw_buffer_ptr() {
+    extern "C" {
+        fn hb_buffer_get_length(buffer: *const std::os::raw::c_void) -> u32;
+    }
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("abc");
+    assert_eq!(unsafe { hb_buffer_get_length(buffer.as_ptr()) }, 3);
+    assert_eq!(buffer.as_mut_ptr() as *const _, buffer.as_ptr());
+
+    let glyphs = shape("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+                       "\u{0644}\u{0644}\u{0647}", "");
+    assert_eq!(unsafe { hb_buffer_get_length(glyphs.as_ptr()) }, 1);
+}
+
+#[test]
+fn feature_range_at_mark() {
+    use rustybuzz::BufferClusterLevel;
+
+    let font_data = std::fs::read("tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mark_offsets = |level: BufferClusterLevel, features: &[Feature]| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_cluster_level(level);
+        // LAM + FATHA
+        buffer.push_str("\u{0644}\u{064E}");
+        let glyphs = rustybuzz::shape(&font, features, buffer);
+        glyphs.glyph_positions().iter().map(|p| (p.x_offset, p.y_offset)).collect::<Vec<_>>()
+    };
+
+    let positioned = mark_offsets(BufferClusterLevel::MonotoneGraphemes, &[]);
+    assert!(positioned.iter().any(|&offset| offset != (0, 0)));
+
+    // The range covers only the mark, which belongs to the LAM cluster.
+    let features = [Feature::from_str("-mark[2:4]").unwrap()];
+    assert_eq!(mark_offsets(BufferClusterLevel::MonotoneGraphemes, &features), positioned);
+
+    // Each character has its own cluster.
+    assert_ne!(mark_offsets(BufferClusterLevel::Characters, &features), positioned);
+
+    // The range covers the base, so the mark is affected too.
+    let features = [Feature::from_str("-mark[0:1]").unwrap()];
+    assert_ne!(mark_offsets(BufferClusterLevel::MonotoneGraphemes, &features), positioned);
+}
+
+#[test]
+fn set_len() {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("ab");
+    assert!(buffer.ensure(16));
+    assert!(buffer.capacity() >= 16);
+
+    buffer.set_len(4);
+    assert_eq!(buffer.len(), 4);
+
+    buffer.set_len(1);
+    assert_eq!(buffer.len(), 1);
+    buffer.push_str("c");
+    assert_eq!(buffer.len(), 2);
+
+    let font_data = std::fs::read("tests/fonts/in-house/1a3d8f381387dd29be1e897e4b5100ac8b4829e1.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut glyphs = rustybuzz::shape(&font, &[], buffer);
+    let capacity = glyphs.capacity();
+    assert!(glyphs.ensure(capacity));
+    glyphs.set_len(capacity);
+    assert_eq!(glyphs.glyph_infos().len(), capacity);
+    assert_eq!(glyphs.glyph_positions().len(), capacity);
+    assert_eq!(glyphs.glyph_infos()[capacity - 1].codepoint, 0);
+    assert_eq!(glyphs.glyph_positions()[capacity - 1].x_advance, 0);
+
+    glyphs.set_len(0);
+    assert!(glyphs.is_empty());
+    assert!(glyphs.glyph_positions().is_empty());
+}
+
+#[test]
+fn arabic_disable_calt() {
+    // KAF + THEH + LAM. calt replaces the initial KAF followed by the medial THEH.
+    let font = "tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf";
+    let text = "\u{0643}\u{062B}\u{0644}";
+    let glyph_ids = |features| -> Vec<u32> {
+        shape(font, text, features).glyph_infos().iter().map(|info| info.codepoint).collect()
+    };
+
+    assert_eq!(glyph_ids(""), vec![8, 9, 10]);
+    assert_eq!(glyph_ids("-calt"), vec![8, 9, 7]);
+    // The range covers only THEH, so KAF is still replaced.
+    assert_eq!(glyph_ids("-calt[2:4]"), vec![8, 9, 10]);
+}
+
+#[test]
+fn not_found_glyph() {
+    let font_data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+
+    // The font doesn't have 'a' and U+0628 ARABIC LETTER BEH.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("a\u{0644}\u{0628}");
+    buffer.set_direction(rustybuzz::Direction::LeftToRight);
+    assert_eq!(buffer.not_found_glyph(), 0);
+    buffer.set_not_found_glyph(1);
+    assert_eq!(buffer.not_found_glyph(), 1);
+
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+    let infos = glyphs.glyph_infos();
+    assert_eq!(infos.len(), 3);
+    assert_eq!((infos[0].cluster, infos[0].codepoint), (0, 1));
+    assert_eq!((infos[1].cluster, infos[1].codepoint), (1, 3));
+    assert_eq!((infos[2].cluster, infos[2].codepoint), (3, 1));
+
+    let buffer = glyphs.into_buffer();
+    assert_eq!(buffer.not_found_glyph(), 1);
+}
+
+#[test]
+fn remove_default_ignorables_clusters() {
+    use rustybuzz::{BufferFlags, Direction};
+
+    let font_data = std::fs::read("tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let shape = |text: &str, direction: Option<Direction>| -> Vec<(u32, u32)> {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        if let Some(direction) = direction {
+            buffer.set_direction(direction);
+            buffer.set_script(rustybuzz::script::LATIN);
+        }
+        buffer.set_flags(BufferFlags::REMOVE_DEFAULT_IGNORABLES);
+        let glyphs = rustybuzz::shape(&font, &[], buffer);
+        glyphs.glyph_infos().iter().map(|info| (info.codepoint, info.cluster)).collect()
+    };
+
+    // KAF + SOFT HYPHEN + LAM. SOFT HYPHEN is transparent for joining,
+    // so the letters are still joined: initial KAF and final LAM.
+    // The glyph output is right-to-left, so SOFT HYPHEN is merged into LAM, the glyph before it.
+    assert_eq!(shape("\u{0643}\u{00AD}\u{0644}", None), vec![(8, 2), (7, 0)]);
+
+    // KAF + SOFT HYPHEN + SOFT HYPHEN + LAM. Both are merged into LAM.
+    assert_eq!(shape("\u{0643}\u{00AD}\u{00AD}\u{0644}", None), vec![(8, 2), (7, 0)]);
+
+    // In left-to-right output, SOFT HYPHEN is merged into KAF, which keeps its cluster.
+    let ltr = shape("\u{0643}\u{00AD}\u{0644}", Some(Direction::LeftToRight));
+    assert_eq!(ltr, vec![(2, 0), (3, 4)]);
+
+    // A leading SOFT HYPHEN is merged into the glyph after it.
+    let ltr = shape("\u{00AD}\u{0643}", Some(Direction::LeftToRight));
+    assert_eq!(ltr, vec![(2, 0)]);
+}
+
+#[test]
+fn glyph_origins() {
+    let font_data = std::fs::read("tests/fonts/in-house/4cbbc461be066fccc611dcc634af6e8cb2705537.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+
+    // FULLWIDTH LATIN CAPITAL LETTER X, with a `VORG` origin.
+    assert_eq!(font.glyph_h_origin(2), (0, 0));
+    assert_eq!(font.glyph_v_origin(2), (500, 867));
+    assert_eq!(font.glyph_v_origin(u32::MAX), (0, 0));
+}
+
+#[test]
+fn shape_with_result() {
+    // No GPOS.
+    let font_data = std::fs::read("tests/fonts/in-house/8228d035fcd65d62ec9728fb34f42c63be93a5d3.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("x\u{0301}");
+    let result = rustybuzz::shape_with_result(&font, &[], buffer);
+    assert!(result.used_fallback);
+    assert!(!result.had_error);
+    assert_eq!(result.direction, rustybuzz::Direction::LeftToRight);
+    assert_eq!(result.script, rustybuzz::script::LATIN);
+    assert_eq!(result.len(), 2);
+    assert_eq!((result.glyph_positions()[1].x_offset, result.glyph_positions()[1].y_offset), (-19, -27));
+
+    // Has GPOS.
+    let font_data = std::fs::read("tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0644}\u{064E}");
+    let result = rustybuzz::shape_with_result(&font, &[], buffer);
+    assert!(!result.used_fallback);
+    assert_eq!(result.direction, rustybuzz::Direction::RightToLeft);
+    assert_eq!(result.script, rustybuzz::script::ARABIC);
+    let buffer = result.glyphs.into_buffer();
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn position_mut() {
+    let mut glyphs = shape("tests/fonts/in-house/8228d035fcd65d62ec9728fb34f42c63be93a5d3.ttf", "xx", "");
+    let (width, _) = glyphs.total_advance();
+    let advance = glyphs.glyph_positions()[0].x_advance;
+    let x = glyphs.positioned_glyphs((0, 0)).nth(1).unwrap().x;
+
+    glyphs.position_mut(0).x_advance -= 100;
+    assert_eq!(glyphs.glyph_positions()[0].x_advance, advance - 100);
+    assert_eq!(glyphs.total_advance().0, width - 100);
+    assert_eq!(glyphs.positioned_glyphs((0, 0)).nth(1).unwrap().x, x - 100);
+}
+
+#[test]
+#[should_panic]
+fn position_mut_out_of_bounds() {
+    let mut glyphs = shape("tests/fonts/in-house/8228d035fcd65d62ec9728fb34f42c63be93a5d3.ttf", "x", "");
+    glyphs.position_mut(1);
+}
+
+#[test]
+fn mongolian_variation_selector_form() {
+    let font = "tests/fonts/in-house/37033cc5cf37bb223d7355153016b6ccece93b28.ttf";
+    let glyph_ids = |text| -> Vec<u32> {
+        shape(font, text, "").glyph_infos().iter().map(|info| info.codepoint).collect()
+    };
+
+    // UE + UE, with FVS1 selecting the alternative initial form.
+    let default_forms = glyph_ids("\u{1826}\u{1826}");
+    let selected_forms = glyph_ids("\u{1826}\u{180B}\u{1826}");
+    assert_eq!(selected_forms.len(), 2);
+    assert_ne!(selected_forms[0], default_forms[0]);
+    assert_eq!(selected_forms[1], default_forms[1]);
+}
+
+#[test]
+fn canonically_equivalent_input() {
+    fn glyphs(font: &str, text: &str) -> Vec<(u32, i32, i32, i32)> {
+        let glyphs = shape(font, text, "");
+        glyphs.glyph_infos().iter().zip(glyphs.glyph_positions())
+            .map(|(info, pos)| (info.codepoint, pos.x_advance, pos.x_offset, pos.y_offset))
+            .collect()
+    }
+
+    // LAM + FATHA + YEH WITH HAMZA ABOVE, with YEH + HAMZA ABOVE in NFD.
+    let font = "tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf";
+    assert_eq!(glyphs(font, "\u{0644}\u{064E}\u{0626}"), glyphs(font, "\u{0644}\u{064E}\u{064A}\u{0654}"));
+
+    // SHIN + DAGESH + SHIN DOT, with marks in the canonical and reversed order.
+    let font = "tests/fonts/in-house/43ef465752be9af900745f72fe29cb853a1401a5.ttf";
+    assert_eq!(glyphs(font, "\u{05E9}\u{05BC}\u{05C1}"), glyphs(font, "\u{05E9}\u{05C1}\u{05BC}"));
+
+    // HE + PATAH + SHIN + DAGESH + SHIN DOT + QAMATS.
+    assert_eq!(
+        glyphs(font, "\u{05D4}\u{05B7}\u{05E9}\u{05BC}\u{05C1}\u{05B8}"),
+        glyphs(font, "\u{05D4}\u{05B7}\u{05E9}\u{05C1}\u{05B8}\u{05BC}")
+    );
+}
+
+#[test]
+fn segment_properties() {
+    let mut buffer = UnicodeBuffer::new();
+    assert_eq!(buffer.direction(), rustybuzz::Direction::Invalid);
+    assert!(buffer.script().tag().is_null());
+    assert_eq!(buffer.language(), None);
+
+    buffer.set_direction(rustybuzz::Direction::TopToBottom);
+    buffer.set_script(rustybuzz::script::ARABIC);
+    buffer.set_language(rustybuzz::Language::from_str("ur").unwrap());
+    assert_eq!(buffer.direction(), rustybuzz::Direction::TopToBottom);
+    assert_eq!(buffer.script(), rustybuzz::script::ARABIC);
+    assert_eq!(buffer.language(), Some(rustybuzz::Language::from_str("ur").unwrap()));
+
+    // Set properties are not guessed.
+    buffer.push_str("abc");
+    buffer.guess_segment_properties();
+    assert_eq!(buffer.direction(), rustybuzz::Direction::TopToBottom);
+    assert_eq!(buffer.script(), rustybuzz::script::ARABIC);
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{05D0}");
+    buffer.guess_segment_properties();
+    assert_eq!(buffer.direction(), rustybuzz::Direction::RightToLeft);
+    assert_eq!(buffer.script(), rustybuzz::script::HEBREW);
+}
+
+#[test]
+fn paint_color_glyph() {
+    use rustybuzz::{Color, CompositeMode, Paint, PaintSink, Transform};
+
+    #[derive(Default)]
+    struct Layers {
+        clip: u32,
+        layers: Vec<(u32, Color)>,
+    }
+
+    impl PaintSink for Layers {
+        fn push_transform(&mut self, _: Transform) {}
+        fn pop_transform(&mut self) {}
+        fn push_clip_glyph(&mut self, glyph: u32) { self.clip = glyph; }
+        fn pop_clip(&mut self) {}
+        fn paint(&mut self, paint: Paint) {
+            if let Paint::Solid(color) = paint {
+                self.layers.push((self.clip, color));
+            }
+        }
+        fn push_layer(&mut self, _: CompositeMode) {}
+        fn pop_layer(&mut self) {}
+    }
+
+    let data = std::fs::read("tests/fonts/in-house/53374c7ca3657be37efde7ed02ae34229a56ae1f.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    let mut sink = Layers::default();
+    assert!(font.paint_glyph(8, &mut sink));
+    assert_eq!(sink.layers, vec![
+        (9, Color { red: 0, green: 0, blue: 0, alpha: 255 }),
+        (10, Color { red: 255, green: 0, blue: 0, alpha: 255 }),
+        (11, Color { red: 255, green: 204, blue: 0, alpha: 255 }),
+    ]);
+
+    // Not a color glyph.
+    let mut sink = Layers::default();
+    assert!(!font.paint_glyph(9, &mut sink));
+    assert!(sink.layers.is_empty());
+
+    // No COLR table.
+    let data = std::fs::read("tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    assert!(!font.paint_glyph(1, &mut sink));
+}
+
+#[test]
+fn cluster_map() {
+    // LAM + LAM + HEH is a single ligature.
+    let glyphs = shape(
+        "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+        "\u{0644}\u{0644}\u{0647}",
+        "",
+    );
+    assert_eq!(glyphs.cluster_map(), vec![(0, 0..1)]);
+
+    // SYRIAC ABBREVIATION MARK is stretched into multiple glyphs.
+    let glyphs = shape(
+        "tests/fonts/in-house/d9b8bc10985f24796826c29f7ccba3d0ae11ec02.ttf",
+        "\u{0718}\u{070F}\u{0718}\u{0718}\u{002E}",
+        "",
+    );
+    let len = glyphs.len();
+    assert!(len > 5);
+    assert_eq!(glyphs.cluster_map(), vec![(8, 0..1), (6, 1..2), (4, 2..3), (2, 3..len - 1), (0, len - 1..len)]);
+
+    assert!(shape("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf", "", "")
+        .cluster_map().is_empty());
+}
+
+#[test]
+fn source_indices_after_reordering() {
+    let data = std::fs::read("tests/fonts/in-house/43ef465752be9af900745f72fe29cb853a1401a5.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    let shape = |text: &str, cluster_level| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_cluster_level(cluster_level);
+        buffer.reset_clusters();
+        rustybuzz::shape(&font, &[], buffer)
+    };
+
+    let shin_dot = shape("\u{05C1}", rustybuzz::BufferClusterLevel::Characters).glyph_infos()[0].codepoint;
+    let dagesh = shape("\u{05BC}", rustybuzz::BufferClusterLevel::Characters).glyph_infos()[0].codepoint;
+
+    // SHIN + DAGESH + SHIN DOT. Normalization moves SHIN DOT before DAGESH.
+    let glyphs = shape("\u{05E9}\u{05BC}\u{05C1}", rustybuzz::BufferClusterLevel::Characters);
+    let infos: Vec<_> = glyphs.glyph_infos().iter().map(|info| (info.codepoint, info.cluster)).collect();
+    // Right-to-left, so in the reversed logical order.
+    assert_eq!(infos.len(), 3);
+    assert_eq!(infos[0], (dagesh, 1));
+    assert_eq!(infos[1], (shin_dot, 2));
+    assert_eq!(infos[2].1, 0);
+
+    // Monotone levels merge reordered characters into a single cluster.
+    let glyphs = shape("\u{05E9}\u{05BC}\u{05C1}", rustybuzz::BufferClusterLevel::MonotoneCharacters);
+    let clusters: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.cluster).collect();
+    assert_eq!(clusters, vec![1, 1, 0]);
+}
+
+#[test]
+fn unsafe_to_break_across_shapers() {
+    use rustybuzz::{GlyphBuffer, GlyphFlags};
+
+    // Returns clusters at which the text can be broken without reshaping
+    // and checks that breaking there produces the same glyphs.
+    fn safe_breaks(font: &str, text: &str, features: &str, rtl: bool) -> Vec<u32> {
+        let codepoints = |glyphs: &GlyphBuffer| -> Vec<u32> {
+            glyphs.glyph_infos().iter().map(|info| info.codepoint).collect()
+        };
+
+        let glyphs = shape(font, text, features);
+        let infos = glyphs.glyph_infos();
+        let mut breaks: Vec<_> = infos.iter()
+            .map(|info| info.cluster)
+            .filter(|&c| c != 0)
+            .filter(|&c| !infos.iter().any(|info| {
+                info.cluster == c && info.flags().contains(GlyphFlags::UNSAFE_TO_BREAK)
+            }))
+            .collect();
+        breaks.sort_unstable();
+        breaks.dedup();
+
+        for &c in &breaks {
+            let (before, after) = text.split_at(c as usize);
+            let before = codepoints(&shape(font, before, features));
+            let after = codepoints(&shape(font, after, features));
+            let joined = if rtl { [after, before].concat() } else { [before, after].concat() };
+            assert_eq!(joined, codepoints(&glyphs), "break at {}", c);
+        }
+
+        breaks
+    }
+
+    // The default shaper. 0x12 0x13 0x14 is a ligature.
+    let font = "tests/fonts/aots/gsub4_1_simple_f1.otf";
+    assert_eq!(shape(font, "\u{11}\u{12}\u{13}\u{14}\u{11}", "test").len(), 3);
+    assert_eq!(safe_breaks(font, "\u{11}\u{12}\u{13}\u{14}\u{11}", "test", false), vec![1, 4]);
+
+    // The Arabic shaper. KAF + THEH + LAM are joined.
+    let font = "tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf";
+    assert!(safe_breaks(font, "\u{0643}\u{062B}\u{0644}", "", true).is_empty());
+}
+
+#[test]
+fn nominal_glyphs() {
+    let data = std::fs::read("tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    // THEH, KAF, LAM, MEEM
+    let mut glyphs = [0; 4];
+    assert_eq!(font.nominal_glyphs(&['\u{062B}', '\u{0643}', '\u{0644}', '\u{0645}'], &mut glyphs), 4);
+    assert_eq!(glyphs, [1, 2, 3, 4]);
+
+    // Stops at the first missing glyph.
+    let mut glyphs = [0; 4];
+    assert_eq!(font.nominal_glyphs(&['\u{0643}', 'A', '\u{0644}'], &mut glyphs), 1);
+    assert_eq!(glyphs, [2, 0, 0, 0]);
+
+    // And at the end of the output.
+    let mut glyphs = [0; 1];
+    assert_eq!(font.nominal_glyphs(&['\u{0643}', '\u{0644}'], &mut glyphs), 1);
+    assert_eq!(font.nominal_glyphs(&[], &mut glyphs), 0);
+}
+
+#[test]
+fn shape_run() {
+    use rustybuzz::{Direction, SegmentProperties};
+
+    let data = std::fs::read("tests/fonts/in-house/298c9e1d955f10f6f72c6915c3c6ff9bf9695cec.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let glyphs = |text: &str, direction| -> Vec<(u32, u32)> {
+        let props = SegmentProperties {
+            direction,
+            script: rustybuzz::script::ARABIC,
+            language: None,
+        };
+
+        rustybuzz::shape_run(&font, text, props, &[]).glyph_infos().iter()
+            .map(|info| (info.codepoint, info.cluster))
+            .collect()
+    };
+
+    // KAF + THEH + LAM
+    assert_eq!(glyphs("\u{0643}\u{062B}\u{0644}", Direction::RightToLeft), vec![(8, 4), (9, 2), (10, 0)]);
+
+    // Forced left-to-right, the text is shaped in visual order, i.e. reversed.
+    let ltr: Vec<_> = glyphs("\u{0643}\u{062B}\u{0644}", Direction::LeftToRight)
+        .iter().map(|&(glyph, _)| glyph).collect();
+    let reversed: Vec<_> = glyphs("\u{0644}\u{062B}\u{0643}", Direction::RightToLeft)
+        .iter().map(|&(glyph, _)| glyph).collect();
+    assert_eq!(ltr, reversed);
+    assert_ne!(ltr, vec![8, 9, 10]);
+
+    // Latin script with Arabic text disables Arabic joining.
+    let props = SegmentProperties {
+        direction: Direction::RightToLeft,
+        script: rustybuzz::script::LATIN,
+        language: None,
+    };
+    let glyphs = rustybuzz::shape_run(&font, "\u{0643}\u{062B}\u{0644}", props, &[]);
+    let glyphs: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.codepoint).collect();
+    assert_eq!(glyphs, vec![3, 1, 2]);
+}
+
+#[test]
+fn has_feature() {
+    use rustybuzz::{script, Language, Tag};
+
+    let load = |path: &str| std::fs::read(path).unwrap();
+    let tag = |s: &[u8; 4]| Tag::from_bytes(s);
+
+    let data = load("tests/fonts/text-rendering-tests/TestShapeAran.ttf");
+    let font = Font::from_slice(&data, 0).unwrap();
+    assert!(font.has_feature(tag(b"init"), script::ARABIC, None));
+    assert!(font.has_feature(tag(b"mark"), script::ARABIC, None));
+    assert!(!font.has_feature(tag(b"stch"), script::ARABIC, None));
+
+    let data = load("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf");
+    let font = Font::from_slice(&data, 0).unwrap();
+    assert!(font.has_feature(tag(b"init"), script::ARABIC, None));
+    assert!(!font.has_feature(tag(b"stch"), script::ARABIC, None));
+
+    let data = load("tests/fonts/in-house/d9b8bc10985f24796826c29f7ccba3d0ae11ec02.ttf");
+    let font = Font::from_slice(&data, 0).unwrap();
+    assert!(font.has_feature(tag(b"stch"), script::SYRIAC, None));
+    assert!(!font.has_feature(tag(b"stch"), script::ARABIC, None));
+
+    let data = load("tests/fonts/text-rendering-tests/Selawik-variable.ttf");
+    let font = Font::from_slice(&data, 0).unwrap();
+    assert!(!font.has_feature(tag(b"init"), script::ARABIC, None));
+    // Selected via the `DFLT` script.
+    assert!(font.has_feature(tag(b"liga"), script::ARABIC, None));
+    assert!(font.has_feature(tag(b"kern"), script::ARABIC, None));
+    // `locl` is present only in Turkish and a few other language systems.
+    let turkish = Language::from_str("tr").unwrap();
+    assert!(font.has_feature(tag(b"locl"), script::LATIN, Some(&turkish)));
+    assert!(!font.has_feature(tag(b"locl"), script::LATIN, None));
+}
+
+#[test]
+fn cluster_mapping_stacked_marks() {
+    // Three marks on top of LAM, followed by YEH with HAMZA.
+    let glyphs = shape(
+        "tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf",
+        "\u{0644}\u{064E}\u{0670}\u{0653}\u{0626}",
+        "",
+    );
+
+    // Right-to-left, so the last cluster is first.
+    let clusters: Vec<_> = (0..glyphs.len()).map(|i| glyphs.glyph_to_cluster(i)).collect();
+    assert_eq!(clusters, vec![8, 0, 0, 0]);
+    assert_eq!(glyphs.cluster_to_glyph_range(0), 1..4);
+    assert_eq!(glyphs.cluster_to_glyph_range(6), 1..4);
+    assert_eq!(glyphs.cluster_to_glyph_range(8), 0..1);
+}
+
+#[test]
+fn positioned_glyphs() {
+    // Three marks on top of LAM, followed by YEH with HAMZA.
+    let glyphs = shape(
+        "tests/fonts/in-house/bf39b0e91ef9807f15a9e283a21a14a209fd2cfc.ttf",
+        "\u{0644}\u{064E}\u{0670}\u{0653}\u{0626}",
+        "",
+    );
+
+    let positioned: Vec<_> = glyphs.positioned_glyphs((100, 50)).collect();
+    assert_eq!(positioned.len(), glyphs.len());
+
+    let mut pen_x = 100;
+    for (glyph, (info, pos)) in positioned.iter().zip(glyphs.glyph_infos().iter().zip(glyphs.glyph_positions())) {
+        assert_eq!(glyph.glyph_id, info.codepoint);
+        assert_eq!(glyph.cluster, info.cluster);
+        assert_eq!(glyph.x, pen_x + pos.x_offset);
+        assert_eq!(glyph.y, 50 + pos.y_offset);
+        pen_x += pos.x_advance;
+    }
+
+    // Marks do not advance the pen, so they are drawn at their offset
+    // from the same pen position as the next glyph.
+    let positions = glyphs.glyph_positions();
+    let mark = positions.iter().position(|p| p.x_advance == 0).unwrap();
+    assert!(mark + 1 < positions.len());
+    assert_eq!(positioned[mark].x - positions[mark].x_offset,
+               positioned[mark + 1].x - positions[mark + 1].x_offset);
+}
+
+#[test]
+fn cgj_blocks_mark_reordering() {
+    let font_data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let clusters = |text: &str| -> Vec<u32> {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        // Do not merge clusters, so the glyph order can be seen from them.
+        buffer.set_cluster_level(rustybuzz::BufferClusterLevel::Characters);
+        let glyphs = rustybuzz::shape(&font, &[], buffer);
+        glyphs.glyph_infos().iter().map(|info| info.cluster).collect()
+    };
+
+    // BEH + KASRA + FATHA. FATHA has a lower combining class, so it goes first.
+    // Glyphs are in visual order.
+    assert_eq!(clusters("\u{0628}\u{0650}\u{064E}"), vec![2, 4, 0]);
+
+    // COMBINING GRAPHEME JOINER keeps the original order.
+    // The joiner itself is hidden and may be removed, depending on the font.
+    let mut with_cgj = clusters("\u{0628}\u{0650}\u{034F}\u{064E}");
+    with_cgj.retain(|&c| c != 4);
+    assert_eq!(with_cgj, vec![6, 2, 0]);
+}
+
+#[test]
+fn shape_chunks() {
+    let font_path = "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf";
+    let font_data = std::fs::read(font_path).unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let text = "\u{0644}\u{0644}\u{0647} \u{0644}\u{0647} \u{0644}\u{0644}\u{0647}\u{0644}";
+
+    let glyphs = |b: &rustybuzz::GlyphBuffer| -> Vec<_> {
+        b.glyph_infos().iter().zip(b.glyph_positions())
+            .map(|(i, p)| (i.codepoint, i.cluster, p.x_advance, p.x_offset, p.y_offset))
+            .collect()
+    };
+
+    let chunks: Vec<_> = rustybuzz::shape_chunks(&font, &[], text, 4).collect();
+    assert!(chunks.len() > 1);
+
+    // Right-to-left, so chunks are in reverse visual order.
+    let chunked: Vec<_> = chunks.iter().rev().flat_map(|b| glyphs(b)).collect();
+    assert_eq!(chunked, glyphs(&shape(font_path, text, "")));
+
+    // A long word of joined BEHs can't be split, so the second chunk
+    // is doubled until it reaches the end of the text.
+    let word = "\u{0628}".repeat(1000);
+    let text = format!("\u{0628} {} \u{0628}", word);
+    let chunks: Vec<_> = rustybuzz::shape_chunks(&font, &[], &text, 4).collect();
+    let lens: Vec<_> = chunks.iter().map(|b| b.len()).collect();
+    assert_eq!(lens, vec![1, 1003]);
+    let chunked: Vec<_> = chunks.iter().rev().flat_map(|b| glyphs(b)).collect();
+    assert_eq!(chunked, glyphs(&shape(font_path, &text, "")));
+}
+
+#[test]
+fn context() {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.set_pre_context("abcdef");
+    buffer.push_str("\u{0628}");
+    buffer.set_post_context("123456");
+    assert_eq!(buffer.pre_context(), vec!['b', 'c', 'd', 'e', 'f']);
+    assert_eq!(buffer.post_context(), vec!['1', '2', '3', '4', '5']);
+
+    buffer.clear();
+    assert!(buffer.pre_context().is_empty());
+    assert!(buffer.post_context().is_empty());
+}
+
+#[test]
+fn context_joining() {
+    let font_data = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let glyph = |pre: &str, post: &str| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.set_pre_context(pre);
+        buffer.push_str("\u{0628}");
+        buffer.set_post_context(post);
+        rustybuzz::shape(&font, &[], buffer).glyph_infos()[0].codepoint
+    };
+
+    // BEH joins with BEHs from the context, even though they are not shaped.
+    let isolated = glyph("", "");
+    let initial = glyph("", "\u{0628}");
+    let medial = glyph("\u{0628}", "\u{0628}");
+    let final_ = glyph("\u{0628}", "");
+    assert_ne!(isolated, initial);
+    assert_ne!(isolated, medial);
+    assert_ne!(isolated, final_);
+    assert_ne!(initial, final_);
+
+    // Spaces do not join.
+    assert_eq!(glyph(" ", " "), isolated);
+}
+
+#[test]
+fn feature_stages() {
+    let font_data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0644}\u{0644}\u{0647}");
+    buffer.guess_segment_properties();
+
+    let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+    let stages = plan.feature_stages();
+
+    // All GSUB stages are before GPOS ones.
+    let gsub = rustybuzz::Tag::from_bytes(b"GSUB");
+    let gpos = rustybuzz::Tag::from_bytes(b"GPOS");
+    let first_gpos = stages.iter().position(|s| s.table == gpos).unwrap();
+    assert!(stages[..first_gpos].iter().all(|s| s.table == gsub));
+    assert!(stages[first_gpos..].iter().all(|s| s.table == gpos));
+
+    // The Arabic shaper has a fallback shaping pause.
+    assert!(stages.iter().any(|s| s.table == gsub && s.has_pause));
+
+    // The ligature comes from GSUB.
+    assert!(stages.iter().any(|s| s.table == gsub && !s.lookups.is_empty()));
+}
+
+#[test]
+fn needs_fallback_joining() {
+    fn plan(font_path: &str, text: &str) -> (bool, bool) {
+        let font_data = std::fs::read(font_path).unwrap();
+        let font = Font::from_slice(&font_data, 0).unwrap();
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+        (plan.needs_fallback_joining(), plan.has_stch())
+    }
+
+    // Has all the joining features.
+    let (fallback, _) = plan("tests/fonts/text-rendering-tests/TestShapeAran.ttf", "\u{0628}\u{0628}");
+    assert!(!fallback);
+
+    // Has no `isol`.
+    let (fallback, stch) = plan("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf", "\u{0628}\u{0628}");
+    assert!(fallback);
+    assert!(!stch);
+
+    // A Latin font.
+    let (fallback, _) = plan("tests/fonts/text-rendering-tests/Selawik-variable.ttf", "\u{0628}\u{0628}");
+    assert!(fallback);
+
+    // Not an Arabic text.
+    let (fallback, _) = plan("tests/fonts/text-rendering-tests/Selawik-variable.ttf", "ab");
+    assert!(!fallback);
+
+    // Syriac has no fallback, but this font has `stch`.
+    let (fallback, stch) = plan("tests/fonts/in-house/d9b8bc10985f24796826c29f7ccba3d0ae11ec02.ttf", "\u{0718}\u{070F}\u{0718}");
+    assert!(!fallback);
+    assert!(stch);
+}
+
+#[test]
+fn shape_with_plan() {
+    let font_data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0644}\u{0644}\u{0647}");
+    buffer.guess_segment_properties();
+
+    let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+
+    // The plan can be reused for runs with the same properties.
+    for text in &["\u{0644}\u{0644}\u{0647}", "\u{0644}\u{0647}"] {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        let glyphs = rustybuzz::shape_with_plan(&font, &plan, buffer, &[]).unwrap();
+
+        let expected = shape(
+            "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+            text,
+            "",
+        );
+
+        let infos = |b: &rustybuzz::GlyphBuffer| -> Vec<_> {
+            b.glyph_infos().iter().map(|i| (i.codepoint, i.cluster)).collect()
+        };
+        let positions = |b: &rustybuzz::GlyphBuffer| -> Vec<_> {
+            b.glyph_positions().iter().map(|p| (p.x_advance, p.x_offset, p.y_offset)).collect()
+        };
+
+        assert_eq!(infos(&glyphs), infos(&expected));
+        assert_eq!(positions(&glyphs), positions(&expected));
+    }
+}
+
+#[test]
+fn shape_with_plan_mismatch() {
+    use rustybuzz::{Direction, Language, PlanMismatch};
+
+    let font_data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let make_buffer = || {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("\u{0644}\u{0644}\u{0647}");
+        buffer
+    };
+
+    let mut buffer = make_buffer();
+    buffer.guess_segment_properties();
+    let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+
+    let mut buffer = make_buffer();
+    buffer.set_direction(Direction::LeftToRight);
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, buffer, &[]).err(), Some(PlanMismatch::Direction));
+
+    let mut buffer = make_buffer();
+    buffer.set_script(rustybuzz::script::SYRIAC);
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, buffer, &[]).err(), Some(PlanMismatch::Script));
+
+    let mut buffer = make_buffer();
+    buffer.set_language(Language::from_str("fa").unwrap());
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, buffer, &[]).err(), Some(PlanMismatch::Language));
+
+    let features = [Feature::from_str("-rlig").unwrap()];
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &features).err(),
+               Some(PlanMismatch::Features));
+
+    let other_data = std::fs::read("tests/fonts/in-house/1c04a16f32a39c26c851b7fc014d2e8d298ba2b8.ttf").unwrap();
+    let other_font = Font::from_slice(&other_data, 0).unwrap();
+    assert_eq!(rustybuzz::shape_with_plan(&other_font, &plan, make_buffer(), &[]).err(),
+               Some(PlanMismatch::Face));
+
+    assert!(rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &[]).is_ok());
+}
+
+#[test]
+fn disable_lookup() {
+    use rustybuzz::{ShapePlan, Tag};
+
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let gsub = Tag::from_bytes(b"GSUB");
+
+    // LAM + LAM + HEH is a single `rlig` ligature.
+    let make_buffer = || {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("\u{0644}\u{0644}\u{0647}");
+        buffer.guess_segment_properties();
+        buffer
+    };
+
+    let mut plan = ShapePlan::new(&font, &make_buffer(), &[]);
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &[]).unwrap().len(), 1);
+    assert!(plan.disabled_lookups().is_empty());
+
+    let rlig = plan.feature_stages().into_iter()
+        .find(|stage| stage.features.contains(&Tag::from_bytes(b"rlig")))
+        .unwrap();
+    for &index in &rlig.lookups {
+        assert!(plan.disable_lookup(gsub, index));
+    }
+
+    assert_eq!(rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &[]).unwrap().len(), 3);
+
+    let disabled: Vec<_> = rlig.lookups.iter().map(|&index| (gsub, index)).collect();
+    assert_eq!(plan.disabled_lookups(), disabled.as_slice());
+
+    // Other plans are not affected.
+    assert_eq!(rustybuzz::shape(&font, &[], make_buffer()).len(), 1);
+
+    // Unknown lookups and tables.
+    assert!(!plan.disable_lookup(gsub, u16::MAX));
+    assert!(!plan.disable_lookup(Tag::from_bytes(b"kern"), rlig.lookups[0]));
+    assert_eq!(plan.disabled_lookups(), disabled.as_slice());
+}
+
+#[test]
+fn tag_byte_order() {
+    use rustybuzz::Tag;
+
+    // Same as `HB_TAG('a', 'r', 'a', 'b')`.
+    let tag = Tag::from_bytes(b"arab");
+    assert_eq!(tag.as_u32(), 0x61726162);
+    assert_eq!(tag.to_bytes(), *b"arab");
+    assert_eq!(Tag(0x61726162), tag);
+
+    // Round trip via a feature.
+    let feature = Feature::from_str("stch").unwrap();
+    assert_eq!(&feature.tag.to_bytes(), b"stch");
+}
+
+#[test]
+fn word_cache() {
+    use rustybuzz::{script, Direction, SegmentProperties, WordCache};
+
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let props = SegmentProperties {
+        direction: Direction::RightToLeft,
+        script: script::ARABIC,
+        language: None,
+    };
+
+    let glyph_ids = |glyphs: &rustybuzz::GlyphBuffer| -> Vec<(u32, u32, i32)> {
+        glyphs.glyph_infos().iter().zip(glyphs.glyph_positions())
+            .map(|(info, pos)| (info.codepoint, info.cluster, pos.x_advance))
+            .collect()
+    };
+
+    let mut cache = WordCache::new(2);
+    assert!(cache.is_empty());
+
+    // LAM + LAM + HEH, twice.
+    let word = "\u{0644}\u{0644}\u{0647}";
+    let first = cache.shape(&font, &[], word, props.clone());
+    let second = cache.shape(&font, &[], word, props.clone());
+    assert_eq!(glyph_ids(&first), glyph_ids(&second));
+    assert_eq!(glyph_ids(&first), glyph_ids(&rustybuzz::shape_run(&font, word, props.clone(), &[])));
+    assert_eq!((cache.hits(), cache.misses(), cache.len()), (1, 1, 1));
+
+    // Features are a part of the key.
+    let no_rlig = [Feature::from_str("-rlig").unwrap()];
+    let unligated = cache.shape(&font, &no_rlig, word, props.clone());
+    assert_ne!(glyph_ids(&unligated), glyph_ids(&first));
+    assert_eq!((cache.hits(), cache.misses(), cache.len()), (1, 2, 2));
+
+    // The oldest word is evicted when the cache is full.
+    cache.shape(&font, &[], "\u{0628}", props.clone());
+    assert_eq!(cache.len(), 2);
+    cache.shape(&font, &[], word, props.clone());
+    assert_eq!((cache.hits(), cache.misses()), (1, 4));
+
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(cache.capacity(), 2);
+
+    // Overridden advances are a part of the key.
+    let mut font = Font::from_slice(&data, 0).unwrap();
+    let advance = cache.shape(&font, &[], word, props.clone()).glyph_positions()[0].x_advance;
+    font.set_glyph_h_advance_func(|_| 100);
+    let glyphs = cache.shape(&font, &[], word, props.clone());
+    assert_ne!(advance, 100);
+    assert_eq!(glyphs.glyph_positions()[0].x_advance, 100);
+
+    // Another font with the same data is a different font.
+    let other_font = Font::from_slice(&data, 0).unwrap();
+    assert_eq!(cache.shape(&other_font, &[], word, props.clone()).glyph_positions()[0].x_advance, advance);
+    assert_eq!(cache.misses(), 7);
+}
+
+#[test]
+fn utf16_clusters() {
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    // LAM + FATHA + ARABIC MATHEMATICAL BEH + ALEF, with UTF-16 code unit offsets.
+    // The third character is a surrogate pair.
+    let text = "\u{0644}\u{064E}\u{1EE01}\u{0627}";
+    let mut buffer = UnicodeBuffer::new();
+    let mut offset = 0;
+    for c in text.chars() {
+        buffer.push_char_with_cluster(c, offset);
+        offset += c.len_utf16() as u32;
+    }
+
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+    let clusters: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.cluster).collect();
+    // FATHA is merged into the LAM cluster. Right-to-left, so clusters decrease.
+    assert_eq!(clusters, vec![4, 2, 0, 0]);
+
+    // Clusters can be changed after pushing.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    for (i, cluster) in [0, 1, 2, 4].iter().enumerate() {
+        buffer.set_cluster(i, *cluster);
+    }
+
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+    let same: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.cluster).collect();
+    assert_eq!(same, clusters);
+}
+
+#[test]
+fn push_utf16_and_utf32() {
+    let font = "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf";
+    let data = std::fs::read(font).unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    // BEH + ARABIC MATHEMATICAL BEH + BEH. The second character is a surrogate pair.
+    let text = "\u{0628}\u{1EE01}\u{0628}";
+    let glyph_ids = |buffer: UnicodeBuffer| -> Vec<(u32, u32)> {
+        rustybuzz::shape(&font, &[], buffer).glyph_infos().iter()
+            .map(|info| (info.codepoint, info.cluster))
+            .collect()
+    };
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    let utf8 = glyph_ids(buffer);
+
+    let utf16: Vec<u16> = text.encode_utf16().collect();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_utf16(&utf16);
+    assert_eq!(buffer.len(), 3);
+    let glyphs = glyph_ids(buffer);
+    assert_eq!(glyphs.iter().map(|g| g.0).collect::<Vec<_>>(), utf8.iter().map(|g| g.0).collect::<Vec<_>>());
+    assert_eq!(glyphs.iter().map(|g| g.1).collect::<Vec<_>>(), vec![3, 1, 0]);
+
+    let utf32: Vec<u32> = text.chars().map(|c| c as u32).collect();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_utf32(&utf32);
+    let glyphs = glyph_ids(buffer);
+    assert_eq!(glyphs.iter().map(|g| g.0).collect::<Vec<_>>(), utf8.iter().map(|g| g.0).collect::<Vec<_>>());
+    assert_eq!(glyphs.iter().map(|g| g.1).collect::<Vec<_>>(), vec![2, 1, 0]);
+
+    // Unpaired surrogates and invalid values are replaced. The replacement character
+    // doesn't join, so BEHs around it are isolated, just like around U+FFFD itself.
+    let mut expected = UnicodeBuffer::new();
+    expected.push_str("\u{0628}\u{FFFD}\u{0628}");
+    let expected: Vec<_> = glyph_ids(expected).iter().map(|g| g.0).collect();
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_utf16(&[0x0628, 0xD83B, 0x0628]);
+    assert_eq!(glyph_ids(buffer).iter().map(|g| g.0).collect::<Vec<_>>(), expected);
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_utf32(&[0x0628, 0xD800, 0x0628]);
+    assert_eq!(glyph_ids(buffer).iter().map(|g| g.0).collect::<Vec<_>>(), expected);
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_utf32(&[0x0628, 0x110000, 0x0628]);
+    assert_eq!(glyph_ids(buffer).iter().map(|g| g.0).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn shape_glyph_content() {
+    extern "C" {
+        fn hb_buffer_set_content_type(buffer: *mut std::os::raw::c_void, content_type: u32);
+    }
+
+    const HB_BUFFER_CONTENT_TYPE_GLYPHS: u32 = 2;
+
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+
+    let make_buffer = || {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("\u{0644}\u{0644}\u{0647}");
+        unsafe { hb_buffer_set_content_type(buffer.as_mut_ptr(), HB_BUFFER_CONTENT_TYPE_GLYPHS) };
+        buffer
+    };
+
+    let result = rustybuzz::shape_with_result(&font, &[], make_buffer());
+    assert!(result.had_error);
+    // Returned as is.
+    let codepoints: Vec<_> = result.glyph_infos().iter().map(|info| info.codepoint).collect();
+    assert_eq!(codepoints, vec![0x0644, 0x0644, 0x0647]);
+
+    let glyphs = rustybuzz::shape(&font, &[], make_buffer());
+    let codepoints: Vec<_> = glyphs.glyph_infos().iter().map(|info| info.codepoint).collect();
+    assert_eq!(codepoints, vec![0x0644, 0x0644, 0x0647]);
+
+    let mut buffer = make_buffer();
+    buffer.guess_segment_properties();
+    let plan = rustybuzz::ShapePlan::new(&font, &buffer, &[]);
+    let glyphs = rustybuzz::shape_with_plan(&font, &plan, make_buffer(), &[]).unwrap();
+    assert_eq!(glyphs.len(), 3);
+
+    // An empty buffer is fine.
+    let mut buffer = UnicodeBuffer::new();
+    unsafe { hb_buffer_set_content_type(buffer.as_mut_ptr(), HB_BUFFER_CONTENT_TYPE_GLYPHS) };
+    assert!(rustybuzz::shape(&font, &[], buffer).is_empty());
+}
+
+#[test]
+fn default_script_fallback() {
+    use rustybuzz::{Direction, ShapePlan, Tag};
+
+    let plan = |font_path: &str, text: &str, direction: Direction| {
+        let data = std::fs::read(font_path).unwrap();
+        let font = Font::from_slice(&data, 0).unwrap();
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(direction);
+        let plan = ShapePlan::new(&font, &buffer, &[]);
+        let features: Vec<_> = plan.feature_stages().into_iter().flat_map(|s| s.features).collect();
+        (plan.chosen_script(Tag::from_bytes(b"GSUB")), plan.chosen_script(Tag::from_bytes(b"GPOS")), features)
+    };
+
+    let tag = |s: &[u8; 4]| Tag::from_bytes(s);
+
+    // The font has an Arabic script.
+    let (gsub, _, _) = plan("tests/fonts/text-rendering-tests/TestShapeAran.ttf", "\u{0628}", Direction::RightToLeft);
+    assert_eq!(gsub, Some(tag(b"arab")));
+
+    // No Arabic script in the font, so the `DFLT` one is used,
+    // and its default features are still applied to Arabic text.
+    let selawik = "tests/fonts/text-rendering-tests/Selawik-variable.ttf";
+    let (gsub, gpos, features) = plan(selawik, "\u{0628}", Direction::RightToLeft);
+    assert_eq!(gsub, Some(tag(b"DFLT")));
+    assert_eq!(gpos, Some(tag(b"DFLT")));
+    assert!(features.contains(&tag(b"liga")));
+    assert!(features.contains(&tag(b"kern")));
+
+    // The same for text with an unset script.
+    let (gsub, _, features) = plan(selawik, "123", Direction::LeftToRight);
+    assert_eq!(gsub, Some(tag(b"DFLT")));
+    assert!(features.contains(&tag(b"liga")));
+
+    // Not a layout table.
+    let data = std::fs::read(selawik).unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("a");
+    buffer.guess_segment_properties();
+    assert_eq!(ShapePlan::new(&font, &buffer, &[]).chosen_script(tag(b"kern")), None);
+}
+
+#[test]
+fn shape_plan_matches() {
+    use rustybuzz::{script, Direction, Language, SegmentProperties, ShapePlan, Variation};
+
+    let data = std::fs::read("tests/fonts/text-rendering-tests/Selawik-variable.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let other_data = std::fs::read("tests/fonts/text-rendering-tests/TestShapeAran.ttf").unwrap();
+    let other_font = Font::from_slice(&other_data, 0).unwrap();
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("abc");
+    buffer.guess_segment_properties();
+    let props = SegmentProperties {
+        direction: Direction::LeftToRight,
+        script: script::LATIN,
+        language: buffer.language(),
+    };
+    let kern = [Feature::from_str("-kern").unwrap()];
+
+    let plan = ShapePlan::new(&font, &buffer, &[]);
+    assert!(plan.matches(&font, &props, &[]));
+    assert!(!plan.matches(&font, &props, &kern));
+    assert!(!plan.matches(&other_font, &props, &[]));
+    assert!(!plan.matches(&font, &SegmentProperties { direction: Direction::RightToLeft, ..props.clone() }, &[]));
+    assert!(!plan.matches(&font, &SegmentProperties { script: script::ARABIC, ..props.clone() }, &[]));
+    let turkish = SegmentProperties { language: Some(Language::from_str("tr").unwrap()), ..props.clone() };
+    assert!(!plan.matches(&font, &turkish, &[]));
+
+    // Equal inputs produce equal plans.
+    assert!(plan == ShapePlan::new(&font, &buffer, &[]));
+    assert!(plan != ShapePlan::new(&font, &buffer, &kern));
+
+    // A font with different variations is a different font.
+    let mut bold_font = Font::from_slice(&data, 0).unwrap();
+    bold_font.set_variations(&[Variation::from_str("wght=700").unwrap()]);
+    assert!(!plan.matches(&bold_font, &props, &[]));
+    assert!(plan != ShapePlan::new(&bold_font, &buffer, &[]));
+
+    // Disabled lookups are a part of the plan.
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0644}\u{0644}\u{0647}");
+    buffer.guess_segment_properties();
+    let props = SegmentProperties {
+        direction: buffer.direction(),
+        script: buffer.script(),
+        language: buffer.language(),
+    };
+
+    let plan = ShapePlan::new(&font, &buffer, &[]);
+    let mut other_plan = ShapePlan::new(&font, &buffer, &[]);
+    let lookup = plan.feature_stages().into_iter()
+        .find(|stage| stage.features.contains(&rustybuzz::Tag::from_bytes(b"rlig")))
+        .unwrap()
+        .lookups[0];
+    assert!(other_plan.disable_lookup(rustybuzz::Tag::from_bytes(b"GSUB"), lookup));
+    assert!(plan.matches(&font, &props, &[]));
+    assert!(!other_plan.matches(&font, &props, &[]));
+    assert!(plan != other_plan);
+}
+
+#[test]
+fn mark_positioning_features() {
+    use rustybuzz::{ShapePlan, Tag};
+
+    let data = std::fs::read("tests/fonts/in-house/f499fbc23865022234775c43503bba2e63978fe1.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{09B0}\u{09CD}\u{09A5}\u{09CD}\u{09AF}\u{09C0}");
+    buffer.guess_segment_properties();
+    let plan = ShapePlan::new(&font, &buffer, &[]);
+
+    // All GPOS features, including `abvm`, are applied together with `mark` and `mkmk`,
+    // in the lookup order.
+    let gpos: Vec<_> = plan.feature_stages().into_iter()
+        .filter(|stage| stage.table == Tag::from_bytes(b"GPOS"))
+        .collect();
+    assert_eq!(gpos.len(), 1);
+    assert!(gpos[0].features.contains(&Tag::from_bytes(b"abvm")));
+    assert!(gpos[0].lookups.windows(2).all(|w| w[0] < w[1]));
+
+    // Disabled like any other default feature.
+    let plan = ShapePlan::new(&font, &buffer, &[Feature::from_str("-abvm").unwrap()]);
+    assert!(plan.feature_stages().iter().all(|stage| !stage.features.contains(&Tag::from_bytes(b"abvm"))));
+}
+
+#[test]
+fn variation_axes() {
+    use rustybuzz::{Tag, Variation};
+
+    let data = std::fs::read("tests/fonts/text-rendering-tests/Selawik-variable.ttf").unwrap();
+    let mut font = Font::from_slice(&data, 0).unwrap();
+    let axes = font.variation_axes();
+    assert_eq!(axes.len(), 1);
+    assert_eq!(axes[0].tag, Tag::from_bytes(b"wght"));
+    assert_eq!((axes[0].min, axes[0].default, axes[0].max), (300.0, 400.0, 700.0));
+    assert_eq!(axes[0].name_id, 256);
+    assert_eq!(axes[0].name.as_deref(), Some("Weight"));
+
+    // Selawik has `HVAR`, so advances depend on the weight.
+    let width = |font: &Font| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("Selawik");
+        let glyphs = rustybuzz::shape(font, &[], buffer);
+        glyphs.glyph_positions().iter().map(|p| p.x_advance).sum::<i32>()
+    };
+    let regular = width(&font);
+    font.set_variations(&[Variation { tag: axes[0].tag, value: axes[0].max }]);
+    assert!(width(&font) > regular);
+
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    assert!(Font::from_slice(&data, 0).unwrap().variation_axes().is_empty());
+}
+
+#[test]
+fn named_instances() {
+    use rustybuzz::{Tag, Variation};
+
+    let data = std::fs::read("tests/fonts/text-rendering-tests/Selawik-variable.ttf").unwrap();
+    let mut font = Font::from_slice(&data, 0).unwrap();
+    let instances = font.named_instances();
+    let names: Vec<_> = instances.iter().map(|i| i.name.as_deref().unwrap()).collect();
+    assert_eq!(names, ["Light", "Semilight", "Regular", "Semibold", "Bold"]);
+    assert_eq!(instances[4].subfamily_name_id, 261);
+    assert_eq!(instances[4].postscript_name_id, None);
+    assert_eq!(instances[4].coordinates, [Variation { tag: Tag::from_bytes(b"wght"), value: 700.0 }]);
+
+    let width = |font: &Font| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("Selawik");
+        let glyphs = rustybuzz::shape(font, &[], buffer);
+        glyphs.glyph_positions().iter().map(|p| p.x_advance).sum::<i32>()
+    };
+    let regular = width(&font);
+    assert!(font.set_named_instance(4));
+    assert!(width(&font) > regular);
+    assert!(font.set_named_instance(2));
+    assert_eq!(width(&font), regular);
+
+    assert!(!font.set_named_instance(5));
+    assert_eq!(width(&font), regular);
+}
+
+#[test]
+fn message_func_stch() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let font_data = std::fs::read("tests/fonts/in-house/d9b8bc10985f24796826c29f7ccba3d0ae11ec02.ttf").unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+
+    let messages = Rc::new(RefCell::new(Vec::new()));
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0718}\u{070F}\u{0718}\u{0718}\u{002E}");
+    {
+        let messages = messages.clone();
+        buffer.set_message_func(move |msg, _| {
+            if msg.starts_with("stch ") {
+                messages.borrow_mut().push(msg.to_string());
+            }
+
+            true
+        });
+    }
+
+    let glyphs = rustybuzz::shape(&font, &[], buffer);
+
+    // Traced only in debug builds.
+    let messages = messages.borrow();
+    if !cfg!(debug_assertions) {
+        assert!(messages.is_empty());
+        return;
+    }
+
+    assert_eq!(messages.len(), 1);
+    let value = |name: &str| -> i32 {
+        let prefix = format!("{}=", name);
+        messages[0].split(' ')
+            .find_map(|field| field.strip_prefix(prefix.as_str()))
+            .unwrap().parse().unwrap()
+    };
+    assert!(value("w_total") > 0);
+    assert!(value("n_repeating") > 0);
+    // Four other glyphs, plus each repeating tile and its `n_copies` copies.
+    assert!(glyphs.len() as i32 >= 4 + value("n_repeating") * (1 + value("n_copies")));
+}
+
+#[test]
+fn glyph_h_advance_func() {
+    let data = std::fs::read("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf").unwrap();
+    let mut font = Font::from_slice(&data, 0).unwrap();
+    font.set_glyph_h_advance_func(|_| 500);
+
+    // LAM + LAM + HEH is a single ligature.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str("\u{0644}\u{0644}\u{0647}");
+    let mut glyphs = rustybuzz::shape(&font, &[], buffer);
+    glyphs.set_nominal_advances(&font);
+    assert_eq!(glyphs.len(), 1);
+    assert_eq!(glyphs.glyph_positions()[0].x_advance, 500);
+}
+
+#[test]
+fn insert_and_remove_glyphs() {
+    use rustybuzz::{GlyphInfo, GlyphPosition};
+
+    let clusters = |glyphs: &rustybuzz::GlyphBuffer| -> Vec<u32> {
+        glyphs.glyph_infos().iter().map(|info| info.cluster).collect()
+    };
+
+    let mut glyphs = shape("tests/fonts/text-rendering-tests/Selawik-variable.ttf", "abc", "");
+    assert_eq!(clusters(&glyphs), vec![0, 1, 2]);
+
+    // A hyphen with a too large cluster is clamped between its neighbors.
+    let hyphen = glyphs.glyph_infos()[0].codepoint;
+    assert!(glyphs.insert_glyph(1, GlyphInfo::new(hyphen, 5), GlyphPosition::new(300, 0, 0, 0)));
+    assert_eq!(clusters(&glyphs), vec![0, 1, 1, 2]);
+    assert_eq!(glyphs.glyph_positions()[1].x_advance, 300);
+    assert_eq!(glyphs.glyph_positions().len(), 4);
+
+    // The first cluster is merged into the next one.
+    glyphs.remove_glyph(0);
+    assert_eq!(clusters(&glyphs), vec![0, 0, 2]);
+    assert_eq!(glyphs.glyph_positions()[0].x_advance, 300);
+
+    // Appending at the end.
+    assert!(glyphs.insert_glyph(3, GlyphInfo::new(hyphen, 1), GlyphPosition::new(300, 0, 0, 0)));
+    assert_eq!(clusters(&glyphs), vec![0, 0, 2, 2]);
+
+    // Removing the last glyph keeps the previous cluster.
+    glyphs.remove_glyph(3);
+    glyphs.remove_glyph(2);
+    assert_eq!(clusters(&glyphs), vec![0, 0]);
+
+    // Right-to-left: LAM + LAM + HEH ligature, SPACE, LAM. Clusters decrease.
+    let mut glyphs = shape(
+        "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+        "\u{0644}\u{0644}\u{0647} \u{0644}",
+        "",
+    );
+    assert_eq!(clusters(&glyphs), vec![7, 6, 0]);
+
+    // The ligature is logically first, so the space takes over its cluster.
+    glyphs.remove_glyph(2);
+    assert_eq!(clusters(&glyphs), vec![7, 0]);
+
+    // The logical end is on the left.
+    let glyph = glyphs.glyph_infos()[0].codepoint;
+    assert!(glyphs.insert_glyph(0, GlyphInfo::new(glyph, 0), GlyphPosition::new(0, 0, 0, 0)));
+    assert_eq!(clusters(&glyphs), vec![7, 7, 0]);
+    assert!(clusters(&glyphs).windows(2).all(|w| w[0] >= w[1]));
+}
+
+#[test]
+fn split_at_cluster() {
+    use rustybuzz::SplitError;
+
+    let clusters = |glyphs: &rustybuzz::GlyphBuffer| -> Vec<u32> {
+        glyphs.glyph_infos().iter().map(|info| info.cluster).collect()
+    };
+
+    // BEH + BEH + SPACE + BEH, in visual order.
+    let glyphs = shape(
+        "tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf",
+        "\u{0628}\u{0628} \u{0628}",
+        "",
+    );
+    assert_eq!(clusters(&glyphs), vec![5, 4, 2, 0]);
+
+    // Safe between words. The first word is at the end of the buffer.
+    let (first, second) = glyphs.split_at_cluster(4).unwrap();
+    assert_eq!(clusters(&first), vec![2, 0]);
+    assert_eq!(clusters(&second), vec![5, 4]);
+    assert_eq!(first.glyph_infos()[0].codepoint, glyphs.glyph_infos()[2].codepoint);
+    assert_eq!(first.glyph_positions()[0].x_advance, glyphs.glyph_positions()[2].x_advance);
+    assert_eq!(first.total_advance().0 + second.total_advance().0, glyphs.total_advance().0);
+
+    // Joined letters are unsafe to split.
+    assert_eq!(glyphs.split_at_cluster(2).unwrap_err(), SplitError::UnsafeToBreak);
+    // Not a cluster start.
+    assert_eq!(glyphs.split_at_cluster(1).unwrap_err(), SplitError::NotClusterStart);
+
+    // Left-to-right.
+    let glyphs = shape("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf", "ab cd", "");
+    let (first, second) = glyphs.split_at_cluster(3).unwrap();
+    assert_eq!(clusters(&first), vec![0, 1, 2]);
+    assert_eq!(clusters(&second), vec![3, 4]);
+}
+
+#[test]
+fn arabic_numerals() {
+    use rustybuzz::{script, Direction, Language};
+
+    let data = std::fs::read("tests/fonts/in-house/87765fa22133578a50b54a4aaf3d3c21a86883e2.ttf").unwrap();
+    let font = Font::from_slice(&data, 0).unwrap();
+    let shape = |features: &str| {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str("123");
+        buffer.set_script(script::ARABIC);
+        buffer.set_direction(Direction::RightToLeft);
+        buffer.set_language(Language::from_str("ar").unwrap());
+        let features: Vec<_> = features.split(',').filter(|s| !s.is_empty())
+            .map(|s| Feature::from_str(s).unwrap()).collect();
+        let glyphs = rustybuzz::shape(&font, &features, buffer);
+        glyphs.glyph_infos().iter().map(|info| info.codepoint).collect::<Vec<_>>()
+    };
+    let glyphs = |names: &[&str]| -> Vec<u32> {
+        names.iter().map(|name| font.glyph_from_name(name).unwrap()).collect()
+    };
+
+    // Off by default.
+    assert_eq!(shape(""), glyphs(&["three", "two", "one"]));
+    assert_eq!(shape("+anum"), glyphs(&["uni0663", "uni0662", "uni0661"]));
+}
+
+#[test]
+fn shape_words() {
+    use rustybuzz::{script, Direction, SegmentProperties};
+
+    let font_path = "tests/fonts/text-rendering-tests/TestShapeAran.ttf";
+    let font_data = std::fs::read(font_path).unwrap();
+    let font = Font::from_slice(&font_data, 0).unwrap();
+    let props = SegmentProperties {
+        direction: Direction::RightToLeft,
+        script: script::ARABIC,
+        language: None,
+    };
+
+    let glyphs = |b: &rustybuzz::GlyphBuffer| -> Vec<_> {
+        b.glyph_infos().iter().zip(b.glyph_positions())
+            .map(|(i, p)| (i.codepoint, p.x_advance, p.x_offset, p.y_offset))
+            .collect()
+    };
+
+    // BEHs joined across a ZWJ, which is tokenized as a separate word.
+    let words = ["\u{0628}\u{0628}", "\u{200D}", "\u{0628}"];
+    let text: String = words.concat();
+    let whole = glyphs(&rustybuzz::shape_run(&font, &text, props.clone(), &[]));
+
+    // Right-to-left, so words are in reverse visual order.
+    let shaped = rustybuzz::shape_words(&font, &[], &words, props.clone());
+    assert_eq!(shaped.len(), words.len());
+    assert_eq!(shaped[2].glyph_infos()[0].cluster, 0);
+    let joined: Vec<_> = shaped.iter().rev().flat_map(|b| glyphs(b)).collect();
+    assert_eq!(joined, whole);
+
+    // Without a context, forms at the word boundaries are different.
+    let isolated: Vec<_> = words.iter().rev()
+        .flat_map(|word| glyphs(&rustybuzz::shape_run(&font, word, props.clone(), &[])))
+        .collect();
+    assert_ne!(isolated, whole);
+}
+
+#[test]
+fn positional_form() {
+    use rustybuzz::ArabicForm;
+
+    // BEH, BEH, BEH, space, LATIN SMALL LETTER A.
+    let glyphs = shape("tests/fonts/text-rendering-tests/TestShapeAran.ttf",
+                       "\u{0628}\u{0628}\u{0628} a", "");
+    let mut forms: Vec<_> = glyphs.glyph_infos().iter()
+        .map(|info| (info.cluster, info.positional_form()))
+        .collect();
+    forms.sort_by_key(|&(cluster, _)| cluster);
+
+    assert_eq!(forms, vec![
+        (0, Some(ArabicForm::Initial)),
+        (2, Some(ArabicForm::Medial)),
+        (4, Some(ArabicForm::Final)),
+        (6, None),
+        (7, None),
+    ]);
+
+    // Not shaped by the Arabic shaper.
+    let glyphs = shape("tests/fonts/in-house/a919b33197965846f21074b24e30250d67277bce.ttf", "a", "");
+    assert_eq!(glyphs.glyph_infos()[0].positional_form(), None);
+}